@@ -0,0 +1,417 @@
+use crate::Scope;
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+#[cfg(unix)]
+use std::os::unix::fs::MetadataExt;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::RwLock,
+    time::{Duration, SystemTime},
+};
+use subtle::ConstantTimeEq;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+pub const API_KEY_PREFIX: &str = "umem_";
+const API_KEY_SECRET_BYTES: usize = 32;
+const API_KEY_STORE_FILE: &str = "api_keys.json";
+
+/// A personal access token bound to a user and a set of scopes.
+///
+/// Only the SHA-256 hash of the secret part is persisted; the plaintext key is
+/// returned once by [`ApiKeyStore::create`] and cannot be recovered afterwards.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: i64,
+    pub revoked_at: Option<i64>,
+    hash: String,
+}
+
+impl ApiKey {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+}
+
+#[derive(Default)]
+struct StoreState {
+    keys: HashMap<String, ApiKey>,
+    fingerprint: Option<FileFingerprint>,
+}
+
+/// Identifies a version of the store file. Modification times can be coarse, so the
+/// length (and inode, since every write replaces the file) is compared as well.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+struct FileFingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    #[cfg(unix)]
+    ino: u64,
+}
+
+impl From<fs::Metadata> for FileFingerprint {
+    fn from(metadata: fs::Metadata) -> Self {
+        Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            #[cfg(unix)]
+            ino: metadata.ino(),
+        }
+    }
+}
+
+/// File backed store of hashed API keys.
+///
+/// Servers re-read the file in the background when it changes on disk (see
+/// [`ApiKeyStore::spawn_reload_task`]), so keys created or revoked from the command
+/// line take effect without a restart. Verifying a key never touches the disk.
+pub struct ApiKeyStore {
+    path: PathBuf,
+    state: RwLock<StoreState>,
+}
+
+impl ApiKeyStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let store = Self {
+            path: path.as_ref().to_path_buf(),
+            state: RwLock::new(StoreState::default()),
+        };
+        store.reload()?;
+        Ok(store)
+    }
+
+    /// Opens the store at `API_KEY_STORE_PATH`, or `<data dir>/umem/api_keys.json`.
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_path()?)
+    }
+
+    fn default_path() -> Result<PathBuf> {
+        if let Ok(path) = std::env::var("API_KEY_STORE_PATH") {
+            return Ok(PathBuf::from(path));
+        }
+        Ok(dirs::data_dir()
+            .context("cannot determine data directory for api key store")?
+            .join("umem")
+            .join(API_KEY_STORE_FILE))
+    }
+
+    /// Generates a new key for `user_id` and returns it together with its plaintext form.
+    pub fn create(
+        &self,
+        user_id: &str,
+        name: &str,
+        scopes: Vec<String>,
+    ) -> Result<(ApiKey, String)> {
+        if user_id.is_empty() {
+            bail!("API key user_id cannot be empty");
+        }
+        // Keys are never granted AUTH_DEFAULT_SCOPES, so one without scopes is useless.
+        if scopes.is_empty() {
+            bail!("API key needs at least one scope");
+        }
+        for scope in &scopes {
+            scope.parse::<Scope>()?;
+        }
+        self.reload()?;
+
+        let id = Uuid::new_v4().simple().to_string();
+        let mut secret = [0u8; API_KEY_SECRET_BYTES];
+        rand::rng().fill_bytes(&mut secret);
+        let secret = URL_SAFE_NO_PAD.encode(secret);
+
+        let api_key = ApiKey {
+            id: id.clone(),
+            user_id: user_id.to_string(),
+            name: name.to_string(),
+            scopes,
+            created_at: chrono::Utc::now().timestamp(),
+            revoked_at: None,
+            hash: hash_secret(&secret),
+        };
+
+        let mut state = self.state.write().expect("api key store lock poisoned");
+        state.keys.insert(id.clone(), api_key.clone());
+        self.persist(&mut state)?;
+
+        Ok((api_key, format!("{API_KEY_PREFIX}{id}_{secret}")))
+    }
+
+    /// Returns the key matching `key` if it exists and has not been revoked, as of
+    /// the last reload.
+    pub fn verify(&self, key: &str) -> Option<ApiKey> {
+        let (id, secret) = key.strip_prefix(API_KEY_PREFIX)?.split_once('_')?;
+        let state = self.state.read().expect("api key store lock poisoned");
+        let api_key = state.keys.get(id)?;
+
+        let matches: bool = hash_secret(secret)
+            .as_bytes()
+            .ct_eq(api_key.hash.as_bytes())
+            .into();
+        if !matches || api_key.is_revoked() {
+            return None;
+        }
+
+        Some(api_key.clone())
+    }
+
+    pub fn revoke(&self, id: &str) -> Result<ApiKey> {
+        self.reload()?;
+
+        let mut state = self.state.write().expect("api key store lock poisoned");
+        let api_key = state
+            .keys
+            .get_mut(id)
+            .with_context(|| format!("API key {id} not found"))?;
+        if api_key.revoked_at.is_none() {
+            api_key.revoked_at = Some(chrono::Utc::now().timestamp());
+        }
+        let api_key = api_key.clone();
+        self.persist(&mut state)?;
+
+        Ok(api_key)
+    }
+
+    pub fn list(&self, user_id: Option<&str>) -> Result<Vec<ApiKey>> {
+        self.reload()?;
+
+        let state = self.state.read().expect("api key store lock poisoned");
+        let mut keys = state
+            .keys
+            .values()
+            .filter(|key| user_id.is_none_or(|user_id| key.user_id == user_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|key| key.created_at);
+        Ok(keys)
+    }
+
+    /// Reloads the store every `interval` on the blocking pool until the runtime
+    /// shuts down. A file that cannot be read keeps the keys loaded before.
+    pub fn spawn_reload_task(&'static self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match tokio::task::spawn_blocking(|| self.reload()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => warn!("Failed to reload api key store: {:#}", e),
+                    Err(e) => warn!("Api key store reload panicked: {}", e),
+                }
+            }
+        })
+    }
+
+    /// Re-reads the file if it changed since it was last read or written.
+    pub fn reload(&self) -> Result<()> {
+        let fingerprint = match fs::metadata(&self.path) {
+            Ok(metadata) => Some(FileFingerprint::from(metadata)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        if self
+            .state
+            .read()
+            .expect("api key store lock poisoned")
+            .fingerprint
+            == fingerprint
+        {
+            return Ok(());
+        }
+
+        debug!("Loading api keys from {}", self.path.display());
+        let keys: Vec<ApiKey> = serde_json::from_slice(&fs::read(&self.path)?)
+            .with_context(|| format!("invalid api key store {}", self.path.display()))?;
+
+        let mut state = self.state.write().expect("api key store lock poisoned");
+        state.keys = keys.into_iter().map(|key| (key.id.clone(), key)).collect();
+        state.fingerprint = fingerprint;
+        Ok(())
+    }
+
+    fn persist(&self, state: &mut StoreState) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut keys = state.keys.values().collect::<Vec<_>>();
+        keys.sort_by_key(|key| key.created_at);
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(&keys)?)?;
+        fs::rename(&tmp_path, &self.path)?;
+
+        state.fingerprint = Some(fs::metadata(&self.path)?.into());
+        Ok(())
+    }
+}
+
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn open_store(dir: &TempDir) -> ApiKeyStore {
+        ApiKeyStore::open(dir.path().join(API_KEY_STORE_FILE)).unwrap()
+    }
+
+    #[test]
+    fn test_create_and_verify() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = open_store(&dir);
+        let (api_key, plaintext) = store.create("user-1", "ci", vec!["memories:read".into()])?;
+
+        assert!(plaintext.starts_with(API_KEY_PREFIX));
+        let verified = store.verify(&plaintext).expect("key should verify");
+        assert_eq!(verified.id, api_key.id);
+        assert_eq!(verified.user_id, "user-1");
+        assert_eq!(verified.scopes, vec!["memories:read".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_unknown_scopes() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = open_store(&dir);
+        assert!(
+            store
+                .create("user-1", "ci", vec!["memories:everything".into()])
+                .is_err()
+        );
+        assert!(store.list(None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_keys_without_scopes() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = open_store(&dir);
+        assert!(store.create("user-1", "ci", vec![]).is_err());
+        assert!(store.list(None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_keys_stored_without_scopes_get_no_default_grant() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = open_store(&dir);
+        let (api_key, _) = store.create("user-1", "ci", vec!["memories:read".into()])?;
+        let principal = crate::Principal::from(ApiKey {
+            scopes: Vec::new(),
+            ..api_key
+        });
+        assert!(principal.scopes.is_empty());
+        assert!(!principal.allows(Scope::MemoriesRead));
+
+        let token = crate::Principal::new("user-1".to_string(), ["openid"]);
+        assert!(token.allows(Scope::MemoriesRead));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plaintext_is_not_persisted() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = open_store(&dir);
+        let (_, plaintext) = store.create("user-1", "ci", vec!["memories:read".into()])?;
+
+        let contents = fs::read_to_string(dir.path().join(API_KEY_STORE_FILE))?;
+        let (_, secret) = plaintext
+            .strip_prefix(API_KEY_PREFIX)
+            .and_then(|key| key.split_once('_'))
+            .unwrap();
+        assert!(!contents.contains(secret));
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_tampered_and_unknown_keys() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = open_store(&dir);
+        let (api_key, plaintext) = store.create("user-1", "ci", vec!["memories:read".into()])?;
+
+        assert!(store.verify(&format!("{plaintext}x")).is_none());
+        assert!(
+            store
+                .verify(&format!("{API_KEY_PREFIX}{}_secret", api_key.id))
+                .is_none()
+        );
+        assert!(store.verify("umem_unknown_secret").is_none());
+        assert!(store.verify("not-an-api-key").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_revoked_keys_are_rejected() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = open_store(&dir);
+        let (api_key, plaintext) = store.create("user-1", "ci", vec!["memories:read".into()])?;
+
+        store.revoke(&api_key.id)?;
+        assert!(store.verify(&plaintext).is_none());
+        assert!(store.list(Some("user-1"))?[0].is_revoked());
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_are_visible_to_other_instances() -> Result<()> {
+        let dir = TempDir::new()?;
+        let server = open_store(&dir);
+        let cli = open_store(&dir);
+
+        let (api_key, plaintext) = cli.create("user-1", "ci", vec!["memories:read".into()])?;
+        assert!(server.verify(&plaintext).is_none());
+        server.reload()?;
+        assert!(server.verify(&plaintext).is_some());
+
+        cli.revoke(&api_key.id)?;
+        server.reload()?;
+        assert!(server.verify(&plaintext).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_file_is_an_error() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join(API_KEY_STORE_FILE);
+        fs::write(&path, "not json")?;
+        assert!(ApiKeyStore::open(&path).is_err());
+
+        let store = open_store(&TempDir::new()?);
+        let (_, plaintext) = store.create("user-1", "ci", vec!["memories:read".into()])?;
+        fs::write(&store.path, "{")?;
+        assert!(store.reload().is_err());
+        // The keys loaded before are kept
+        assert!(store.verify(&plaintext).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_filters_by_user() -> Result<()> {
+        let dir = TempDir::new()?;
+        let store = open_store(&dir);
+        store.create("user-1", "first", vec!["memories:read".into()])?;
+        store.create("user-2", "second", vec!["memories:read".into()])?;
+
+        assert_eq!(store.list(None)?.len(), 2);
+        let keys = store.list(Some("user-2"))?;
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].name, "second");
+        Ok(())
+    }
+}
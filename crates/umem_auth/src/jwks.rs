@@ -0,0 +1,380 @@
+use anyhow::{Context, Result, anyhow, bail};
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use reqwest::{Client, header::CACHE_CONTROL, header::HeaderMap};
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+use tokio::{
+    sync::{Mutex, RwLock},
+    task::JoinHandle,
+    time::Instant,
+};
+use tracing::{debug, info, warn};
+
+#[derive(Clone, Debug)]
+pub struct JwksCacheConfig {
+    /// Lifetime of a key set when the provider sends no `Cache-Control: max-age`.
+    pub default_ttl: Duration,
+    /// Bounds applied to the provider's `max-age`.
+    pub min_ttl: Duration,
+    pub max_ttl: Duration,
+    /// Minimum time between two fetches, also used as the retry delay after a failure.
+    pub refresh_cooldown: Duration,
+    pub request_timeout: Duration,
+}
+
+impl Default for JwksCacheConfig {
+    fn default() -> Self {
+        Self {
+            default_ttl: Duration::from_secs(15 * 60),
+            min_ttl: Duration::from_secs(60),
+            max_ttl: Duration::from_secs(24 * 60 * 60),
+            refresh_cooldown: Duration::from_secs(30),
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[derive(Default)]
+struct CacheState {
+    jwks: Option<JwkSet>,
+    expires_at: Option<Instant>,
+    last_attempt: Option<Instant>,
+}
+
+/// Caches the identity provider's JWKS and keeps it up to date.
+///
+/// The key set is refreshed when it expires, on a background schedule, and when a
+/// token references an unknown `kid` (rate limited by `refresh_cooldown`). If the
+/// provider is unavailable the last good key set keeps being served.
+pub struct JwksCache {
+    jwks_url: String,
+    client: Client,
+    config: JwksCacheConfig,
+    state: RwLock<CacheState>,
+    refresh_lock: Mutex<()>,
+}
+
+impl JwksCache {
+    pub fn new(jwks_url: String, config: JwksCacheConfig) -> Result<Self> {
+        let client = Client::builder().timeout(config.request_timeout).build()?;
+        Ok(Self {
+            jwks_url,
+            client,
+            config,
+            state: RwLock::new(CacheState::default()),
+            refresh_lock: Mutex::new(()),
+        })
+    }
+
+    /// Returns the key with id `kid`, refreshing the key set if needed.
+    pub async fn find(&self, kid: &str) -> Result<Jwk> {
+        if self.is_expired().await {
+            let _ = self.refresh().await;
+        }
+
+        if let Some(jwk) = self.lookup(kid).await {
+            return Ok(jwk);
+        }
+
+        debug!("Unknown kid {}, refreshing jwks", kid);
+        let _ = self.refresh().await;
+        self.lookup(kid)
+            .await
+            .ok_or_else(|| anyhow!("No matching kid found in jwks"))
+    }
+
+    /// Fetches the key set unless another fetch happened within `refresh_cooldown`.
+    pub async fn refresh(&self) -> Result<()> {
+        let _guard = self.refresh_lock.lock().await;
+
+        let last_attempt = self.state.read().await.last_attempt;
+        if last_attempt.is_some_and(|last| last.elapsed() < self.config.refresh_cooldown) {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        self.state.write().await.last_attempt = Some(now);
+
+        match self.fetch().await {
+            Ok((jwks, ttl)) => {
+                debug!("Fetched {} keys from {}", jwks.keys.len(), self.jwks_url);
+                let mut state = self.state.write().await;
+                state.jwks = Some(jwks);
+                state.expires_at = Some(now + ttl);
+                Ok(())
+            }
+            Err(e) => {
+                let mut state = self.state.write().await;
+                if state.jwks.is_some() {
+                    warn!("Failed to refresh jwks, serving last good key set: {}", e);
+                    state.expires_at = Some(now + self.config.refresh_cooldown);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Keeps the key set fresh in the background until the cache is dropped.
+    pub fn spawn_refresh_task(self: &Arc<Self>) -> JoinHandle<()> {
+        let cache: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            loop {
+                let delay = match cache.upgrade() {
+                    Some(cache) => cache.next_refresh_delay().await,
+                    None => break,
+                };
+                tokio::time::sleep(delay).await;
+
+                let Some(cache) = cache.upgrade() else {
+                    break;
+                };
+                if let Err(e) = cache.refresh().await {
+                    warn!("Scheduled jwks refresh failed: {}", e);
+                }
+            }
+            info!("Jwks refresh task stopped");
+        })
+    }
+
+    async fn next_refresh_delay(&self) -> Duration {
+        let state = self.state.read().await;
+        let delay = state
+            .expires_at
+            .map(|expires_at| expires_at.saturating_duration_since(Instant::now()))
+            .unwrap_or_default();
+        delay.max(self.config.refresh_cooldown)
+    }
+
+    async fn is_expired(&self) -> bool {
+        match self.state.read().await.expires_at {
+            Some(expires_at) => Instant::now() >= expires_at,
+            None => true,
+        }
+    }
+
+    async fn lookup(&self, kid: &str) -> Option<Jwk> {
+        let state = self.state.read().await;
+        state.jwks.as_ref()?.find(kid).cloned()
+    }
+
+    async fn fetch(&self) -> Result<(JwkSet, Duration)> {
+        let response = self.client.get(&self.jwks_url).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("{} returned with status-code {}", self.jwks_url, status);
+        }
+
+        let ttl = max_age(response.headers())
+            .unwrap_or(self.config.default_ttl)
+            .clamp(self.config.min_ttl, self.config.max_ttl);
+        let jwks = response.json().await.context("invalid jwks response")?;
+        Ok((jwks, ttl))
+    }
+}
+
+fn max_age(headers: &HeaderMap) -> Option<Duration> {
+    let cache_control = headers.get(CACHE_CONTROL)?.to_str().ok()?;
+    cache_control.split(',').find_map(|directive| {
+        let directive = directive.trim();
+        if directive == "no-cache" || directive == "no-store" {
+            return Some(Duration::ZERO);
+        }
+        directive
+            .strip_prefix("max-age=")?
+            .parse()
+            .ok()
+            .map(Duration::from_secs)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Json, Router,
+        extract::State,
+        http::{HeaderValue, StatusCode},
+        response::{IntoResponse, Response},
+        routing::get,
+    };
+    use serde_json::json;
+    use std::sync::Mutex as StdMutex;
+
+    #[derive(Default)]
+    struct StandIn {
+        kids: Vec<&'static str>,
+        failing: bool,
+        cache_control: Option<&'static str>,
+        hits: usize,
+    }
+
+    type SharedStandIn = Arc<StdMutex<StandIn>>;
+
+    async fn serve_jwks(State(stand_in): State<SharedStandIn>) -> Response {
+        let mut stand_in = stand_in.lock().unwrap();
+        stand_in.hits += 1;
+        if stand_in.failing {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+
+        let keys = stand_in
+            .kids
+            .iter()
+            .map(|kid| json!({ "kid": kid, "kty": "RSA", "alg": "RS256", "n": "n", "e": "AQAB" }))
+            .collect::<Vec<_>>();
+        let mut response = Json(json!({ "keys": keys })).into_response();
+        if let Some(cache_control) = stand_in.cache_control {
+            response
+                .headers_mut()
+                .insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+        }
+        response
+    }
+
+    async fn start_stand_in(stand_in: StandIn) -> (String, SharedStandIn) {
+        let stand_in = Arc::new(StdMutex::new(stand_in));
+        let app = Router::new()
+            .route("/jwks", get(serve_jwks))
+            .with_state(Arc::clone(&stand_in));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{}/jwks", addr), stand_in)
+    }
+
+    fn config(refresh_cooldown: Duration) -> JwksCacheConfig {
+        JwksCacheConfig {
+            min_ttl: Duration::ZERO,
+            refresh_cooldown,
+            ..Default::default()
+        }
+    }
+
+    fn kid(jwk: &Jwk) -> &str {
+        jwk.common.key_id.as_deref().unwrap_or_default()
+    }
+
+    fn hits(stand_in: &SharedStandIn) -> usize {
+        stand_in.lock().unwrap().hits
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_cached() -> Result<()> {
+        let (url, stand_in) = start_stand_in(StandIn {
+            kids: vec!["a"],
+            ..Default::default()
+        })
+        .await;
+        let cache = JwksCache::new(url, config(Duration::ZERO))?;
+
+        assert_eq!(kid(&cache.find("a").await?), "a");
+        assert_eq!(kid(&cache.find("a").await?), "a");
+        assert_eq!(hits(&stand_in), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unknown_kid_triggers_refresh() -> Result<()> {
+        let (url, stand_in) = start_stand_in(StandIn {
+            kids: vec!["old"],
+            ..Default::default()
+        })
+        .await;
+        let cache = JwksCache::new(url, config(Duration::ZERO))?;
+        cache.find("old").await?;
+
+        stand_in.lock().unwrap().kids = vec!["old", "rotated"];
+        assert_eq!(kid(&cache.find("rotated").await?), "rotated");
+        assert_eq!(hits(&stand_in), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unknown_kid_refresh_is_rate_limited() -> Result<()> {
+        let (url, stand_in) = start_stand_in(StandIn {
+            kids: vec!["a"],
+            ..Default::default()
+        })
+        .await;
+        let cache = JwksCache::new(url, config(Duration::from_secs(60)))?;
+        cache.find("a").await?;
+
+        assert!(cache.find("unknown").await.is_err());
+        assert!(cache.find("unknown").await.is_err());
+        assert_eq!(hits(&stand_in), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_serves_last_good_keys_when_provider_fails() -> Result<()> {
+        let (url, stand_in) = start_stand_in(StandIn {
+            kids: vec!["a"],
+            cache_control: Some("max-age=0"),
+            ..Default::default()
+        })
+        .await;
+        let cache = JwksCache::new(url, config(Duration::ZERO))?;
+        cache.find("a").await?;
+
+        stand_in.lock().unwrap().failing = true;
+        assert!(cache.refresh().await.is_err());
+        assert_eq!(kid(&cache.find("a").await?), "a");
+        assert!(hits(&stand_in) >= 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_honors_cache_control() -> Result<()> {
+        let (url, stand_in) = start_stand_in(StandIn {
+            kids: vec!["a"],
+            cache_control: Some("public, max-age=0"),
+            ..Default::default()
+        })
+        .await;
+        let cache = JwksCache::new(url, config(Duration::ZERO))?;
+        cache.find("a").await?;
+        cache.find("a").await?;
+        assert_eq!(hits(&stand_in), 2);
+
+        stand_in.lock().unwrap().cache_control = Some("public, max-age=3600");
+        cache.find("a").await?;
+        cache.find("a").await?;
+        assert_eq!(hits(&stand_in), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_refresh_picks_up_rotation() -> Result<()> {
+        let (url, stand_in) = start_stand_in(StandIn {
+            kids: vec!["a"],
+            cache_control: Some("max-age=0"),
+            ..Default::default()
+        })
+        .await;
+        let cache = Arc::new(JwksCache::new(url, config(Duration::from_millis(20)))?);
+        cache.refresh().await?;
+        let task = cache.spawn_refresh_task();
+
+        stand_in.lock().unwrap().kids = vec!["b"];
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(cache.lookup("b").await.is_some());
+
+        drop(cache);
+        tokio::time::timeout(Duration::from_secs(1), task).await??;
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_age_parsing() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(max_age(&headers), None);
+
+        headers.insert(CACHE_CONTROL, "public, max-age=300".parse().unwrap());
+        assert_eq!(max_age(&headers), Some(Duration::from_secs(300)));
+
+        headers.insert(CACHE_CONTROL, "no-store".parse().unwrap());
+        assert_eq!(max_age(&headers), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,108 @@
+mod api_key;
+mod jwks;
+mod oidc;
+mod scope;
+mod token;
+
+pub use api_key::{API_KEY_PREFIX, ApiKey, ApiKeyStore};
+pub use jwks::{JwksCache, JwksCacheConfig};
+use lazy_static::lazy_static;
+pub use oidc::{OidcConfig, ProviderMetadata, discover};
+pub use scope::{Scope, Scopes};
+use std::time::Duration;
+pub use token::{Claims, TokenValidator};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+const DEFAULT_SCOPES: &str = "memories:read memories:write memories:delete";
+const DEFAULT_API_KEY_RELOAD_INTERVAL_SECS: u64 = 5;
+
+lazy_static! {
+    static ref API_KEY_STORE: Result<ApiKeyStore, String> =
+        ApiKeyStore::open_default().map_err(|e| format!("{e:#}"));
+    static ref DEFAULT_GRANT: Scopes = Scopes::from_raw(
+        std::env::var("AUTH_DEFAULT_SCOPES")
+            .as_deref()
+            .unwrap_or(DEFAULT_SCOPES)
+            .split_whitespace()
+    );
+}
+
+/// The process wide API key store, opened from `API_KEY_STORE_PATH` on first use.
+pub fn api_key_store() -> anyhow::Result<&'static ApiKeyStore> {
+    API_KEY_STORE
+        .as_ref()
+        .map_err(|e| anyhow::anyhow!("api key store failed to initialize: {e}"))
+}
+
+/// Keeps the API key store current in the background, re-reading it every
+/// `API_KEY_RELOAD_INTERVAL_SECS` seconds (default 5).
+pub fn spawn_api_key_reload_task() -> anyhow::Result<JoinHandle<()>> {
+    let interval = std::env::var("API_KEY_RELOAD_INTERVAL_SECS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_API_KEY_RELOAD_INTERVAL_SECS);
+    Ok(api_key_store()?.spawn_reload_task(Duration::from_secs(interval)))
+}
+
+/// The authenticated caller of a request.
+#[derive(Clone, Debug)]
+pub struct Principal {
+    pub user_id: String,
+    pub scopes: Scopes,
+}
+
+impl Principal {
+    /// Tokens that carry no umem scope at all are granted `AUTH_DEFAULT_SCOPES`, so
+    /// tokens from providers that know nothing about umem scopes keep working.
+    pub fn new<'a>(user_id: String, raw_scopes: impl IntoIterator<Item = &'a str>) -> Self {
+        let scopes = match Scopes::from_raw(raw_scopes) {
+            scopes if scopes.is_empty() => DEFAULT_GRANT.clone(),
+            scopes => scopes,
+        };
+        Self { user_id, scopes }
+    }
+
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.allows(scope)
+    }
+}
+
+/// API keys hold exactly the scopes they were created with.
+impl From<ApiKey> for Principal {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            user_id: api_key.user_id,
+            scopes: Scopes::from_raw(api_key.scopes.iter().map(String::as_str)),
+        }
+    }
+}
+
+impl From<Claims> for Principal {
+    fn from(claims: Claims) -> Self {
+        let scopes = claims.scopes();
+        Self::new(claims.sub.clone(), scopes)
+    }
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+/// Resolves a bearer credential, either an API key or a JWT, to its [`Principal`].
+pub async fn authenticate(validator: &TokenValidator, token: &str) -> Result<Principal, String> {
+    if is_api_key(token) {
+        let store = api_key_store().map_err(|e| {
+            warn!("{:#}", e);
+            "API keys are unavailable".to_string()
+        })?;
+        return store
+            .verify(token)
+            .map(Principal::from)
+            .ok_or_else(|| "Invalid API key".to_string());
+    }
+
+    let token_data = validator.check_token(token).await?;
+    Ok(Principal::from(token_data.claims))
+}
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result, bail};
+use jsonwebtoken::Algorithm;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{str::FromStr, time::Duration};
+use tracing::{debug, info};
+
+const DISCOVERY_PATHS: [&str; 2] = [
+    ".well-known/openid-configuration",
+    ".well-known/oauth-authorization-server",
+];
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Identity provider settings, read from the environment.
+///
+/// `WORKOS_AUTHKIT_URL` and `WORKOS_CLIENT_ID` are still honored when the generic
+/// `OIDC_*` variables are not set.
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub audiences: Vec<String>,
+    pub algorithms: Vec<Algorithm>,
+    /// Overrides the `jwks_uri` advertised by the provider.
+    pub jwks_url: Option<String>,
+}
+
+impl OidcConfig {
+    pub fn from_env() -> Result<Self> {
+        let issuer_url = env_with_fallback("OIDC_ISSUER_URL", "WORKOS_AUTHKIT_URL")
+            .context("OIDC_ISSUER_URL not set")?;
+        let audiences = split_list(
+            &env_with_fallback("OIDC_AUDIENCE", "WORKOS_CLIENT_ID")
+                .context("OIDC_AUDIENCE not set")?,
+        );
+        let algorithms =
+            split_list(&std::env::var("OIDC_ALGORITHMS").unwrap_or_else(|_| "RS256".to_string()))
+                .iter()
+                .map(|algorithm| {
+                    Algorithm::from_str(algorithm).map_err(|_| {
+                        anyhow::anyhow!("Unsupported algorithm in OIDC_ALGORITHMS: {algorithm}")
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+        if audiences.is_empty() {
+            bail!("OIDC_AUDIENCE must contain at least one audience");
+        }
+        if algorithms.is_empty() {
+            bail!("OIDC_ALGORITHMS must contain at least one algorithm");
+        }
+
+        Ok(Self {
+            issuer_url,
+            audiences,
+            algorithms,
+            jwks_url: std::env::var("JWKS_URL").ok().filter(|url| !url.is_empty()),
+        })
+    }
+}
+
+/// Authorization server metadata published by the issuer.
+///
+/// Only the fields umem relies on are typed; everything else is kept so the metadata
+/// can be re-served verbatim to MCP clients.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub jwks_uri: String,
+    #[serde(flatten)]
+    pub other: serde_json::Map<String, serde_json::Value>,
+}
+
+/// Fetches the issuer's metadata from `.well-known/openid-configuration`, falling back
+/// to the RFC 8414 `.well-known/oauth-authorization-server` document.
+pub async fn discover(issuer_url: &str) -> Result<ProviderMetadata> {
+    let client = Client::builder().timeout(DISCOVERY_TIMEOUT).build()?;
+    let issuer = normalize(issuer_url);
+
+    let mut last_error = None;
+    for path in DISCOVERY_PATHS {
+        let url = format!("{}/{}", issuer, path);
+        debug!("Fetching provider metadata from {}", url);
+        match fetch_metadata(&client, &url).await {
+            Ok(metadata) => {
+                if normalize(&metadata.issuer) != issuer {
+                    bail!(
+                        "Issuer mismatch: configured {} but provider reports {}",
+                        issuer,
+                        metadata.issuer
+                    );
+                }
+                info!("Discovered provider metadata at {}", url);
+                return Ok(metadata);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error
+        .expect("at least one discovery path")
+        .context(format!("Provider metadata discovery failed for {}", issuer)))
+}
+
+async fn fetch_metadata(client: &Client, url: &str) -> Result<ProviderMetadata> {
+    let response = client.get(url).send().await?;
+    let status = response.status();
+    if !status.is_success() {
+        bail!("{url} returned with status-code {status}");
+    }
+    Ok(response.json().await?)
+}
+
+fn normalize(url: &str) -> &str {
+    url.trim_end_matches('/')
+}
+
+fn env_with_fallback(name: &str, fallback: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(fallback))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, routing::get};
+    use serde_json::json;
+
+    async fn start_provider(router: impl FnOnce(String) -> Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let app = router(issuer.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        issuer
+    }
+
+    fn metadata(issuer: &str) -> serde_json::Value {
+        json!({
+            "issuer": issuer,
+            "jwks_uri": format!("{issuer}/keys"),
+            "authorization_endpoint": format!("{issuer}/auth"),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_discovers_openid_configuration() -> Result<()> {
+        let issuer = start_provider(|issuer| {
+            let body = metadata(&issuer);
+            Router::new().route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(body) }),
+            )
+        })
+        .await;
+
+        let discovered = discover(&format!("{issuer}/")).await?;
+        assert_eq!(discovered.jwks_uri, format!("{issuer}/keys"));
+        assert_eq!(
+            discovered.other["authorization_endpoint"],
+            json!(format!("{issuer}/auth"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_oauth_authorization_server() -> Result<()> {
+        let issuer = start_provider(|issuer| {
+            let body = metadata(&issuer);
+            Router::new().route(
+                "/.well-known/oauth-authorization-server",
+                get(move || async move { Json(body) }),
+            )
+        })
+        .await;
+
+        let discovered = discover(&issuer).await?;
+        assert_eq!(discovered.issuer, issuer);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rejects_issuer_mismatch() {
+        let issuer = start_provider(|_| {
+            let body = metadata("https://evil.example.com");
+            Router::new().route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(body) }),
+            )
+        })
+        .await;
+
+        assert!(discover(&issuer).await.is_err());
+    }
+
+    #[test]
+    fn test_split_list() {
+        assert_eq!(split_list(" a, b,,c "), vec!["a", "b", "c"]);
+        assert!(split_list("").is_empty());
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{Result, bail};
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+/// Permissions a credential can carry. `Admin` implies every other scope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Scope {
+    MemoriesRead,
+    MemoriesWrite,
+    MemoriesDelete,
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [
+        Scope::MemoriesRead,
+        Scope::MemoriesWrite,
+        Scope::MemoriesDelete,
+        Scope::Admin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::MemoriesRead => "memories:read",
+            Scope::MemoriesWrite => "memories:write",
+            Scope::MemoriesDelete => "memories:delete",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Scope::ALL.into_iter().find(|scope| scope.as_str() == s) {
+            Some(scope) => Ok(scope),
+            None => bail!("Unknown scope {s}"),
+        }
+    }
+}
+
+/// The set of umem scopes granted to a principal.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Scopes(BTreeSet<Scope>);
+
+impl Scopes {
+    /// Collects the umem scopes from `raw`, ignoring scopes meant for other services
+    /// (`openid`, `email`, ...).
+    pub fn from_raw<'a>(raw: impl IntoIterator<Item = &'a str>) -> Self {
+        Self(raw.into_iter().filter_map(|s| s.parse().ok()).collect())
+    }
+
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.0.contains(&Scope::Admin) || self.0.contains(&scope)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Scope> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+impl FromIterator<Scope> for Scopes {
+    fn from_iter<I: IntoIterator<Item = Scope>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_raw_ignores_foreign_scopes() {
+        let scopes = Scopes::from_raw(["openid", "memories:read", "email"]);
+        assert_eq!(scopes.iter().collect::<Vec<_>>(), vec![Scope::MemoriesRead]);
+    }
+
+    #[test]
+    fn test_allows() {
+        let scopes = Scopes::from_raw(["memories:read"]);
+        assert!(scopes.allows(Scope::MemoriesRead));
+        assert!(!scopes.allows(Scope::MemoriesWrite));
+        assert!(!scopes.allows(Scope::Admin));
+    }
+
+    #[test]
+    fn test_admin_implies_all() {
+        let scopes = Scopes::from_raw(["admin"]);
+        assert!(Scope::ALL.into_iter().all(|scope| scopes.allows(scope)));
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        for scope in Scope::ALL {
+            assert_eq!(scope.as_str().parse::<Scope>().unwrap(), scope);
+        }
+        assert!("memories:admin".parse::<Scope>().is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::{
+    jwks::{JwksCache, JwksCacheConfig},
+    oidc::{self, OidcConfig, ProviderMetadata},
+};
+use anyhow::Result;
+use jsonwebtoken::{
+    Algorithm, DecodingKey, TokenData, Validation, decode_header, jwk::KeyAlgorithm,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: String,
+    pub exp: usize,
+    /// Space delimited scopes (RFC 8693).
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Scopes as issued by Okta and Azure AD, either a list or a space delimited string.
+    #[serde(default)]
+    pub scp: Option<ScopeClaim>,
+    /// Auth0 RBAC permissions.
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScopeClaim {
+    Delimited(String),
+    List(Vec<String>),
+}
+
+impl Claims {
+    /// Every scope carried by the token, whichever claim it was issued in.
+    pub fn scopes(&self) -> Vec<&str> {
+        let mut scopes = self
+            .scope
+            .iter()
+            .flat_map(|scope| scope.split_whitespace())
+            .collect::<Vec<_>>();
+        match &self.scp {
+            Some(ScopeClaim::Delimited(scp)) => scopes.extend(scp.split_whitespace()),
+            Some(ScopeClaim::List(scp)) => scopes.extend(scp.iter().map(String::as_str)),
+            None => {}
+        }
+        scopes.extend(self.permissions.iter().map(String::as_str));
+        scopes
+    }
+}
+
+/// Validates bearer JWTs against the issuer, audiences and algorithms of an OIDC provider.
+pub struct TokenValidator {
+    jwks: Arc<JwksCache>,
+    issuer: String,
+    audiences: Vec<String>,
+    algorithms: Vec<Algorithm>,
+}
+
+impl TokenValidator {
+    pub fn new(
+        jwks: Arc<JwksCache>,
+        issuer: String,
+        audiences: Vec<String>,
+        algorithms: Vec<Algorithm>,
+    ) -> Self {
+        Self {
+            jwks,
+            issuer,
+            audiences,
+            algorithms,
+        }
+    }
+
+    /// Discovers the provider described by `config` and starts refreshing its key set.
+    pub async fn discover(config: OidcConfig) -> Result<(Self, ProviderMetadata)> {
+        let metadata = oidc::discover(&config.issuer_url).await?;
+        let jwks_url = config.jwks_url.unwrap_or_else(|| metadata.jwks_uri.clone());
+        info!("Using JWKS URL: {}", jwks_url);
+
+        let jwks = Arc::new(JwksCache::new(jwks_url, JwksCacheConfig::default())?);
+        if let Err(e) = jwks.refresh().await {
+            warn!("Initial jwks fetch failed, retrying on demand: {}", e);
+        }
+        jwks.spawn_refresh_task();
+
+        let validator = Self::new(
+            jwks,
+            metadata.issuer.clone(),
+            config.audiences,
+            config.algorithms,
+        );
+        Ok((validator, metadata))
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    pub async fn check_token(&self, token: &str) -> Result<TokenData<Claims>, String> {
+        let header = decode_header(token).map_err(|op| format!("JWT Header Error: {:?}", op))?;
+        if !self.algorithms.contains(&header.alg) {
+            return Err(format!("Algorithm {:?} is not allowed", header.alg));
+        }
+        let kid = header.kid.ok_or("No kid found in token header")?;
+
+        let jwk = self.jwks.find(&kid).await.map_err(|op| op.to_string())?;
+        let algorithm_mismatch = jwk
+            .common
+            .key_algorithm
+            .is_some_and(|key_algorithm| signing_algorithm(key_algorithm) != Some(header.alg));
+        if algorithm_mismatch {
+            return Err(format!("Key {} does not allow {:?}", kid, header.alg));
+        }
+
+        let decoding_key =
+            DecodingKey::from_jwk(&jwk).map_err(|op| format!("Decoding Key Error: {:?}", op))?;
+
+        // Only the header's algorithm, which is allowed above: jsonwebtoken rejects
+        // every token when the list mixes key families.
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&self.audiences);
+        validation.set_issuer(&[self.issuer.as_str()]);
+
+        let token_data = jsonwebtoken::decode::<Claims>(token, &decoding_key, &validation)
+            .map_err(|op| format!("JWT Decode Error: {:?}", op))?;
+
+        Ok(token_data)
+    }
+}
+
+/// The JWS algorithm a JWK is restricted to, if it names one.
+fn signing_algorithm(key_algorithm: KeyAlgorithm) -> Option<Algorithm> {
+    match key_algorithm {
+        KeyAlgorithm::HS256 => Some(Algorithm::HS256),
+        KeyAlgorithm::HS384 => Some(Algorithm::HS384),
+        KeyAlgorithm::HS512 => Some(Algorithm::HS512),
+        KeyAlgorithm::ES256 => Some(Algorithm::ES256),
+        KeyAlgorithm::ES384 => Some(Algorithm::ES384),
+        KeyAlgorithm::RS256 => Some(Algorithm::RS256),
+        KeyAlgorithm::RS384 => Some(Algorithm::RS384),
+        KeyAlgorithm::RS512 => Some(Algorithm::RS512),
+        KeyAlgorithm::PS256 => Some(Algorithm::PS256),
+        KeyAlgorithm::PS384 => Some(Algorithm::PS384),
+        KeyAlgorithm::PS512 => Some(Algorithm::PS512),
+        KeyAlgorithm::EdDSA => Some(Algorithm::EdDSA),
+        KeyAlgorithm::RSA1_5 | KeyAlgorithm::RSA_OAEP | KeyAlgorithm::RSA_OAEP_256 => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, routing::get};
+    use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    const SECRET: &[u8] = b"umem-test-secret-umem-test-secret";
+    const ISSUER: &str = "https://issuer.example.com";
+    const AUDIENCE: &str = "umem";
+
+    async fn start_jwks() -> Arc<JwksCache> {
+        let jwks = json!({
+            "keys": [{ "kty": "oct", "kid": "k1", "alg": "HS256", "k": URL_SAFE_NO_PAD.encode(SECRET) }]
+        });
+        let app = Router::new().route("/keys", get(move || async move { Json(jwks) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/keys", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        Arc::new(JwksCache::new(url, JwksCacheConfig::default()).unwrap())
+    }
+
+    fn sign(issuer: &str, audience: &str) -> String {
+        sign_claims(json!({
+            "sub": "user-1",
+            "iss": issuer,
+            "aud": audience,
+            "exp": chrono::Utc::now().timestamp() + 600,
+        }))
+    }
+
+    fn sign_claims(claims: serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("k1".to_string());
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(SECRET)).unwrap()
+    }
+
+    fn validator(jwks: Arc<JwksCache>, algorithms: Vec<Algorithm>) -> TokenValidator {
+        TokenValidator::new(jwks, ISSUER.into(), vec![AUDIENCE.into()], algorithms)
+    }
+
+    #[tokio::test]
+    async fn test_accepts_valid_token() {
+        let validator = validator(start_jwks().await, vec![Algorithm::HS256]);
+        let token_data = validator
+            .check_token(&sign(ISSUER, AUDIENCE))
+            .await
+            .unwrap();
+        assert_eq!(token_data.claims.sub, "user-1");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_issuer_and_audience() {
+        let validator = validator(start_jwks().await, vec![Algorithm::HS256]);
+        assert!(
+            validator
+                .check_token(&sign("https://other.example.com", AUDIENCE))
+                .await
+                .is_err()
+        );
+        assert!(validator.check_token(&sign(ISSUER, "other")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_accepts_tokens_when_algorithms_mix_key_families() {
+        let validator = validator(
+            start_jwks().await,
+            vec![Algorithm::RS256, Algorithm::ES256, Algorithm::HS256],
+        );
+        let token_data = validator
+            .check_token(&sign(ISSUER, AUDIENCE))
+            .await
+            .unwrap();
+        assert_eq!(token_data.claims.sub, "user-1");
+    }
+
+    #[tokio::test]
+    async fn test_rejects_disallowed_algorithm() {
+        let validator = validator(start_jwks().await, vec![Algorithm::RS256]);
+        assert!(
+            validator
+                .check_token(&sign(ISSUER, AUDIENCE))
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_malformed_token() {
+        let validator = validator(start_jwks().await, vec![Algorithm::HS256]);
+        assert!(validator.check_token("not-a-jwt").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_collects_scopes_from_all_claims() {
+        let validator = validator(start_jwks().await, vec![Algorithm::HS256]);
+        let token = sign_claims(json!({
+            "sub": "user-1",
+            "iss": ISSUER,
+            "aud": AUDIENCE,
+            "exp": chrono::Utc::now().timestamp() + 600,
+            "scope": "openid memories:read",
+            "scp": ["memories:write"],
+            "permissions": ["admin"],
+        }));
+        let token_data = validator.check_token(&token).await.unwrap();
+        assert_eq!(
+            token_data.claims.scopes(),
+            vec!["openid", "memories:read", "memories:write", "admin"]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+mod markdown;
+mod semantic;
+mod spans;
+
+use anyhow::{bail, Result};
+pub use markdown::markdown;
+pub use semantic::semantic;
+use spans::{char_offsets, Span};
+use umem_embeddings::Embedder;
+
+/// Estimated tokens per chunk, well within what bge-m3 embeds without truncation.
+pub const DEFAULT_MAX_TOKENS: usize = 512;
+const DEFAULT_OVERLAP_TOKENS: usize = 64;
+const DEFAULT_BREAKPOINT_PERCENTILE: f32 = 95.0;
+
+/// A piece of a longer text.
+///
+/// `text` is the source between the byte offsets `start` and `end`, except that
+/// Markdown chunks after the first of a section are prefixed with the section headings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    pub text: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Chunk {
+    fn span(source: &str, start: usize, end: usize) -> Self {
+        Self {
+            text: source[start..end].to_string(),
+            start,
+            end,
+        }
+    }
+
+    fn offset(self, by: usize) -> Self {
+        Self {
+            start: self.start + by,
+            end: self.end + by,
+            ..self
+        }
+    }
+}
+
+/// How a text is split into chunks. Token counts are estimated like
+/// [`umem_embeddings::estimate_tokens`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChunkStrategy {
+    /// Windows of `max_tokens` cut between words, each repeating the last
+    /// `overlap_tokens` of the one before.
+    FixedTokens {
+        max_tokens: usize,
+        overlap_tokens: usize,
+    },
+    /// Whole sentences packed together while they fit.
+    Sentences { max_tokens: usize },
+    /// Sections starting at Markdown headings, split between paragraphs when too long.
+    Markdown { max_tokens: usize },
+    /// Sentences grouped by topic: a chunk ends where the embedding distance between
+    /// neighbouring sentences is above the `breakpoint_percentile` of all distances.
+    Semantic {
+        max_tokens: usize,
+        breakpoint_percentile: f32,
+    },
+}
+
+impl Default for ChunkStrategy {
+    fn default() -> Self {
+        Self::Sentences {
+            max_tokens: DEFAULT_MAX_TOKENS,
+        }
+    }
+}
+
+impl ChunkStrategy {
+    /// Reads `CHUNK_STRATEGY` (`fixed`, `sentences`, `markdown` or `semantic`, default
+    /// `sentences`), `CHUNK_MAX_TOKENS`, `CHUNK_OVERLAP_TOKENS` and
+    /// `CHUNK_BREAKPOINT_PERCENTILE`, keeping the defaults for unset variables.
+    pub fn from_env() -> Result<Self> {
+        let max_tokens = env_parse("CHUNK_MAX_TOKENS")
+            .filter(|tokens| *tokens > 0)
+            .unwrap_or(DEFAULT_MAX_TOKENS);
+        let strategy = std::env::var("CHUNK_STRATEGY").unwrap_or_default();
+        Ok(match strategy.as_str() {
+            "" | "sentences" => Self::Sentences { max_tokens },
+            "fixed" => Self::FixedTokens {
+                max_tokens,
+                overlap_tokens: env_parse("CHUNK_OVERLAP_TOKENS").unwrap_or(DEFAULT_OVERLAP_TOKENS),
+            },
+            "markdown" => Self::Markdown { max_tokens },
+            "semantic" => Self::Semantic {
+                max_tokens,
+                breakpoint_percentile: env_parse("CHUNK_BREAKPOINT_PERCENTILE")
+                    .filter(|percentile| (0.0..=100.0).contains(percentile))
+                    .unwrap_or(DEFAULT_BREAKPOINT_PERCENTILE),
+            },
+            other => bail!("Unknown CHUNK_STRATEGY {other}"),
+        })
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        match *self {
+            Self::FixedTokens { max_tokens, .. }
+            | Self::Sentences { max_tokens }
+            | Self::Markdown { max_tokens }
+            | Self::Semantic { max_tokens, .. } => max_tokens,
+        }
+    }
+
+    /// Splits `text`; only the semantic strategy calls `embedder`.
+    pub async fn chunk<E>(&self, text: &str, embedder: &E) -> Result<Vec<Chunk>>
+    where
+        E: Embedder + Sync + ?Sized,
+    {
+        Ok(match *self {
+            Self::FixedTokens {
+                max_tokens,
+                overlap_tokens,
+            } => fixed_tokens(text, max_tokens, overlap_tokens),
+            Self::Sentences { max_tokens } => sentences(text, max_tokens),
+            Self::Markdown { max_tokens } => markdown(text, max_tokens),
+            Self::Semantic {
+                max_tokens,
+                breakpoint_percentile,
+            } => semantic(text, max_tokens, breakpoint_percentile, embedder).await?,
+        })
+    }
+}
+
+pub fn fixed_tokens(text: &str, max_tokens: usize, overlap_tokens: usize) -> Vec<Chunk> {
+    let words = spans::words(text);
+    let offsets = char_offsets(text, &words);
+    let tokens = |first: usize, last: usize| estimate(&offsets, first, last);
+
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    while first < words.len() {
+        let mut last = first;
+        while last + 1 < words.len() && tokens(first, last + 1) <= max_tokens {
+            last += 1;
+        }
+        chunks.push(Chunk::span(text, words[first].0, words[last].1));
+        if last + 1 == words.len() {
+            break;
+        }
+        let mut next = last + 1;
+        while next - 1 > first && tokens(next - 1, last) <= overlap_tokens {
+            next -= 1;
+        }
+        first = next;
+    }
+    chunks
+}
+
+pub fn sentences(text: &str, max_tokens: usize) -> Vec<Chunk> {
+    pack(text, &spans::sentences(text), max_tokens)
+}
+
+/// Averages the embeddings of the chunks of one text into a unit vector standing for
+/// the whole text.
+pub fn mean_embedding(embeddings: &[Vec<f32>]) -> Vec<f32> {
+    let Some(first) = embeddings.first() else {
+        return Vec::new();
+    };
+    let mut mean = vec![0.0; first.len()];
+    for embedding in embeddings {
+        for (sum, value) in mean.iter_mut().zip(embedding) {
+            *sum += value;
+        }
+    }
+    let norm = mean.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        mean.iter_mut().for_each(|value| *value /= norm);
+    }
+    mean
+}
+
+/// Packs runs of consecutive `units` into chunks of at most `max_tokens`. A unit too
+/// long on its own is split between words.
+fn pack(text: &str, units: &[Span], max_tokens: usize) -> Vec<Chunk> {
+    let offsets = char_offsets(text, units);
+    let tokens = |first: usize, last: usize| estimate(&offsets, first, last);
+
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    while first < units.len() {
+        if tokens(first, first) > max_tokens {
+            let (start, end) = units[first];
+            chunks.extend(
+                fixed_tokens(&text[start..end], max_tokens, 0)
+                    .into_iter()
+                    .map(|chunk| chunk.offset(start)),
+            );
+            first += 1;
+            continue;
+        }
+        let mut last = first;
+        while last + 1 < units.len() && tokens(first, last + 1) <= max_tokens {
+            last += 1;
+        }
+        chunks.push(Chunk::span(text, units[first].0, units[last].1));
+        first = last + 1;
+    }
+    chunks
+}
+
+/// Estimated tokens from the start of span `first` to the end of span `last`.
+fn estimate(offsets: &[(usize, usize)], first: usize, last: usize) -> usize {
+    (offsets[last].1 - offsets[first].0).div_ceil(4)
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use umem_embeddings::estimate_tokens;
+
+    fn assert_offsets(text: &str, chunks: &[Chunk]) {
+        for chunk in chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_fixed_windows_overlap() {
+        let text = (0..100)
+            .map(|n| format!("w{n:02}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let chunks = fixed_tokens(&text, 20, 5);
+        assert!(chunks.len() > 1);
+        assert!(chunks
+            .iter()
+            .all(|chunk| estimate_tokens(&chunk.text) <= 20));
+        assert_offsets(&text, &chunks);
+        for pair in chunks.windows(2) {
+            assert!(pair[1].start < pair[0].end, "windows should overlap");
+        }
+        assert_eq!(chunks.last().unwrap().end, text.len());
+    }
+
+    #[test]
+    fn test_sentences_are_kept_whole() {
+        let text = "The first sentence is here. The second one follows!\n\nA new paragraph? Yes.";
+        let chunks = sentences(text, 10);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "The first sentence is here.",
+                "The second one follows!",
+                "A new paragraph? Yes.",
+            ]
+        );
+        assert_offsets(text, &chunks);
+    }
+
+    #[test]
+    fn test_long_sentences_are_split_between_words() {
+        let text = format!("{}.", "word ".repeat(200).trim_end());
+        let chunks = sentences(&text, 64);
+        assert!(chunks.len() > 2);
+        assert!(chunks
+            .iter()
+            .all(|chunk| estimate_tokens(&chunk.text) <= 64));
+        assert_offsets(&text, &chunks);
+        let words = chunks
+            .iter()
+            .map(|chunk| chunk.text.split_whitespace().count())
+            .sum::<usize>();
+        assert_eq!(words, 200);
+    }
+
+    #[test]
+    fn test_mean_embedding_is_normalized() {
+        let mean = mean_embedding(&[vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert!((mean[0] - mean[1]).abs() < 1e-6);
+        assert!((mean.iter().map(|v| v * v).sum::<f32>() - 1.0).abs() < 1e-6);
+    }
+}
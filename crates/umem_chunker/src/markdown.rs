@@ -0,0 +1,137 @@
+use crate::{
+    pack,
+    spans::{self, is_fence, lines},
+    Chunk,
+};
+use umem_embeddings::estimate_tokens;
+
+/// A run of headings and the text under them. Text before the first heading forms a
+/// section without headings.
+struct Section {
+    start: usize,
+    headings_end: usize,
+    end: usize,
+}
+
+/// Splits `text` at every Markdown heading outside code fences. Headings with no text
+/// under them stay with the following section. Sections too long for one chunk are
+/// split between paragraphs, and every piece after the first is prefixed with the
+/// section headings.
+pub fn markdown(text: &str, max_tokens: usize) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    for section in sections(text) {
+        let headings = text[section.start..section.headings_end].trim_end();
+        let body = spans::blocks(text, section.headings_end, section.end);
+        if body.is_empty() {
+            if !headings.is_empty() {
+                chunks.push(Chunk::span(
+                    text,
+                    section.start,
+                    section.start + headings.len(),
+                ));
+            }
+            continue;
+        }
+        if headings.is_empty() {
+            chunks.extend(pack(text, &body, max_tokens));
+            continue;
+        }
+
+        let budget = max_tokens
+            .saturating_sub(estimate_tokens(headings) + 1)
+            .max(1);
+        for (i, piece) in pack(text, &body, budget).into_iter().enumerate() {
+            chunks.push(if i == 0 {
+                Chunk::span(text, section.start, piece.end)
+            } else {
+                Chunk {
+                    text: format!("{headings}\n\n{}", piece.text),
+                    ..piece
+                }
+            });
+        }
+    }
+    chunks
+}
+
+fn sections(text: &str) -> Vec<Section> {
+    let mut sections = Vec::new();
+    let mut current = Section {
+        start: 0,
+        headings_end: 0,
+        end: 0,
+    };
+    let mut has_body = false;
+    let mut in_fence = false;
+    for (start, line) in lines(text) {
+        let trimmed = line.trim();
+        if is_fence(trimmed) {
+            in_fence = !in_fence;
+        }
+        if !in_fence && is_heading(trimmed) {
+            if has_body || current.start == current.headings_end && start > current.start {
+                // The previous section has text of its own, or is a preamble.
+                current.end = start;
+                sections.push(current);
+                current = Section {
+                    start,
+                    headings_end: start,
+                    end: start,
+                };
+                has_body = false;
+            }
+            current.headings_end = start + line.len();
+            continue;
+        }
+        has_body |= !trimmed.is_empty();
+    }
+    current.end = text.len();
+    if current.start < current.end {
+        sections.push(current);
+    }
+    sections
+}
+
+fn is_heading(line: &str) -> bool {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    (1..=6).contains(&level) && line[level..].starts_with(' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_is_chunked_by_heading() {
+        let text = "Intro text.\n\n# Guide\n\n## Install\n\nRun the installer.\n\n```sh\n# not a heading\n\n```\n\n## Use\n\nStart it.\n";
+        let chunks = markdown(text, 100);
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Intro text.",
+                "# Guide\n\n## Install\n\nRun the installer.\n\n```sh\n# not a heading\n\n```",
+                "## Use\n\nStart it.",
+            ]
+        );
+        for chunk in &chunks {
+            assert_eq!(&text[chunk.start..chunk.end], chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_long_sections_repeat_their_headings() {
+        let text = format!("## Notes\n\n{}", "word ".repeat(200));
+        let chunks = markdown(&text, 64);
+        assert!(chunks.len() > 1);
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.text.starts_with("## Notes\n\n")));
+        assert!(chunks
+            .iter()
+            .all(|chunk| estimate_tokens(&chunk.text) <= 64));
+        assert_eq!(chunks.last().unwrap().end, text.trim_end().len());
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{pack, spans, Chunk};
+use anyhow::Result;
+use umem_embeddings::Embedder;
+
+/// Embeds every sentence of `text` and ends a chunk wherever the cosine distance
+/// between neighbouring sentences is above the `breakpoint_percentile` of all such
+/// distances. Groups longer than `max_tokens` are packed by sentence.
+pub async fn semantic<E>(
+    text: &str,
+    max_tokens: usize,
+    breakpoint_percentile: f32,
+    embedder: &E,
+) -> Result<Vec<Chunk>>
+where
+    E: Embedder + Sync + ?Sized,
+{
+    let sentences = spans::sentences(text);
+    if sentences.len() < 2 {
+        return Ok(pack(text, &sentences, max_tokens));
+    }
+    let embeddings = embedder
+        .generate_embeddings_bulk(
+            sentences
+                .iter()
+                .map(|&(start, end)| &text[start..end])
+                .collect(),
+        )
+        .await?;
+    let distances = embeddings
+        .windows(2)
+        .map(|pair| 1.0 - cosine_similarity(&pair[0], &pair[1]))
+        .collect::<Vec<_>>();
+    let threshold = percentile(&distances, breakpoint_percentile);
+
+    let mut chunks = Vec::new();
+    let mut first = 0;
+    for (i, distance) in distances.iter().enumerate() {
+        if *distance > threshold {
+            chunks.extend(pack(text, &sentences[first..=i], max_tokens));
+            first = i + 1;
+        }
+    }
+    chunks.extend(pack(text, &sentences[first..], max_tokens));
+    Ok(chunks)
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// Nearest-rank percentile of `values`, which must not be empty.
+fn percentile(values: &[f32], percentile: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let rank = (percentile / 100.0 * (sorted.len() - 1) as f32).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+
+    /// Puts sentences about cats and everything else on orthogonal axes.
+    struct TopicEmbedder;
+
+    #[async_trait]
+    impl Embedder for TopicEmbedder {
+        fn model(&self) -> &str {
+            "topics"
+        }
+
+        fn dimensions(&self) -> usize {
+            2
+        }
+
+        async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+            Ok(if text.to_lowercase().contains("cat") {
+                vec![1.0, 0.0]
+            } else {
+                vec![0.0, 1.0]
+            })
+        }
+
+        async fn generate_embeddings_bulk<'em>(
+            &self,
+            texts: Vec<&'em str>,
+        ) -> Result<Vec<Vec<f32>>> {
+            let mut embeddings = Vec::new();
+            for text in texts {
+                embeddings.push(self.generate_embedding(text).await?);
+            }
+            Ok(embeddings)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_chunks_break_where_the_topic_changes() {
+        let text = "Cats purr. A cat naps all day. Rust compiles to native code. It is fast.";
+        let chunks = semantic(text, 512, 50.0, &TopicEmbedder).await.unwrap();
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.text.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Cats purr. A cat naps all day.",
+                "Rust compiles to native code. It is fast.",
+            ]
+        );
+        assert_eq!(chunks[1].start, text.find("Rust").unwrap());
+    }
+}
@@ -0,0 +1,101 @@
+//! Byte spans of the words, sentences and blocks of a text. Spans are trimmed, sorted
+//! and disjoint, and index into the text they were found in.
+
+pub(crate) type Span = (usize, usize);
+
+pub(crate) fn words(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// Sentences end after `.`, `!` or `?` followed by whitespace, and never cross a blank
+/// line.
+pub(crate) fn sentences(text: &str) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|(_, next)| *next);
+        let end = match (c, next) {
+            ('.' | '!' | '?', Some(next)) if next.is_whitespace() => i + c.len_utf8(),
+            ('\n', Some('\n')) => i,
+            _ => continue,
+        };
+        push_trimmed(text, start, end, &mut spans);
+        start = end;
+    }
+    push_trimmed(text, start, text.len(), &mut spans);
+    spans
+}
+
+/// Paragraphs of `text[from..to]`, separated by blank lines outside code fences.
+pub(crate) fn blocks(text: &str, from: usize, to: usize) -> Vec<Span> {
+    let mut spans = Vec::new();
+    let mut start = from;
+    let mut in_fence = false;
+    for (offset, line) in lines(&text[from..to]) {
+        let line_start = from + offset;
+        let trimmed = line.trim();
+        if is_fence(trimmed) {
+            in_fence = !in_fence;
+        }
+        if !in_fence && trimmed.is_empty() {
+            push_trimmed(text, start, line_start, &mut spans);
+            start = line_start + line.len();
+        }
+    }
+    push_trimmed(text, start, to, &mut spans);
+    spans
+}
+
+/// Lines of `text` with their byte offsets, keeping line endings.
+pub(crate) fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split_inclusive('\n').scan(0, |offset, line| {
+        let start = *offset;
+        *offset += line.len();
+        Some((start, line))
+    })
+}
+
+pub(crate) fn is_fence(line: &str) -> bool {
+    line.starts_with("```") || line.starts_with("~~~")
+}
+
+/// Character offsets of the start and end of each span, counted from the start of the
+/// first, so the tokens of any run of spans are known without rescanning the text.
+pub(crate) fn char_offsets(text: &str, spans: &[Span]) -> Vec<(usize, usize)> {
+    let mut offsets = Vec::with_capacity(spans.len());
+    let mut byte = spans.first().map_or(0, |span| span.0);
+    let mut chars = 0;
+    for &(start, end) in spans {
+        chars += text[byte..start].chars().count();
+        let span_start = chars;
+        chars += text[start..end].chars().count();
+        byte = end;
+        offsets.push((span_start, chars));
+    }
+    offsets
+}
+
+fn push_trimmed(text: &str, start: usize, end: usize, spans: &mut Vec<Span>) {
+    let slice = &text[start..end];
+    let trimmed_start = start + (slice.len() - slice.trim_start().len());
+    let trimmed_end = start + slice.trim_end().len();
+    if trimmed_start < trimmed_end {
+        spans.push((trimmed_start, trimmed_end));
+    }
+}
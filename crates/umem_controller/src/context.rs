@@ -0,0 +1,181 @@
+use umem_embeddings::estimate_tokens;
+use umem_proto_generated::generated;
+
+pub const DEFAULT_TOKEN_BUDGET: u32 = 2000;
+pub const CANDIDATE_LIMIT: u64 = 50;
+
+const CONTEXT_HEADER: &str = "<memories>\n";
+const CONTEXT_FOOTER: &str = "</memories>";
+const RELEVANCE_WEIGHT: f32 = 0.7;
+const PRIORITY_WEIGHT: f32 = 0.2;
+const RECENCY_WEIGHT: f32 = 0.1;
+const MAX_PRIORITY: i32 = 10;
+const RECENCY_HALF_LIFE_SECS: f32 = 30.0 * 24.0 * 60.0 * 60.0;
+
+/// A memory returned by vector search together with its similarity score.
+pub struct ScoredMemory {
+    pub memory: generated::Memory,
+    pub relevance: f32,
+}
+
+/// Greedily packs the best ranked memories into a context block that fits `token_budget`.
+pub struct ContextPacker {
+    token_budget: usize,
+    now: i64,
+}
+
+impl ContextPacker {
+    pub fn new(token_budget: u32, now: i64) -> Self {
+        let token_budget = match token_budget {
+            0 => DEFAULT_TOKEN_BUDGET,
+            budget => budget,
+        };
+        Self {
+            token_budget: token_budget as usize,
+            now,
+        }
+    }
+
+    pub fn pack(&self, candidates: Vec<ScoredMemory>) -> generated::MemoryContext {
+        let mut ranked = candidates
+            .into_iter()
+            .map(|candidate| (self.rank(&candidate), candidate.memory))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        let mut used = estimate_tokens(CONTEXT_HEADER) + estimate_tokens(CONTEXT_FOOTER);
+        let mut context = String::from(CONTEXT_HEADER);
+        let mut memories = Vec::new();
+
+        for (_, memory) in ranked {
+            let line = Self::format_line(&memory);
+            let cost = estimate_tokens(&line);
+            if used + cost > self.token_budget {
+                continue;
+            }
+            used += cost;
+            context.push_str(&line);
+            memories.push(memory);
+        }
+
+        if memories.is_empty() {
+            return generated::MemoryContext::default();
+        }
+
+        context.push_str(CONTEXT_FOOTER);
+        generated::MemoryContext {
+            context,
+            memories,
+            token_count: used as u32,
+        }
+    }
+
+    fn rank(&self, candidate: &ScoredMemory) -> f32 {
+        let priority =
+            candidate.memory.priority.clamp(0, MAX_PRIORITY) as f32 / MAX_PRIORITY as f32;
+        let age = (self.now - candidate.memory.updated_at).max(0) as f32;
+        let recency = 0.5_f32.powf(age / RECENCY_HALF_LIFE_SECS);
+
+        RELEVANCE_WEIGHT * candidate.relevance
+            + PRIORITY_WEIGHT * priority
+            + RECENCY_WEIGHT * recency
+    }
+
+    fn format_line(memory: &generated::Memory) -> String {
+        let date = chrono::DateTime::from_timestamp(memory.updated_at, 0)
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let mut line = format!("- [{}] {}", date, memory.content.trim());
+        match (memory.page_start, memory.page_end) {
+            (0, _) => {}
+            (start, end) if end > start => line.push_str(&format!(" (pages {start}-{end})")),
+            (start, _) => line.push_str(&format!(" (page {start})")),
+        }
+        if !memory.tags.is_empty() {
+            line.push_str(&format!(" (tags: {})", memory.tags.join(", ")));
+        }
+        line.push('\n');
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_750_000_000;
+
+    fn scored(content: &str, relevance: f32, priority: i32, updated_at: i64) -> ScoredMemory {
+        ScoredMemory {
+            memory: generated::Memory {
+                content: content.to_string(),
+                priority,
+                updated_at,
+                ..Default::default()
+            },
+            relevance,
+        }
+    }
+
+    #[test]
+    fn test_pack_orders_by_relevance() {
+        let packer = ContextPacker::new(1000, NOW);
+        let packed = packer.pack(vec![
+            scored("less relevant", 0.2, 0, NOW),
+            scored("most relevant", 0.9, 0, NOW),
+        ]);
+        let contents = packed
+            .memories
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["most relevant", "less relevant"]);
+        assert!(packed.context.starts_with(CONTEXT_HEADER));
+        assert!(packed.context.ends_with(CONTEXT_FOOTER));
+    }
+
+    #[test]
+    fn test_priority_and_recency_break_ties() {
+        let packer = ContextPacker::new(1000, NOW);
+        let packed = packer.pack(vec![
+            scored("old", 0.5, 0, NOW - 365 * 24 * 60 * 60),
+            scored("important", 0.5, 10, NOW - 365 * 24 * 60 * 60),
+            scored("fresh", 0.5, 0, NOW),
+        ]);
+        assert_eq!(packed.memories[0].content, "important");
+        assert_eq!(packed.memories[1].content, "fresh");
+        assert_eq!(packed.memories[2].content, "old");
+    }
+
+    #[test]
+    fn test_pack_respects_budget() {
+        let packer = ContextPacker::new(30, NOW);
+        let packed = packer.pack(vec![
+            scored(&"x".repeat(400), 0.9, 0, NOW),
+            scored("short and relevant", 0.8, 0, NOW),
+        ]);
+        assert_eq!(packed.memories.len(), 1);
+        assert_eq!(packed.memories[0].content, "short and relevant");
+        assert!(packed.token_count <= 30);
+        assert!(estimate_tokens(&packed.context) <= packed.token_count as usize);
+    }
+
+    #[test]
+    fn test_pack_empty_when_nothing_fits() {
+        let packer = ContextPacker::new(5, NOW);
+        let packed = packer.pack(vec![scored("does not fit in five tokens", 0.9, 0, NOW)]);
+        assert!(packed.memories.is_empty());
+        assert!(packed.context.is_empty());
+        assert_eq!(packed.token_count, 0);
+    }
+
+    #[test]
+    fn test_lines_cite_document_pages() {
+        let mut memory = scored("From the manual", 0.9, 0, NOW).memory;
+        memory.page_start = 12;
+        memory.page_end = 12;
+        assert!(ContextPacker::format_line(&memory).contains("From the manual (page 12)"));
+        memory.page_end = 13;
+        assert!(ContextPacker::format_line(&memory).contains("(pages 12-13)"));
+    }
+}
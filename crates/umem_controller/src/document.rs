@@ -0,0 +1,247 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use umem_proto_generated::generated;
+use umem_web_scrapper::Validators;
+use uuid::Uuid;
+
+/// Largest file accepted by document ingestion.
+pub const MAX_DOCUMENT_BYTES: usize = 16 * 1024 * 1024;
+/// Neighbor chunks returned on each side of a search hit at most.
+pub(crate) const MAX_NEIGHBOR_CHUNKS: u32 = 5;
+/// Web page records one refresh reads from the store at a time.
+pub(crate) const REFRESH_PAGE_SIZE: u32 = 256;
+/// Namespace of the UUIDv5 document and chunk ids.
+const DOCUMENT_ID_NAMESPACE: Uuid = Uuid::from_u128(0x3f0a_91c2_7d4e_5b86_a1c9_0e2d_47f3_b815);
+
+/// Hex SHA-256 of the uploaded bytes, or of the text kept from a web page.
+pub(crate) fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// The same content uploaded twice by a tenant maps to the same document, so
+/// re-ingesting replaces its chunks instead of duplicating them.
+pub(crate) fn document_id(user_id: &str, content_hash: &str) -> String {
+    let name = format!("{}:{}:{}", user_id.len(), user_id, content_hash);
+    Uuid::new_v5(&DOCUMENT_ID_NAMESPACE, name.as_bytes()).to_string()
+}
+
+/// A URL maps to one document per tenant whatever the page currently says, so
+/// re-ingesting it replaces the chunks stored for it before.
+pub(crate) fn url_document_id(user_id: &str, url: &str) -> String {
+    let name = format!("{}:{}:url:{}", user_id.len(), user_id, url);
+    Uuid::new_v5(&DOCUMENT_ID_NAMESPACE, name.as_bytes()).to_string()
+}
+
+pub(crate) fn chunk_id(document_id: &str, chunk_index: usize) -> String {
+    let name = format!("{document_id}:{chunk_index}");
+    Uuid::new_v5(&DOCUMENT_ID_NAMESPACE, name.as_bytes()).to_string()
+}
+
+/// What the server of the page `document` was made from sent to let it be fetched
+/// conditionally.
+pub(crate) fn validators(document: &generated::Document) -> Validators {
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+    Validators {
+        etag: non_empty(&document.etag),
+        last_modified: non_empty(&document.last_modified),
+    }
+}
+
+/// Tags of every chunk of a web page: the caller's, then its URL and title.
+pub(crate) fn web_page_tags(mut tags: Vec<String>, url: &str, title: &str) -> Vec<String> {
+    tags.push(format!("source:{url}"));
+    tags.push(format!("title:{title}"));
+    tags
+}
+
+/// The tags `web_page_tags` added to a chunk's own.
+pub(crate) fn is_web_page_tag(tag: &str) -> bool {
+    tag.starts_with("source:") || tag.starts_with("title:")
+}
+
+/// Text of the first level one heading of `markdown`.
+pub(crate) fn markdown_title(markdown: &str) -> Option<String> {
+    markdown
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("# "))
+        .map(str::trim)
+        .find(|title| !title.is_empty())
+        .map(str::to_string)
+}
+
+/// Ids of the chunks within `radius` of `hit` on either side, excluding the hit.
+pub(crate) fn neighbor_ids(hit: &generated::Memory, radius: u32) -> Vec<String> {
+    let first = hit.chunk_index.saturating_sub(radius);
+    let last = hit.chunk_index.saturating_add(radius);
+    (first..=last)
+        .filter(|index| *index != hit.chunk_index)
+        .map(|index| chunk_id(&hit.document_id, index as usize))
+        .collect()
+}
+
+/// Builds the context of a search hit on a document chunk from the fetched `chunks`
+/// and `documents`, keyed by id. Missing neighbors, past either end of the document,
+/// are skipped.
+pub(crate) fn chunk_context(
+    hit: &generated::Memory,
+    radius: u32,
+    merge_window: bool,
+    chunks: &HashMap<String, generated::Memory>,
+    documents: &HashMap<String, generated::Document>,
+) -> generated::ChunkContext {
+    let mut neighbors = neighbor_ids(hit, radius)
+        .iter()
+        .filter_map(|id| chunks.get(id))
+        .filter(|chunk| chunk.document_id == hit.document_id)
+        .cloned()
+        .collect::<Vec<_>>();
+    neighbors.sort_by_key(|chunk| chunk.chunk_index);
+
+    let window = if merge_window {
+        let mut window = neighbors.iter().collect::<Vec<_>>();
+        let position = window.partition_point(|chunk| chunk.chunk_index < hit.chunk_index);
+        window.insert(position, hit);
+        merge_chunks(&window)
+    } else {
+        String::new()
+    };
+
+    generated::ChunkContext {
+        memory_id: hit.memory_id.clone(),
+        document: documents.get(&hit.document_id).cloned(),
+        neighbors,
+        window,
+    }
+}
+
+/// Joins consecutive chunks in document order. Where a chunk starts inside the one
+/// before, as with overlapping windows, the repeated text is dropped.
+pub(crate) fn merge_chunks(chunks: &[&generated::Memory]) -> String {
+    let mut merged = String::new();
+    let mut previous_end = None;
+    for chunk in chunks {
+        let mut content = chunk.content.as_str();
+        let is_source_slice =
+            (chunk.chunk_end.saturating_sub(chunk.chunk_start)) as usize == content.len();
+        let separator = match previous_end {
+            Some(end) if is_source_slice && chunk.chunk_start < end => {
+                let overlap = ((end - chunk.chunk_start) as usize).min(content.len());
+                if content.is_char_boundary(overlap) {
+                    content = content[overlap..].trim_start();
+                }
+                " "
+            }
+            _ => "\n\n",
+        };
+        if !merged.is_empty() && !content.is_empty() {
+            merged.push_str(separator);
+        }
+        merged.push_str(content);
+        previous_end = previous_end.max(Some(chunk.chunk_end));
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(index: u32, content: &str, start: u32) -> generated::Memory {
+        generated::Memory {
+            memory_id: chunk_id("doc", index as usize),
+            document_id: "doc".to_string(),
+            chunk_index: index,
+            content: content.to_string(),
+            chunk_start: start,
+            chunk_end: start + content.len() as u32,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_context_holds_neighbors_in_order_and_merged_window() {
+        let chunks = [
+            chunk(0, "Alpha beta.", 0),
+            chunk(1, "Gamma delta.", 13),
+            chunk(2, "Epsilon.", 27),
+        ];
+        let fetched = chunks
+            .iter()
+            .map(|chunk| (chunk.memory_id.clone(), chunk.clone()))
+            .collect::<HashMap<_, _>>();
+        let context = chunk_context(&chunks[1], 2, true, &fetched, &HashMap::new());
+        assert_eq!(
+            context
+                .neighbors
+                .iter()
+                .map(|chunk| chunk.chunk_index)
+                .collect::<Vec<_>>(),
+            vec![0, 2]
+        );
+        assert_eq!(context.window, "Alpha beta.\n\nGamma delta.\n\nEpsilon.");
+        assert!(context.document.is_none());
+    }
+
+    #[test]
+    fn test_overlapping_chunks_merge_without_repeats() {
+        let first = chunk(0, "one two three four", 0);
+        let second = chunk(1, "three four five six", 8);
+        assert_eq!(
+            merge_chunks(&[&first, &second]),
+            "one two three four five six"
+        );
+    }
+
+    #[test]
+    fn test_markdown_title_is_the_first_level_one_heading() {
+        assert_eq!(
+            markdown_title("Intro\n\n# Guide\n\n## Install").as_deref(),
+            Some("Guide")
+        );
+        assert_eq!(markdown_title("## Only a section"), None);
+    }
+
+    #[test]
+    fn test_same_content_gives_same_document() {
+        let hash = content_hash(b"report");
+        assert_eq!(document_id("user-1", &hash), document_id("user-1", &hash));
+        assert_ne!(document_id("user-1", &hash), document_id("user-2", &hash));
+        assert_ne!(chunk_id("doc", 0), chunk_id("doc", 1));
+        assert_ne!(
+            url_document_id("user-1", "https://example.com/"),
+            document_id("user-1", &hash)
+        );
+    }
+
+    #[test]
+    fn test_web_page_tags_follow_the_callers() {
+        let tags = web_page_tags(vec!["docs".to_string()], "https://example.com/", "Example");
+        assert_eq!(
+            tags,
+            ["docs", "source:https://example.com/", "title:Example"]
+        );
+        let own = tags
+            .iter()
+            .filter(|tag| !is_web_page_tag(tag))
+            .collect::<Vec<_>>();
+        assert_eq!(own, ["docs"]);
+    }
+
+    #[test]
+    fn test_validators_skip_headers_the_server_did_not_send() {
+        let document = generated::Document {
+            etag: "\"v2\"".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            validators(&document),
+            Validators {
+                etag: Some("\"v2\"".to_string()),
+                last_modified: None,
+            }
+        );
+    }
+}
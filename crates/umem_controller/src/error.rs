@@ -0,0 +1,182 @@
+use qdrant_client::QdrantError;
+use std::time::Duration;
+use umem_embeddings::EmbeddingError;
+
+pub type Result<T> = std::result::Result<T, ControllerError>;
+
+/// Failures surfaced by [`crate::MemoryController`], classified so transports can tell
+/// bad input apart from failures worth retrying.
+#[derive(Debug, thiserror::Error)]
+pub enum ControllerError {
+    #[error("Invalid {field}: {description}")]
+    Validation {
+        field: &'static str,
+        description: String,
+    },
+    #[error("{resource_type} {resource_name} not found")]
+    NotFound {
+        resource_type: &'static str,
+        resource_name: String,
+    },
+    /// The cause is kept for server logs only; it can name backend hosts and accounts.
+    #[error("Embedding backend unavailable")]
+    EmbeddingUnavailable(#[source] anyhow::Error),
+    #[error("Vector store unavailable")]
+    VectorStoreUnavailable(#[source] anyhow::Error),
+    #[error("Rate limited by the {backend}")]
+    RateLimited {
+        backend: &'static str,
+        retry_after: Option<Duration>,
+    },
+    #[error(transparent)]
+    Internal(#[from] anyhow::Error),
+}
+
+impl ControllerError {
+    pub fn validation(field: &'static str, description: impl Into<String>) -> Self {
+        Self::Validation {
+            field,
+            description: description.into(),
+        }
+    }
+
+    pub fn memory_not_found(memory_id: &str) -> Self {
+        Self::NotFound {
+            resource_type: "Memory",
+            resource_name: memory_id.to_string(),
+        }
+    }
+
+    pub fn document_not_found(document_id: &str) -> Self {
+        Self::NotFound {
+            resource_type: "Document",
+            resource_name: document_id.to_string(),
+        }
+    }
+
+    /// Classifies a failure of the embedding backend.
+    pub(crate) fn embedding(error: anyhow::Error) -> Self {
+        let typed = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<EmbeddingError>());
+        match typed {
+            Some(EmbeddingError::RateLimited { retry_after }) => Self::RateLimited {
+                backend: "embedding backend",
+                retry_after: *retry_after,
+            },
+            // The provider rejected the text itself, not our credentials.
+            Some(error @ EmbeddingError::Provider { errors, .. }) if error.is_input_rejected() => {
+                let description = errors
+                    .iter()
+                    .map(|e| e.message.as_str())
+                    .collect::<Vec<_>>()
+                    .join("; ");
+                Self::validation("content", description)
+            }
+            _ => Self::EmbeddingUnavailable(error),
+        }
+    }
+
+    /// Classifies a failure of the vector store.
+    pub(crate) fn vector_store(error: anyhow::Error) -> Self {
+        match error.downcast_ref::<QdrantError>() {
+            Some(QdrantError::ResourceExhaustedError {
+                retry_after_seconds,
+                ..
+            }) => Self::RateLimited {
+                backend: "vector store",
+                retry_after: Some(Duration::from_secs(*retry_after_seconds)),
+            },
+            Some(QdrantError::ResponseError { status })
+                if status.code() == tonic::Code::ResourceExhausted =>
+            {
+                Self::RateLimited {
+                    backend: "vector store",
+                    retry_after: None,
+                }
+            }
+            _ => Self::VectorStoreUnavailable(error),
+        }
+    }
+
+    /// Whether the same request may succeed if retried later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::EmbeddingUnavailable(_)
+                | Self::VectorStoreUnavailable(_)
+                | Self::RateLimited { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_store_resource_exhausted_is_rate_limited() {
+        let error = ControllerError::vector_store(
+            QdrantError::ResourceExhaustedError {
+                status: tonic::Status::resource_exhausted("slow down"),
+                retry_after_seconds: 3,
+            }
+            .into(),
+        );
+        assert!(matches!(
+            error,
+            ControllerError::RateLimited {
+                retry_after: Some(retry_after),
+                ..
+            } if retry_after == Duration::from_secs(3)
+        ));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_vector_store_errors_are_unavailable() {
+        let error = ControllerError::vector_store(
+            QdrantError::ResponseError {
+                status: tonic::Status::unavailable("down"),
+            }
+            .into(),
+        );
+        assert!(matches!(error, ControllerError::VectorStoreUnavailable(_)));
+    }
+
+    #[test]
+    fn test_embedding_rate_limit_keeps_retry_after() {
+        let error = ControllerError::embedding(
+            EmbeddingError::RateLimited {
+                retry_after: Some(Duration::from_secs(4)),
+            }
+            .into(),
+        );
+        assert!(matches!(
+            error,
+            ControllerError::RateLimited {
+                retry_after: Some(retry_after),
+                ..
+            } if retry_after == Duration::from_secs(4)
+        ));
+    }
+
+    #[test]
+    fn test_embedding_circuit_open_is_unavailable() {
+        let error = ControllerError::embedding(
+            EmbeddingError::CircuitOpen {
+                retry_after: Duration::from_secs(1),
+            }
+            .into(),
+        );
+        assert!(matches!(error, ControllerError::EmbeddingUnavailable(_)));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_validation_is_not_retryable() {
+        let error = ControllerError::validation("content", "cannot be empty");
+        assert_eq!(error.to_string(), "Invalid content: cannot be empty");
+        assert!(!error.is_retryable());
+    }
+}
@@ -1,34 +1,106 @@
-use anyhow::Result;
+mod context;
+mod document;
+mod error;
+
+use context::{ContextPacker, ScoredMemory};
+pub use document::MAX_DOCUMENT_BYTES;
+pub use error::{ControllerError, Result};
 use lazy_static::lazy_static;
 use serde_json::json;
+use std::collections::HashMap;
 use tokio::sync::OnceCell;
-use umem_embeddings::Embedder;
+use umem_chunker::{Chunk, ChunkStrategy, mean_embedding};
+use umem_doc_parser::{ExtractedDocument, Extractor};
+use umem_embeddings::{
+    BatchConfig, BatchingEmbedder, BoxedEmbedder, CacheConfig, CacheStats, CachingEmbedder,
+    CfBaaiBgeM3Embeder, Embedder, FallbackEmbedder, OpenAiCompatibleEmbedder, ResilienceConfig,
+    cool_down_from_env, estimate_tokens,
+};
 use umem_proto_generated::generated;
 use umem_vector::QdrantVectorStore;
+use umem_web_scrapper::{Fetched, Scrapper, Validators};
 use uuid::Uuid;
 
+const HEALTH_PROBE: &str = "health check";
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 256;
+/// Namespace of the UUIDv5 memory ids derived from idempotency keys.
+const MEMORY_ID_NAMESPACE: Uuid = Uuid::from_u128(0x6d1c_4f0e_8a7b_5c3d_9e2f_1a4b_6c8d_0e7f);
+
 static MEMORY_STORE: OnceCell<QdrantVectorStore> = OnceCell::const_new();
+static DOCUMENT_STORE: OnceCell<QdrantVectorStore> = OnceCell::const_new();
 
-async fn get_memory_store() -> &'static QdrantVectorStore {
+async fn get_memory_store() -> Result<&'static QdrantVectorStore> {
     MEMORY_STORE
-        .get_or_init(|| async {
+        .get_or_try_init(|| async {
             QdrantVectorStore::new(
                 &std::env::var("QDRANT_URL").expect("QDRANT_URL not set"),
                 &std::env::var("QDRANT_KEY").expect("QDRANT_KEY not set"),
                 &std::env::var("QDRANT_COLLECTION_NAME").expect("QDRANT_COLLECTION_NAME not set"),
             )
             .await
-            .expect("qdrant client failed to intialize")
+            .map_err(ControllerError::vector_store)
         })
         .await
 }
 
+/// Documents live in `<QDRANT_COLLECTION_NAME>_documents`, next to their chunks.
+async fn get_document_store() -> Result<&'static QdrantVectorStore> {
+    DOCUMENT_STORE
+        .get_or_try_init(|| async {
+            let collection_name =
+                std::env::var("QDRANT_COLLECTION_NAME").expect("QDRANT_COLLECTION_NAME not set");
+            QdrantVectorStore::new_record_store(
+                &std::env::var("QDRANT_URL").expect("QDRANT_URL not set"),
+                &std::env::var("QDRANT_KEY").expect("QDRANT_KEY not set"),
+                &format!("{collection_name}_documents"),
+            )
+            .await
+            .map_err(ControllerError::vector_store)
+        })
+        .await
+}
+
+lazy_static! {
+    static ref CFEmbeder: CachingEmbedder<BatchingEmbedder<FallbackEmbedder>> =
+        CachingEmbedder::new(
+            BatchingEmbedder::new(embedding_providers(), BatchConfig::from_env()),
+            CacheConfig::from_env(),
+        );
+}
+
 lazy_static! {
-    static ref CFEmbeder: umem_embeddings::CfBaaiBgeM3Embeder =
-        umem_embeddings::CfBaaiBgeM3Embeder::new(
+    static ref CHUNK_STRATEGY: ChunkStrategy =
+        ChunkStrategy::from_env().expect("Invalid chunking configuration");
+    /// Half of bge-m3's 8192 token context, as the four characters per token estimate
+    /// undercounts some scripts.
+    static ref MEMORY_CHUNK_THRESHOLD_TOKENS: usize = std::env::var("MEMORY_CHUNK_THRESHOLD_TOKENS")
+        .ok()
+        .and_then(|tokens| tokens.parse().ok())
+        .filter(|tokens| *tokens > 0)
+        .unwrap_or(4096);
+    static ref HEALTH_CHECK_EMBEDDING_PROBE: bool = std::env::var("HEALTH_CHECK_EMBEDDING_PROBE")
+        .map(|value| value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+}
+
+/// Cloudflare, then the OpenAI compatible fallbacks from `EMBEDDING_FALLBACK_<N>_*`.
+fn embedding_providers() -> FallbackEmbedder {
+    let resilience = ResilienceConfig::from_env();
+    let mut providers: Vec<(String, BoxedEmbedder)> = vec![(
+        "cloudflare".to_string(),
+        Box::new(CfBaaiBgeM3Embeder::new(
             std::env::var("CLOUDFLARE_ACCOUNT_ID").expect("CLOUDFLARE_ACCOUNT_ID not set"),
             std::env::var("CLOUDFLARE_API_TOKEN").expect("CLOUDFLARE_API_TOKEN not set"),
-        );
+            resilience.clone(),
+        )),
+    )];
+    for fallback in OpenAiCompatibleEmbedder::all_from_env(&resilience)
+        .expect("Invalid embedding fallback configuration")
+    {
+        providers.push((fallback.url().to_string(), Box::new(fallback)));
+    }
+    FallbackEmbedder::new(providers, cool_down_from_env())
+        .expect("Invalid embedding fallback configuration")
 }
 
 #[derive(Debug, Default)]
@@ -36,53 +108,613 @@ pub struct MemoryController;
 
 impl MemoryController {
     pub async fn add_memory(memory: generated::Memory) -> Result<generated::Memory> {
-        let memory_store = get_memory_store().await;
+        let mut memory = Self::prepare_memory(memory, chrono::Utc::now().timestamp())?;
+        let memory_store = get_memory_store().await?;
+        Self::keep_created_at(memory_store, vec![&mut memory]).await?;
+
+        let vectors = embed_memory(&memory.content).await?;
+        memory_store
+            .insert_embedding(memory.clone(), vectors)
+            .await
+            .map_err(ControllerError::vector_store)?;
+        Ok(memory)
+    }
+
+    /// Stores the valid memories of `memory_bulk` in one batch and returns the outcome of
+    /// each memory in request order. Invalid memories fail individually; backend
+    /// failures fail the whole call.
+    pub async fn add_memory_bulk(
+        memory_bulk: generated::MemoryBulk,
+    ) -> Result<Vec<Result<generated::Memory>>> {
+        if memory_bulk.memories.is_empty() {
+            return Err(ControllerError::validation("memories", "cannot be empty"));
+        }
 
         let now = chrono::Utc::now().timestamp();
-        let memory = generated::Memory {
-            memory_id: Uuid::new_v4().to_string(),
+        let mut results = memory_bulk
+            .memories
+            .into_iter()
+            .map(|memory| Self::prepare_memory(memory, now))
+            .collect::<Vec<_>>();
+        if results.iter().all(Result::is_err) {
+            return Ok(results);
+        }
+        let memory_store = get_memory_store().await?;
+        Self::keep_created_at(
+            memory_store,
+            results
+                .iter_mut()
+                .filter_map(|result| result.as_mut().ok())
+                .collect(),
+        )
+        .await?;
+
+        let memories = results
+            .iter()
+            .filter_map(|result| result.as_ref().ok())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let texts = memories
+            .iter()
+            .map(|memory| memory.content.as_str())
+            .collect::<Vec<_>>();
+
+        let vectors = embed_memories(&texts).await?;
+
+        memory_store
+            .insert_embeddings_bulk(std::iter::zip(memories, vectors).collect::<Vec<_>>())
+            .await
+            .map_err(ControllerError::vector_store)?;
+
+        Ok(results)
+    }
+
+    /// Validates a new memory and assigns its id and timestamps.
+    fn prepare_memory(memory: generated::Memory, now: i64) -> Result<generated::Memory> {
+        if memory.content.is_empty() {
+            return Err(ControllerError::validation("content", "cannot be empty"));
+        }
+        if memory.idempotency_key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+            return Err(ControllerError::validation(
+                "idempotency_key",
+                format!("cannot be longer than {MAX_IDEMPOTENCY_KEY_LEN} bytes"),
+            ));
+        }
+        Ok(generated::Memory {
+            memory_id: memory_id(&memory.user_id, &memory.idempotency_key),
             updated_at: now,
             created_at: now,
             ..memory
+        })
+    }
+
+    /// Gives memories whose idempotency key was written before the creation time of the
+    /// stored point, so retried writes only move `updated_at`.
+    async fn keep_created_at(
+        memory_store: &QdrantVectorStore,
+        memories: Vec<&mut generated::Memory>,
+    ) -> Result<()> {
+        let ids = memories
+            .iter()
+            .filter(|memory| !memory.idempotency_key.is_empty())
+            .map(|memory| memory.memory_id.as_str())
+            .collect::<Vec<_>>();
+        let stored = memory_store
+            .get_points(ids)
+            .await
+            .map_err(ControllerError::vector_store)?
+            .into_iter()
+            .map(|point| {
+                serde_json::from_value::<generated::Memory>(json!(point.payload))
+                    .map(|stored| (stored.memory_id, stored.created_at))
+                    .map_err(|e| {
+                        ControllerError::Internal(
+                            anyhow::Error::new(e).context("Payload to Memory parse failed."),
+                        )
+                    })
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+        restore_created_at(memories, &stored);
+        Ok(())
+    }
+
+    /// Extracts the text of an uploaded file, chunks it and stores every chunk as a
+    /// memory linked to a document record. Uploading the same bytes again replaces the
+    /// chunks of the earlier upload. PDF pages whose text cannot be extracted are left
+    /// out and reported rather than failing the upload.
+    pub async fn ingest_document(
+        parameters: generated::IngestDocumentParameters,
+    ) -> Result<generated::IngestDocumentResponse> {
+        if parameters.user_id.is_empty() {
+            return Err(ControllerError::validation("user_id", "cannot be empty"));
+        }
+        if parameters.content.is_empty() {
+            return Err(ControllerError::validation("content", "cannot be empty"));
+        }
+        if parameters.content.len() > MAX_DOCUMENT_BYTES {
+            return Err(ControllerError::validation(
+                "content",
+                format!("cannot be larger than {MAX_DOCUMENT_BYTES} bytes"),
+            ));
+        }
+
+        let generated::IngestDocumentParameters {
+            user_id,
+            filename,
+            mime_type,
+            content,
+            title,
+            priority,
+            tags,
+        } = parameters;
+        let content_hash = document::content_hash(&content);
+        let extracted = {
+            let filename = filename.clone();
+            let mime_type = mime_type.clone();
+            tokio::task::spawn_blocking(move || {
+                Extractor::extract_document(&content, &filename, &mime_type)
+            })
+            .await
+            .map_err(anyhow::Error::from)?
+        }
+        .map_err(|e| {
+            ControllerError::validation("content", format!("could not extract text: {e:#}"))
+        })?;
+
+        let strategy = if extracted.is_markdown {
+            ChunkStrategy::Markdown {
+                max_tokens: CHUNK_STRATEGY.max_tokens(),
+            }
+        } else {
+            *CHUNK_STRATEGY
         };
+        let chunks = chunk(&strategy, &extracted.text).await?;
+        if chunks.is_empty() {
+            return Err(ControllerError::validation(
+                "content",
+                "no text could be extracted",
+            ));
+        }
 
-        let vectors = CFEmbeder
-            .generate_embedding(memory.content.as_str())
-            .await?;
-        memory_store
-            .insert_embedding(memory.clone(), vectors)
-            .await?;
-        Ok(memory)
+        let now = chrono::Utc::now().timestamp();
+        let metadata = extracted.metadata.clone();
+        let document = generated::Document {
+            document_id: document::document_id(&user_id, &content_hash),
+            user_id,
+            title: if title.is_empty() {
+                metadata.title.unwrap_or_else(|| filename.clone())
+            } else {
+                title
+            },
+            source: filename,
+            mime_type,
+            content_hash,
+            created_at: now,
+            chunk_count: chunks.len() as u32,
+            author: metadata.author.unwrap_or_default(),
+            authored_at: metadata.created_at.unwrap_or_default(),
+            page_count: extracted.pages.len() as u32,
+            language: metadata.language.unwrap_or_default(),
+            ..Default::default()
+        };
+        Self::store_document(document, chunks, priority, tags, Some(&extracted)).await
     }
 
-    pub async fn add_memory_bulk(memory_bulk: generated::MemoryBulk) -> Result<()> {
-        let memory_store = get_memory_store().await;
+    /// Fetches a public web page and stores it like [`MemoryController::ingest_html`].
+    /// A URL ingested before is fetched conditionally on the validators its server
+    /// sent then, and is left as stored when the server answers it has not changed.
+    pub async fn ingest_url(
+        mut parameters: generated::IngestUrlParameters,
+    ) -> Result<generated::IngestDocumentResponse> {
+        if parameters.user_id.is_empty() {
+            return Err(ControllerError::validation("user_id", "cannot be empty"));
+        }
+        if parameters.url.is_empty() {
+            return Err(ControllerError::validation("url", "cannot be empty"));
+        }
 
-        let texts = memory_bulk
-            .memories
+        let allow_private_urls = allow_private_urls();
+        if !allow_private_urls {
+            parameters.url = Scrapper::ensure_public_url(&parameters.url)
+                .await
+                .map_err(|e| ControllerError::validation("url", format!("{e:#}")))?
+                .to_string();
+        }
+        let previous = Self::find_document(&document::url_document_id(
+            &parameters.user_id,
+            &parameters.url,
+        ))
+        .await?;
+        let validators = previous
+            .as_ref()
+            .map(document::validators)
+            .unwrap_or_default();
+        let fetched = if allow_private_urls {
+            Scrapper::scrape_if_changed(&parameters.url, &validators).await
+        } else {
+            Scrapper::scrape_public_if_changed(&parameters.url, &validators).await
+        }
+        .map_err(|e| ControllerError::validation("url", format!("could not fetch: {e:#}")))?;
+        match (fetched, previous) {
+            (Fetched::Modified { html, validators }, previous) => {
+                Self::store_web_page(parameters, html, validators, previous).await
+            }
+            (Fetched::NotModified, Some(previous)) => {
+                Self::retag_web_page(&previous, parameters.priority, parameters.tags).await?;
+                Ok(generated::IngestDocumentResponse {
+                    document: Some(previous),
+                    unchanged: true,
+                    ..Default::default()
+                })
+            }
+            (Fetched::NotModified, None) => Err(ControllerError::validation(
+                "url",
+                "the server answered 304 Not Modified to an unconditional request",
+            )),
+        }
+    }
+
+    /// Keeps the main content of a fetched web page as Markdown, chunks it by heading
+    /// and stores every chunk as a memory tagged with the page URL and title. The
+    /// document record takes the byline, publication date, canonical URL and language
+    /// the page declares. Ingesting the same URL again replaces the chunks stored for
+    /// it before, unless its text is unchanged.
+    pub async fn ingest_html(
+        parameters: generated::IngestUrlParameters,
+        html: &str,
+    ) -> Result<generated::IngestDocumentResponse> {
+        let previous = Self::find_document(&document::url_document_id(
+            &parameters.user_id,
+            &parameters.url,
+        ))
+        .await?;
+        Self::store_web_page(
+            parameters,
+            html.to_string(),
+            Validators::default(),
+            previous,
+        )
+        .await
+    }
+
+    /// Stores a web page for [`MemoryController::ingest_html`]. When the text is the
+    /// one `previous` was made from, only the new `validators`, priority and tags are
+    /// recorded.
+    async fn store_web_page(
+        parameters: generated::IngestUrlParameters,
+        html: String,
+        validators: Validators,
+        previous: Option<generated::Document>,
+    ) -> Result<generated::IngestDocumentResponse> {
+        let generated::IngestUrlParameters {
+            user_id,
+            url,
+            priority,
+            tags,
+        } = parameters;
+        let page = {
+            let url = url.clone();
+            tokio::task::spawn_blocking(move || Extractor::extract_article(&html, &url))
+                .await
+                .map_err(anyhow::Error::from)?
+        };
+        let content_hash = document::content_hash(page.text.as_bytes());
+        let etag = validators.etag.unwrap_or_default();
+        let last_modified = validators.last_modified.unwrap_or_default();
+
+        if let Some(previous) = previous.filter(|previous| previous.content_hash == content_hash) {
+            let document = generated::Document {
+                etag,
+                last_modified,
+                ..previous
+            };
+            get_document_store()
+                .await?
+                .insert_record(document.clone())
+                .await
+                .map_err(ControllerError::vector_store)?;
+            Self::retag_web_page(&document, priority, tags).await?;
+            return Ok(generated::IngestDocumentResponse {
+                document: Some(document),
+                unchanged: true,
+                ..Default::default()
+            });
+        }
+
+        let strategy = ChunkStrategy::Markdown {
+            max_tokens: CHUNK_STRATEGY.max_tokens(),
+        };
+        let chunks = chunk(&strategy, &page.text).await?;
+        if chunks.is_empty() {
+            return Err(ControllerError::validation("url", "the page has no text"));
+        }
+
+        let metadata = page.metadata;
+        let title = metadata
+            .title
+            .or_else(|| document::markdown_title(&page.text))
+            .unwrap_or_else(|| url.clone());
+        let tags = document::web_page_tags(tags, &url, &title);
+        let document = generated::Document {
+            document_id: document::url_document_id(&user_id, &url),
+            user_id,
+            title,
+            content_hash,
+            source: url,
+            mime_type: "text/html".to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            chunk_count: chunks.len() as u32,
+            author: metadata.author.unwrap_or_default(),
+            authored_at: metadata.created_at.unwrap_or_default(),
+            canonical_url: metadata.canonical_url.unwrap_or_default(),
+            language: metadata.language.unwrap_or_default(),
+            etag,
+            last_modified,
+            ..Default::default()
+        };
+        Self::store_document(document, chunks, priority, tags, None).await
+    }
+
+    /// Re-ingests the web pages stored for `user_id`, or for every tenant when it is
+    /// empty, with the priority and tags their chunks have. Pages are fetched
+    /// conditionally, so a run over unchanged pages embeds nothing. Meant to be run on
+    /// a schedule; every page gets its own result so one failing site does not stop
+    /// the others.
+    pub async fn refresh_urls(
+        user_id: &str,
+    ) -> Result<Vec<(String, Result<generated::IngestDocumentResponse>)>> {
+        let document_store = get_document_store().await?;
+
+        let mut filter = vec![("mime_type".to_string(), "text/html".to_string())];
+        if !user_id.is_empty() {
+            filter.push(("user_id".to_string(), user_id.to_string()));
+        }
+        let mut results = Vec::new();
+        let mut offset = None;
+        loop {
+            let page = document_store
+                .scroll_with_payload(filter.clone(), document::REFRESH_PAGE_SIZE, offset)
+                .await
+                .map_err(ControllerError::vector_store)?;
+            for point in page.result {
+                let payload = json!(point.payload);
+                let document = match serde_json::from_value::<generated::Document>(payload.clone())
+                {
+                    Ok(document) => document,
+                    Err(e) => {
+                        let source = payload["source"].as_str().unwrap_or_default().to_string();
+                        let error =
+                            anyhow::Error::new(e).context("Payload to Document parse failed.");
+                        results.push((source, Err(ControllerError::Internal(error))));
+                        continue;
+                    }
+                };
+                // Uploaded HTML files share the MIME type but not the URL derived id.
+                if document.document_id
+                    != document::url_document_id(&document.user_id, &document.source)
+                {
+                    continue;
+                }
+
+                let first_chunk = match Self::first_chunk(&document).await {
+                    Ok(first_chunk) => first_chunk.unwrap_or_default(),
+                    Err(e) => {
+                        results.push((document.source, Err(e)));
+                        continue;
+                    }
+                };
+                let tags = first_chunk
+                    .tags
+                    .into_iter()
+                    .filter(|tag| !document::is_web_page_tag(tag))
+                    .collect();
+                let parameters = generated::IngestUrlParameters {
+                    user_id: document.user_id,
+                    url: document.source.clone(),
+                    priority: first_chunk.priority,
+                    tags,
+                };
+                results.push((document.source, Self::ingest_url(parameters).await));
+            }
+
+            offset = page.next_page_offset;
+            if offset.is_none() {
+                return Ok(results);
+            }
+        }
+    }
+
+    async fn first_chunk(document: &generated::Document) -> Result<Option<generated::Memory>> {
+        get_memory_store()
+            .await?
+            .get_point(&document::chunk_id(&document.document_id, 0))
+            .await
+            .map_err(ControllerError::vector_store)?
+            .map(|point| {
+                serde_json::from_value(json!(point.payload)).map_err(|e| {
+                    ControllerError::Internal(
+                        anyhow::Error::new(e).context("Payload to Memory parse failed."),
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// Gives the chunks of an unchanged web page the `priority` and `tags` it was
+    /// ingested with again, when they differ from the stored ones.
+    async fn retag_web_page(
+        document: &generated::Document,
+        priority: i32,
+        tags: Vec<String>,
+    ) -> Result<()> {
+        let tags = document::web_page_tags(tags, &document.source, &document.title);
+        let first_chunk = Self::first_chunk(document).await?;
+        if first_chunk.is_some_and(|chunk| chunk.priority == priority && chunk.tags == tags) {
+            return Ok(());
+        }
+        let ids = (0..document.chunk_count as usize)
+            .map(|index| document::chunk_id(&document.document_id, index))
+            .collect::<Vec<_>>();
+        get_memory_store()
+            .await?
+            .set_payload_bulk(
+                ids.iter().map(String::as_str).collect(),
+                json!({ "priority": priority, "tags": tags }),
+            )
+            .await
+            .map_err(ControllerError::vector_store)
+    }
+
+    /// Embeds `chunks` and stores them as the memories of `document`, replacing any
+    /// chunks it had before, then stores the document record. Chunks whose text the
+    /// stored version of the document already had keep their embeddings. Chunks of
+    /// the `extracted` text get the pages they span.
+    async fn store_document(
+        document: generated::Document,
+        chunks: Vec<Chunk>,
+        priority: i32,
+        tags: Vec<String>,
+        extracted: Option<&ExtractedDocument>,
+    ) -> Result<generated::IngestDocumentResponse> {
+        let now = document.created_at;
+        let memories = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(chunk_index, chunk)| {
+                let (page_start, page_end) = extracted
+                    .and_then(|extracted| extracted.page_range(chunk.start, chunk.end))
+                    .unwrap_or_default();
+                generated::Memory {
+                    user_id: document.user_id.clone(),
+                    memory_id: document::chunk_id(&document.document_id, chunk_index),
+                    content: chunk.text,
+                    priority,
+                    tags: tags.clone(),
+                    created_at: now,
+                    updated_at: now,
+                    document_id: document.document_id.clone(),
+                    chunk_index: chunk_index as u32,
+                    chunk_start: chunk.start as u32,
+                    chunk_end: chunk.end as u32,
+                    page_start,
+                    page_end,
+                    ..Default::default()
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let memory_store = get_memory_store().await?;
+        let document_store = get_document_store().await?;
+        let previous_ids = Self::find_document(&document.document_id)
+            .await?
+            .map(|previous| {
+                (0..previous.chunk_count as usize)
+                    .map(|chunk_index| document::chunk_id(&previous.document_id, chunk_index))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let mut vectors = memory_store
+            .get_points_with_vectors(previous_ids.iter().map(String::as_str).collect())
+            .await
+            .map_err(ControllerError::vector_store)?
+            .into_iter()
+            .map(|(point, vector)| {
+                let memory: generated::Memory = serde_json::from_value(json!(point.payload))
+                    .expect("Payload to Memory parse failed.");
+                (memory.content, vector)
+            })
+            .collect::<HashMap<_, _>>();
+        let mut new_texts = memories
             .iter()
             .map(|memory| memory.content.as_str())
-            .collect();
-
-        let vectors: Vec<Vec<f32>> = CFEmbeder.generate_embeddings_bulk(texts).await?;
+            .filter(|text| !vectors.contains_key(*text))
+            .collect::<Vec<_>>();
+        new_texts.sort_unstable();
+        new_texts.dedup();
+        let new_vectors = if new_texts.is_empty() {
+            Vec::new()
+        } else {
+            CFEmbeder
+                .generate_embeddings_bulk(new_texts.clone())
+                .await
+                .map_err(ControllerError::embedding)?
+        };
+        let embedded_chunks = memories
+            .iter()
+            .filter(|memory| !vectors.contains_key(&memory.content))
+            .count() as u32;
+        vectors.extend(
+            std::iter::zip(new_texts, new_vectors).map(|(text, vector)| (text.to_string(), vector)),
+        );
 
+        // Chunks go in before the document record so a failed upload never leaves a
+        // document without its text. Chunk ids follow their index, so the new chunks
+        // overwrite the old ones and only those past the new end are left to delete.
         memory_store
             .insert_embeddings_bulk(
-                std::iter::zip(memory_bulk.memories, vectors).collect::<Vec<_>>(),
+                memories
+                    .iter()
+                    .map(|memory| (memory.clone(), vectors[&memory.content].clone()))
+                    .collect(),
             )
-            .await?;
+            .await
+            .map_err(ControllerError::vector_store)?;
+        if previous_ids.len() > memories.len() {
+            memory_store
+                .delete_points_bulk(
+                    previous_ids[memories.len()..]
+                        .iter()
+                        .map(String::as_str)
+                        .collect(),
+                )
+                .await
+                .map_err(ControllerError::vector_store)?;
+        }
+        document_store
+            .insert_record(document.clone())
+            .await
+            .map_err(ControllerError::vector_store)?;
 
-        Ok(())
+        Ok(generated::IngestDocumentResponse {
+            document: Some(document),
+            chunks: memories,
+            failed_pages: extracted
+                .map(|extracted| {
+                    extracted
+                        .failed_pages
+                        .iter()
+                        .map(|failure| generated::PageFailure {
+                            page: failure.number,
+                            reason: failure.reason.clone(),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            embedded_chunks,
+            ..Default::default()
+        })
     }
 
     pub async fn update_memory(
-        update_memory_parameters: generated::UpdateMemoryParameters,
+        mut update_memory_parameters: generated::UpdateMemoryParameters,
     ) -> Result<()> {
-        let memory_store = get_memory_store().await;
+        if update_memory_parameters.memory_id.is_empty() {
+            return Err(ControllerError::validation("memory_id", "cannot be empty"));
+        }
+        if update_memory_parameters.content.is_empty() {
+            return Err(ControllerError::validation("content", "cannot be empty"));
+        }
+        let memory_store = get_memory_store().await?;
+
+        let memory = Self::get_owned_memory(
+            &update_memory_parameters.memory_id,
+            &update_memory_parameters.user_id,
+        )
+        .await?;
+        update_memory_parameters.user_id = memory.user_id;
 
-        let vectors = CFEmbeder
-            .generate_embedding(update_memory_parameters.content.as_str())
-            .await?;
+        let vectors = embed_memory(&update_memory_parameters.content).await?;
 
         memory_store
             .update_point(
@@ -90,7 +722,8 @@ impl MemoryController {
                 Some(vectors),
                 Some(update_memory_parameters),
             )
-            .await?;
+            .await
+            .map_err(ControllerError::vector_store)?;
 
         Ok(())
     }
@@ -98,45 +731,267 @@ impl MemoryController {
     pub async fn delete_memory(
         delete_memory_parameters: generated::DeleteMemoryParameters,
     ) -> Result<()> {
-        let memory_store = get_memory_store().await;
+        if delete_memory_parameters.memory_id.is_empty() {
+            return Err(ControllerError::validation("memory_id", "cannot be empty"));
+        }
+        let memory_store = get_memory_store().await?;
+
+        Self::get_owned_memory(
+            &delete_memory_parameters.memory_id,
+            &delete_memory_parameters.user_id,
+        )
+        .await?;
 
         memory_store
             .delete_point(delete_memory_parameters.memory_id.as_str())
-            .await?;
+            .await
+            .map_err(ControllerError::vector_store)?;
+
+        Ok(())
+    }
+
+    /// Deletes a document record and every chunk stored for it.
+    pub async fn delete_document(
+        delete_document_parameters: generated::DeleteDocumentParameters,
+    ) -> Result<()> {
+        let generated::DeleteDocumentParameters {
+            document_id,
+            user_id,
+        } = delete_document_parameters;
+        if document_id.is_empty() {
+            return Err(ControllerError::validation(
+                "document_id",
+                "cannot be empty",
+            ));
+        }
+        let memory_store = get_memory_store().await?;
+        let document_store = get_document_store().await?;
+
+        let document = Self::find_document(&document_id)
+            .await?
+            .ok_or_else(|| ControllerError::document_not_found(&document_id))?;
+        if !user_id.is_empty() && document.user_id != user_id {
+            return Err(ControllerError::document_not_found(&document_id));
+        }
+
+        // Chunks go first so a failed delete can be retried from the document.
+        memory_store
+            .delete_points_by_payload(vec![
+                ("document_id".to_string(), document_id.clone()),
+                ("user_id".to_string(), document.user_id),
+            ])
+            .await
+            .map_err(ControllerError::vector_store)?;
+        document_store
+            .delete_point(&document_id)
+            .await
+            .map_err(ControllerError::vector_store)?;
+
+        Ok(())
+    }
+
+    async fn find_document(document_id: &str) -> Result<Option<generated::Document>> {
+        let point = get_document_store()
+            .await?
+            .get_point(document_id)
+            .await
+            .map_err(ControllerError::vector_store)?;
+        Ok(point.map(|point| {
+            serde_json::from_value(json!(point.payload)).expect("Payload to Document parse failed.")
+        }))
+    }
 
+    /// Fetches a memory, treating memories of other tenants as missing.
+    /// An empty `user_id` skips the tenant check.
+    async fn get_owned_memory(memory_id: &str, user_id: &str) -> Result<generated::Memory> {
+        let memory_store = get_memory_store().await?;
+
+        let point = memory_store
+            .get_point(memory_id)
+            .await
+            .map_err(ControllerError::vector_store)?
+            .ok_or_else(|| ControllerError::memory_not_found(memory_id))?;
+        let memory: generated::Memory =
+            serde_json::from_value(json!(point.payload)).expect("Payload to Memory parse failed.");
+        if !user_id.is_empty() && memory.user_id != user_id {
+            return Err(ControllerError::memory_not_found(memory_id));
+        }
+
+        Ok(memory)
+    }
+
+    /// Checks that the vector store answers.
+    pub async fn check_vector_store() -> Result<()> {
+        get_memory_store()
+            .await?
+            .health_check()
+            .await
+            .map_err(ControllerError::vector_store)
+    }
+
+    /// Checks that the embedding backend is taking requests. This reads the circuit
+    /// breakers unless `HEALTH_CHECK_EMBEDDING_PROBE` asks for a real, billed embedding
+    /// of a short probe text.
+    pub async fn check_embedder() -> Result<()> {
+        if !*HEALTH_CHECK_EMBEDDING_PROBE {
+            return if CFEmbeder.is_available() {
+                Ok(())
+            } else {
+                Err(ControllerError::EmbeddingUnavailable(anyhow::anyhow!(
+                    "every embedding provider is failing"
+                )))
+            };
+        }
+        // Bypasses the cache, which would otherwise answer the probe forever.
+        CFEmbeder
+            .inner()
+            .generate_embedding(HEALTH_PROBE)
+            .await
+            .map_err(ControllerError::embedding)?;
         Ok(())
     }
 
+    /// Starts the task that lets concurrent single embeddings share backend requests.
+    /// Runs until the calling runtime shuts down.
+    pub fn spawn_embedding_coalescer() -> tokio::task::JoinHandle<()> {
+        CFEmbeder.inner().spawn_coalescer()
+    }
+
+    /// Hit and miss counts of the embedding cache.
+    pub fn embedding_cache_stats() -> CacheStats {
+        CFEmbeder.stats()
+    }
+
     /// Qdrant Queries
     pub async fn get_memories_by_query(
         get_memories_by_query_parameters: generated::GetMemoriesByQueryParameters,
-    ) -> Result<generated::MemoryBulk> {
-        let memory_store = get_memory_store().await;
+    ) -> Result<generated::MemorySearchResponse> {
+        if get_memories_by_query_parameters.user_id.is_empty() {
+            return Err(ControllerError::validation("user_id", "cannot be empty"));
+        }
+        if get_memories_by_query_parameters.query.is_empty() {
+            return Err(ControllerError::validation("query", "cannot be empty"));
+        }
+        if get_memories_by_query_parameters.neighbor_chunks > document::MAX_NEIGHBOR_CHUNKS {
+            return Err(ControllerError::validation(
+                "neighbor_chunks",
+                format!("cannot be more than {}", document::MAX_NEIGHBOR_CHUNKS),
+            ));
+        }
+        let memory_store = get_memory_store().await?;
 
         let vector = CFEmbeder
             .generate_embedding(&get_memories_by_query_parameters.query)
-            .await?;
+            .await
+            .map_err(ControllerError::embedding)?;
 
         let search_response = memory_store
             .search_with_vector(vector, Some(10), &get_memories_by_query_parameters.user_id)
-            .await?;
+            .await
+            .map_err(ControllerError::vector_store)?;
 
-        Ok(generated::MemoryBulk {
-            memories: search_response
-                .result
+        let memories = search_response
+            .result
+            .into_iter()
+            .map(|scored_point| {
+                serde_json::from_value(json!(scored_point.payload))
+                    .expect("Payload to Memory parse failed.")
+            })
+            .collect::<Vec<_>>();
+        let contexts = Self::chunk_contexts(&memories, &get_memories_by_query_parameters).await?;
+
+        Ok(generated::MemorySearchResponse { memories, contexts })
+    }
+
+    /// Fetches the parent documents and neighbor chunks the query options ask for and
+    /// builds one context per hit on a document chunk.
+    async fn chunk_contexts(
+        memories: &[generated::Memory],
+        options: &generated::GetMemoriesByQueryParameters,
+    ) -> Result<Vec<generated::ChunkContext>> {
+        if !options.include_document && options.neighbor_chunks == 0 && !options.merge_window {
+            return Ok(Vec::new());
+        }
+        let hits = memories
+            .iter()
+            .filter(|memory| !memory.document_id.is_empty())
+            .collect::<Vec<_>>();
+        if hits.is_empty() {
+            return Ok(Vec::new());
+        }
+        let radius = if options.merge_window {
+            options.neighbor_chunks.max(1)
+        } else {
+            options.neighbor_chunks
+        };
+
+        let mut chunk_ids = hits
+            .iter()
+            .flat_map(|hit| document::neighbor_ids(hit, radius))
+            .collect::<Vec<_>>();
+        chunk_ids.sort();
+        chunk_ids.dedup();
+        let chunks = get_memory_store()
+            .await?
+            .get_points(chunk_ids.iter().map(String::as_str).collect())
+            .await
+            .map_err(ControllerError::vector_store)?
+            .into_iter()
+            .map(|point| {
+                serde_json::from_value::<generated::Memory>(json!(point.payload))
+                    .expect("Payload to Memory parse failed.")
+            })
+            .filter(|chunk| chunk.user_id == options.user_id)
+            .map(|chunk| (chunk.memory_id.clone(), chunk))
+            .collect::<HashMap<_, _>>();
+
+        let documents = if options.include_document {
+            let mut document_ids = hits
+                .iter()
+                .map(|hit| hit.document_id.as_str())
+                .collect::<Vec<_>>();
+            document_ids.sort();
+            document_ids.dedup();
+            get_document_store()
+                .await?
+                .get_points(document_ids)
+                .await
+                .map_err(ControllerError::vector_store)?
                 .into_iter()
-                .map(|scored_point| {
-                    serde_json::from_value(json!(scored_point.payload))
-                        .expect("Payload to Memory parse failed.")
+                .map(|point| {
+                    serde_json::from_value::<generated::Document>(json!(point.payload))
+                        .expect("Payload to Document parse failed.")
                 })
-                .collect::<Vec<_>>(),
-        })
+                .filter(|document| document.user_id == options.user_id)
+                .map(|document| (document.document_id.clone(), document))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(hits
+            .into_iter()
+            .map(|hit| {
+                document::chunk_context(hit, radius, options.merge_window, &chunks, &documents)
+            })
+            .map(|context| generated::ChunkContext {
+                neighbors: if options.neighbor_chunks > 0 {
+                    context.neighbors
+                } else {
+                    Vec::new()
+                },
+                ..context
+            })
+            .collect())
     }
 
     pub async fn get_memories_by_user_id(
         get_memories_by_user_id_parameters: generated::GetMemoriesByUserIdParameters,
     ) -> Result<generated::MemoryBulk> {
-        let memory_store = get_memory_store().await;
+        if get_memories_by_user_id_parameters.user_id.is_empty() {
+            return Err(ControllerError::validation("user_id", "cannot be empty"));
+        }
+        let memory_store = get_memory_store().await?;
 
         let search_response = memory_store
             .search_with_payload(
@@ -146,7 +1001,8 @@ impl MemoryController {
                 )],
                 None,
             )
-            .await?;
+            .await
+            .map_err(ControllerError::vector_store)?;
 
         Ok(generated::MemoryBulk {
             memories: search_response
@@ -159,4 +1015,190 @@ impl MemoryController {
                 .collect::<Vec<_>>(),
         })
     }
+
+    /// Ranks the memories closest to `query` and packs them into a prompt-ready context
+    /// block that fits within `token_budget`.
+    pub async fn recall_context(
+        recall_context_parameters: generated::RecallContextParameters,
+    ) -> Result<generated::MemoryContext> {
+        if recall_context_parameters.user_id.is_empty() {
+            return Err(ControllerError::validation("user_id", "cannot be empty"));
+        }
+        if recall_context_parameters.query.is_empty() {
+            return Err(ControllerError::validation("query", "cannot be empty"));
+        }
+        let memory_store = get_memory_store().await?;
+
+        let vector = CFEmbeder
+            .generate_embedding(&recall_context_parameters.query)
+            .await
+            .map_err(ControllerError::embedding)?;
+
+        let search_response = memory_store
+            .search_with_vector(
+                vector,
+                Some(context::CANDIDATE_LIMIT),
+                &recall_context_parameters.user_id,
+            )
+            .await
+            .map_err(ControllerError::vector_store)?;
+
+        let candidates = search_response
+            .result
+            .into_iter()
+            .map(|scored_point| ScoredMemory {
+                relevance: scored_point.score,
+                memory: serde_json::from_value(json!(scored_point.payload))
+                    .expect("Payload to Memory parse failed."),
+            })
+            .collect::<Vec<_>>();
+
+        let packer = ContextPacker::new(
+            recall_context_parameters.token_budget,
+            chrono::Utc::now().timestamp(),
+        );
+        Ok(packer.pack(candidates))
+    }
+}
+
+async fn chunk(strategy: &ChunkStrategy, text: &str) -> Result<Vec<Chunk>> {
+    strategy
+        .chunk(text, &*CFEmbeder)
+        .await
+        .map_err(ControllerError::embedding)
+}
+
+/// Content past `MEMORY_CHUNK_THRESHOLD_TOKENS` is chunked, and its embedding is the
+/// mean of the chunk embeddings, so text beyond the model's context still counts.
+async fn embed_memory(content: &str) -> Result<Vec<f32>> {
+    if estimate_tokens(content) <= *MEMORY_CHUNK_THRESHOLD_TOKENS {
+        return CFEmbeder
+            .generate_embedding(content)
+            .await
+            .map_err(ControllerError::embedding);
+    }
+    Ok(embed_memories(&[content]).await?.swap_remove(0))
+}
+
+/// Embeds every content in one bulk request, chunking long ones like [`embed_memory`].
+async fn embed_memories(contents: &[&str]) -> Result<Vec<Vec<f32>>> {
+    let mut pieces = Vec::new();
+    let mut ranges = Vec::with_capacity(contents.len());
+    for content in contents {
+        let start = pieces.len();
+        if estimate_tokens(content) > *MEMORY_CHUNK_THRESHOLD_TOKENS {
+            pieces.extend(
+                chunk(&CHUNK_STRATEGY, content)
+                    .await?
+                    .into_iter()
+                    .map(|chunk| chunk.text),
+            );
+        }
+        if pieces.len() == start {
+            pieces.push(content.to_string());
+        }
+        ranges.push(start..pieces.len());
+    }
+
+    let vectors = CFEmbeder
+        .generate_embeddings_bulk(pieces.iter().map(String::as_str).collect())
+        .await
+        .map_err(ControllerError::embedding)?;
+    Ok(ranges
+        .into_iter()
+        .map(|range| match &vectors[range] {
+            [vector] => vector.clone(),
+            chunks => mean_embedding(chunks),
+        })
+        .collect())
+}
+
+/// Self-hosted deployments can set `INGEST_ALLOW_PRIVATE_URLS=true` to ingest pages
+/// from their own network.
+fn allow_private_urls() -> bool {
+    std::env::var("INGEST_ALLOW_PRIVATE_URLS").is_ok_and(|value| value == "true")
+}
+
+/// Sets the `created_at` of every memory in `stored`, by id, to the stored one.
+fn restore_created_at(memories: Vec<&mut generated::Memory>, stored: &HashMap<String, i64>) {
+    for memory in memories {
+        if let Some(&created_at) = stored.get(&memory.memory_id) {
+            memory.created_at = created_at;
+        }
+    }
+}
+
+/// A random id, or when the client supplied an idempotency key, one derived from the
+/// tenant and the key so retried writes land on the same point.
+fn memory_id(user_id: &str, idempotency_key: &str) -> String {
+    if idempotency_key.is_empty() {
+        return Uuid::new_v4().to_string();
+    }
+    // Length prefixed so ("a:b", "c") and ("a", "b:c") never collide.
+    let name = format!("{}:{}:{}", user_id.len(), user_id, idempotency_key);
+    Uuid::new_v5(&MEMORY_ID_NAMESPACE, name.as_bytes()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idempotency_key_gives_stable_id() {
+        assert_eq!(memory_id("user-1", "job-42"), memory_id("user-1", "job-42"));
+        assert_ne!(memory_id("user-1", "job-42"), memory_id("user-1", "job-43"));
+    }
+
+    #[test]
+    fn test_idempotency_keys_are_scoped_to_tenant() {
+        assert_ne!(memory_id("user-1", "job-42"), memory_id("user-2", "job-42"));
+        assert_ne!(memory_id("a:b", "c"), memory_id("a", "b:c"));
+    }
+
+    #[test]
+    fn test_without_key_ids_are_random() {
+        assert_ne!(memory_id("user-1", ""), memory_id("user-1", ""));
+    }
+
+    #[test]
+    fn test_retried_writes_keep_created_at() {
+        let prepare = |now| {
+            let memory = generated::Memory {
+                user_id: "user-1".to_string(),
+                content: "note".to_string(),
+                idempotency_key: "job-42".to_string(),
+                ..Default::default()
+            };
+            MemoryController::prepare_memory(memory, now).unwrap()
+        };
+        let first = prepare(100);
+        let stored = HashMap::from([(first.memory_id.clone(), first.created_at)]);
+
+        let mut retry = prepare(200);
+        let mut other = generated::Memory {
+            memory_id: memory_id("user-1", "job-43"),
+            created_at: 200,
+            ..Default::default()
+        };
+        restore_created_at(vec![&mut retry, &mut other], &stored);
+        assert_eq!(retry.memory_id, first.memory_id);
+        assert_eq!((retry.created_at, retry.updated_at), (100, 200));
+        assert_eq!(other.created_at, 200);
+    }
+
+    #[test]
+    fn test_prepare_memory_rejects_oversized_key() {
+        let memory = generated::Memory {
+            content: "note".to_string(),
+            idempotency_key: "k".repeat(MAX_IDEMPOTENCY_KEY_LEN + 1),
+            ..Default::default()
+        };
+        assert!(matches!(
+            MemoryController::prepare_memory(memory, 0),
+            Err(ControllerError::Validation {
+                field: "idempotency_key",
+                ..
+            })
+        ));
+    }
 }
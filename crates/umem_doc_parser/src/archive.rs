@@ -0,0 +1,106 @@
+//! Entries of the ZIP archives DOCX and EPUB files are, inflated within a budget for
+//! the whole archive so a small upload cannot expand without bound.
+
+use anyhow::{Context, Result, bail};
+use std::io::{Cursor, Read};
+use zip::{ZipArchive, result::ZipError};
+
+const LOCAL_FILE_HEADER: &[u8] = b"PK\x03\x04";
+/// Bytes all entries read from one archive may inflate to together.
+const MAX_INFLATED_BYTES: u64 = 64 * 1024 * 1024;
+
+pub(crate) struct Archive<'a> {
+    zip: ZipArchive<Cursor<&'a [u8]>>,
+    remaining: u64,
+}
+
+impl<'a> Archive<'a> {
+    pub(crate) fn is_zip(bytes: &[u8]) -> bool {
+        bytes.starts_with(LOCAL_FILE_HEADER)
+    }
+
+    pub(crate) fn new(bytes: &'a [u8]) -> Result<Self> {
+        Self::with_budget(bytes, MAX_INFLATED_BYTES)
+    }
+
+    fn with_budget(bytes: &'a [u8], budget: u64) -> Result<Self> {
+        Ok(Self {
+            zip: ZipArchive::new(Cursor::new(bytes)).context("Not a ZIP archive")?,
+            remaining: budget,
+        })
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.zip.index_for_name(name).is_some()
+    }
+
+    /// The inflated content of entry `name`, if the archive has it.
+    pub(crate) fn read(&mut self, name: &str) -> Result<Option<Vec<u8>>> {
+        let entry = match self.zip.by_name(name) {
+            Ok(entry) => entry,
+            Err(ZipError::FileNotFound) => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("Corrupt ZIP entry {name}")),
+        };
+        let mut content = Vec::new();
+        entry
+            .take(self.remaining + 1)
+            .read_to_end(&mut content)
+            .with_context(|| format!("Corrupt ZIP entry {name}"))?;
+        let len = content.len() as u64;
+        if len > self.remaining {
+            bail!("ZIP archive inflates to more than {MAX_INFLATED_BYTES} bytes");
+        }
+        self.remaining -= len;
+        Ok(Some(content))
+    }
+
+    pub(crate) fn read_string(&mut self, name: &str) -> Result<Option<String>> {
+        Ok(self
+            .read(name)?
+            .map(|content| String::from_utf8_lossy(&content).into_owned()))
+    }
+}
+
+/// Builds an archive of deflated entries, for tests.
+#[cfg(test)]
+pub(crate) fn archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    use std::io::Write;
+    use zip::{CompressionMethod, ZipWriter, write::SimpleFileOptions};
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (name, content) in entries {
+        writer.start_file(*name, options).unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_entries() -> Result<()> {
+        let bytes = archive(&[("a.txt", b"first"), ("b/c.txt", b"second")]);
+        assert!(Archive::is_zip(&bytes));
+        let mut zip = Archive::new(&bytes)?;
+        assert!(zip.contains("b/c.txt"));
+        assert_eq!(zip.read_string("a.txt")?.as_deref(), Some("first"));
+        assert_eq!(zip.read_string("b/c.txt")?.as_deref(), Some("second"));
+        assert_eq!(zip.read("missing")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_budget_covers_the_whole_archive() -> Result<()> {
+        let zeros = vec![0; 600];
+        let bytes = archive(&[("a", &zeros), ("b", &zeros)]);
+        let mut zip = Archive::with_budget(&bytes, 1000)?;
+        assert!(zip.read("a")?.is_some());
+        // Reading the same entry again counts again.
+        assert!(zip.read("a").is_err());
+        assert!(zip.read("b").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+//! Text of CSV and TSV tables. Every row after the header becomes one line naming its
+//! columns, so a row still makes sense when it lands in a chunk without the header.
+
+use anyhow::{Context, Result};
+
+/// Non-blank rows of `text` split on `delimiter`, honouring double quoted fields.
+fn records(text: &str, delimiter: u8) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    for record in reader.records() {
+        let record = record.context("Malformed delimited text")?;
+        if record.iter().any(|field| !field.trim().is_empty()) {
+            records.push(record.iter().map(str::to_string).collect());
+        }
+    }
+    Ok(records)
+}
+
+pub(crate) fn extract(text: &str, delimiter: u8) -> Result<String> {
+    let mut records = records(text, delimiter)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(String::new());
+    };
+    let rows = records
+        .map(|record| {
+            record
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| !value.trim().is_empty())
+                .map(|(column, value)| match header.get(column) {
+                    Some(name) if !name.trim().is_empty() => {
+                        format!("{}: {}", name.trim(), value.trim())
+                    }
+                    _ => value.trim().to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join("; ")
+        })
+        .collect::<Vec<_>>();
+    if rows.is_empty() {
+        return Ok(header.join(" | "));
+    }
+    Ok(rows.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_name_their_columns() -> Result<()> {
+        let csv = "name,city,note\r\nAda,London,\"Wrote \"\"notes\"\", first\"\n\nAlan,,\"Multi\nline\"\n";
+        assert_eq!(
+            extract(csv, b',')?,
+            "name: Ada; city: London; note: Wrote \"notes\", first\nname: Alan; note: Multi\nline"
+        );
+        assert_eq!(extract("a\tb\n1\t2", b'\t')?, "a: 1; b: 2");
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+//! Text of Word documents, read from `word/document.xml`. Paragraphs styled as
+//! headings become Markdown headings so heading-aware chunking can use them.
+
+use crate::{archive::Archive, xml};
+use anyhow::{Context, Result};
+use quick_xml::events::Event;
+
+pub(crate) fn extract(bytes: &[u8]) -> Result<String> {
+    let mut archive = Archive::new(bytes)?;
+    let document = archive
+        .read_string("word/document.xml")?
+        .context("DOCX file has no word/document.xml")?;
+
+    let mut paragraphs = Vec::new();
+    let mut paragraph = String::new();
+    let mut heading_level = 0;
+    let mut in_text = false;
+    let mut cell_depth = 0;
+    let mut reader = xml::reader(&document);
+    loop {
+        let event = reader
+            .read_event()
+            .context("Malformed XML in word/document.xml")?;
+        let self_closing = matches!(event, Event::Empty(_));
+        match event {
+            Event::Start(element) | Event::Empty(element) => {
+                match xml::local_name(&element).as_str() {
+                    "t" if !self_closing => in_text = true,
+                    "tab" => paragraph.push('\t'),
+                    "br" | "cr" => paragraph.push('\n'),
+                    "tc" if !self_closing => cell_depth += 1,
+                    "pStyle" => {
+                        heading_level = xml::attribute(&element, "val")
+                            .map_or(0, |style| heading_level_of(&style));
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(element) => match element.local_name().as_ref() {
+                b"t" => in_text = false,
+                b"tc" => {
+                    cell_depth -= 1;
+                    paragraph.truncate(paragraph.trim_end().len());
+                    paragraph.push_str(" | ");
+                }
+                b"tr" => {
+                    let row = paragraph.trim().trim_end_matches('|').trim().to_string();
+                    if !row.is_empty() {
+                        paragraphs.push(row);
+                    }
+                    paragraph.clear();
+                }
+                // Paragraphs inside table cells stay on the row.
+                b"p" if cell_depth > 0 => paragraph.push(' '),
+                b"p" => {
+                    let text = paragraph.trim();
+                    if !text.is_empty() {
+                        paragraphs.push(if heading_level > 0 {
+                            format!("{} {text}", "#".repeat(heading_level))
+                        } else {
+                            text.to_string()
+                        });
+                    }
+                    paragraph.clear();
+                    heading_level = 0;
+                }
+                _ => {}
+            },
+            Event::Text(text) if in_text => paragraph.push_str(&xml::text(&text)),
+            Event::CData(text) if in_text => paragraph.push_str(&String::from_utf8_lossy(&text)),
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(paragraphs.join("\n\n"))
+}
+
+/// `Title` and `Heading1` to `Heading6`, as Word names its built-in styles.
+fn heading_level_of(style: &str) -> usize {
+    if style == "Title" {
+        return 1;
+    }
+    style
+        .strip_prefix("Heading")
+        .and_then(|level| level.parse().ok())
+        .filter(|level| (1..=6).contains(level))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::archive;
+
+    #[test]
+    fn test_extracts_headings_paragraphs_and_tables() -> Result<()> {
+        let document = r#"<?xml version="1.0" encoding="UTF-8"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>
+<w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Quarterly report</w:t></w:r></w:p>
+<w:p><w:r><w:t xml:space="preserve">Sales grew </w:t></w:r><w:r><w:t>12%.</w:t></w:r></w:p>
+<w:tbl><w:tr><w:tc><w:p><w:r><w:t>Region</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>EMEA</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+</w:body></w:document>"#;
+        let bytes = archive(&[("word/document.xml", document.as_bytes())]);
+        assert_eq!(
+            extract(&bytes)?,
+            "# Quarterly report\n\nSales grew 12%.\n\nRegion | EMEA"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+//! Text of EPUB books: the XHTML documents of the spine, in reading order, converted
+//! to Markdown.
+
+use crate::{archive::Archive, xml};
+use anyhow::{Context, Result};
+use quick_xml::events::Event;
+use std::collections::{HashMap, HashSet};
+
+pub(crate) fn extract(bytes: &[u8]) -> Result<String> {
+    let mut archive = Archive::new(bytes)?;
+    let container = archive
+        .read_string("META-INF/container.xml")?
+        .context("EPUB file has no META-INF/container.xml")?;
+    let mut package_path = None;
+    for_each_start(&container, |name, element| {
+        if name == "rootfile" && package_path.is_none() {
+            package_path = xml::attribute(element, "full-path");
+        }
+    })
+    .context("Malformed EPUB container")?;
+    let package_path = package_path.context("EPUB container names no package document")?;
+    let package = archive
+        .read_string(&package_path)?
+        .with_context(|| format!("EPUB file has no {package_path}"))?;
+
+    let mut manifest = HashMap::new();
+    let mut spine = Vec::new();
+    for_each_start(&package, |name, element| match name {
+        "item" => {
+            if let (Some(id), Some(href)) = (
+                xml::attribute(element, "id"),
+                xml::attribute(element, "href"),
+            ) {
+                manifest.insert(id, href);
+            }
+        }
+        "itemref" => spine.extend(xml::attribute(element, "idref")),
+        _ => {}
+    })
+    .with_context(|| format!("Malformed EPUB package document {package_path}"))?;
+
+    let base = package_path
+        .rsplit_once('/')
+        .map_or("", |(directory, _)| directory);
+    let mut chapters = Vec::new();
+    // A spine may list a document more than once; it is read and kept only once.
+    let mut read = HashSet::new();
+    for idref in spine {
+        let Some(href) = manifest.get(&idref) else {
+            continue;
+        };
+        let path = resolve(base, href);
+        if !read.insert(path.clone()) {
+            continue;
+        }
+        if let Some(chapter) = archive.read_string(&path)? {
+            let text = mdka::from_html(&chapter);
+            if !text.trim().is_empty() {
+                chapters.push(text.trim().to_string());
+            }
+        }
+    }
+    Ok(chapters.join("\n\n"))
+}
+
+/// Calls `visit` with the local name of every opening or self-closing tag of `xml`.
+fn for_each_start(
+    xml: &str,
+    mut visit: impl FnMut(&str, &quick_xml::events::BytesStart),
+) -> Result<()> {
+    let mut reader = xml::reader(xml);
+    loop {
+        match reader.read_event()? {
+            Event::Start(element) | Event::Empty(element) => {
+                visit(&xml::local_name(&element), &element)
+            }
+            Event::Eof => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+/// Path inside the archive of `href`, relative to the package document in `base`.
+fn resolve(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let mut parts = base
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>();
+    for part in href.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    percent_decode(&parts.join("/"))
+}
+
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::archive;
+
+    #[test]
+    fn test_extracts_chapters_in_spine_order() -> Result<()> {
+        let container = br#"<container><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        let package = br#"<package><manifest>
+<item id="one" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+<item id="two" href="text/two.xhtml" media-type="application/xhtml+xml"/>
+</manifest><spine><itemref idref="two"/><itemref idref="one"/></spine></package>"#;
+        let bytes = archive(&[
+            ("mimetype", b"application/epub+zip"),
+            ("META-INF/container.xml", container),
+            ("OEBPS/content.opf", package),
+            (
+                "OEBPS/text/chapter 1.xhtml",
+                b"<html><body><h1>Chapter One</h1><p>It begins.</p></body></html>",
+            ),
+            (
+                "OEBPS/text/two.xhtml",
+                b"<html><body><h1>Preface</h1></body></html>",
+            ),
+        ]);
+        let text = extract(&bytes)?;
+        let preface = text.find("Preface").unwrap();
+        let chapter = text.find("Chapter One").unwrap();
+        assert!(preface < chapter);
+        assert!(text.contains("It begins."));
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeated_spine_entries_are_read_once() -> Result<()> {
+        let container =
+            br#"<container><rootfiles><rootfile full-path="content.opf"/></rootfiles></container>"#;
+        let package = br#"<package><manifest>
+<item id="one" href="one.xhtml"/><item id="alias" href="./one.xhtml"/>
+</manifest><spine><itemref idref="one"/><itemref idref="one"/><itemref idref="alias"/></spine></package>"#;
+        let bytes = archive(&[
+            ("META-INF/container.xml", container),
+            ("content.opf", package),
+            ("one.xhtml", b"<html><body><p>Only once.</p></body></html>"),
+        ]);
+        assert_eq!(extract(&bytes)?.matches("Only once.").count(), 1);
+        Ok(())
+    }
+}
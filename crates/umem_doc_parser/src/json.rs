@@ -0,0 +1,58 @@
+//! Text of JSON files: one `path: value` line per scalar, so keys stay next to the
+//! values they describe.
+
+use anyhow::Result;
+use serde_json::Value;
+
+pub(crate) fn extract(text: &str) -> Result<String> {
+    let value: Value = serde_json::from_str(text)?;
+    let mut lines = Vec::new();
+    flatten(&value, String::new(), &mut lines);
+    Ok(lines.join("\n"))
+}
+
+fn flatten(value: &Value, path: String, lines: &mut Vec<String>) {
+    let line = |text: &str| {
+        if path.is_empty() {
+            text.to_string()
+        } else {
+            format!("{path}: {text}")
+        }
+    };
+    match value {
+        Value::Object(object) => {
+            for (key, value) in object {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                flatten(value, path, lines);
+            }
+        }
+        Value::Array(array) => {
+            for (index, value) in array.iter().enumerate() {
+                flatten(value, format!("{path}[{index}]"), lines);
+            }
+        }
+        Value::String(text) => lines.push(line(text)),
+        Value::Null => {}
+        scalar => lines.push(line(&scalar.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalars_are_listed_by_path() -> Result<()> {
+        let json =
+            r#"{"user": {"name": "Ada", "tags": ["math", "code"], "age": 36, "spouse": null}}"#;
+        assert_eq!(
+            extract(json)?,
+            "user.age: 36\nuser.name: Ada\nuser.tags[0]: math\nuser.tags[1]: code"
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,152 @@
-use anyhow::Result;
-use lopdf::Document;
-use pandoc::{OutputFormat, OutputKind, PandocOutput};
+mod archive;
+mod delimited;
+mod docx;
+mod epub;
+mod json;
+mod pdf;
+mod readability;
+mod xml;
+
+use anyhow::{Context, Result, bail};
+use archive::Archive;
 use std::path::Path;
-use umem_web_scrapper::Scrapper;
+use umem_web_scrapper::{ScrapeOptions, Scrapper};
+use url::Url;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FileExtractionSource {
     PDF,
+    DOCX,
+    EPUB,
+    HTML,
+    CSV,
+    TSV,
+    JSON,
+    MARKDOWN,
+    TEXT,
+    /// Detected from the file name and content. Formats without a native extractor
+    /// go through pandoc when built with the `pandoc` feature.
     OTHER,
 }
 
+impl FileExtractionSource {
+    /// Detects the format from magic bytes first, then the declared MIME type, then
+    /// the MIME type guessed from the file name, and finally by sniffing the content.
+    pub fn detect(bytes: &[u8], filename: &str, mime_type: &str) -> Self {
+        if bytes.starts_with(b"%PDF") {
+            return Self::PDF;
+        }
+        if Archive::is_zip(bytes) {
+            return match Archive::new(bytes) {
+                Ok(archive) if archive.contains("word/document.xml") => Self::DOCX,
+                Ok(archive) if archive.contains("META-INF/container.xml") => Self::EPUB,
+                _ => Self::OTHER,
+            };
+        }
+
+        let declared = Self::from_mime(mime_type);
+        if declared != Self::OTHER {
+            return declared;
+        }
+        let guessed = mime_guess::from_path(filename)
+            .iter()
+            .map(|mime| Self::from_mime(mime.essence_str()))
+            .find(|source| *source != Self::OTHER);
+        if let Some(guessed) = guessed {
+            return guessed;
+        }
+        // Without a known extension, pandoc could not pick a reader either.
+        let has_extension = Path::new(filename).extension().is_some();
+        match std::str::from_utf8(bytes) {
+            Ok(text) if looks_like_html(text) => Self::HTML,
+            Ok(text) if looks_like_json(text) => Self::JSON,
+            Ok(_) if !has_extension => Self::TEXT,
+            _ => Self::OTHER,
+        }
+    }
+
+    fn from_mime(mime_type: &str) -> Self {
+        let essence = mime_type.split(';').next().unwrap_or_default().trim();
+        match essence.to_ascii_lowercase().as_str() {
+            "application/pdf" => Self::PDF,
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Self::DOCX,
+            "application/epub+zip" => Self::EPUB,
+            "text/html" | "application/xhtml+xml" => Self::HTML,
+            "text/csv" => Self::CSV,
+            "text/tab-separated-values" => Self::TSV,
+            "application/json" => Self::JSON,
+            "text/markdown" | "text/x-markdown" => Self::MARKDOWN,
+            "text/plain" => Self::TEXT,
+            _ => Self::OTHER,
+        }
+    }
+
+    /// Whether extracted text keeps the document structure as Markdown headings.
+    pub fn is_markdown(&self) -> bool {
+        matches!(self, Self::DOCX | Self::EPUB | Self::HTML | Self::MARKDOWN)
+    }
+}
+
+/// The text of a file along with what the format records about it.
+#[derive(Clone, Debug, Default)]
+pub struct ExtractedDocument {
+    pub text: String,
+    /// Whether `text` marks the document structure with Markdown headings.
+    pub is_markdown: bool,
+    pub metadata: DocumentMetadata,
+    /// Where every page starts and ends in `text`. Empty for formats without pages.
+    pub pages: Vec<PageSegment>,
+    /// Pages whose text could not be extracted, in whole or in part.
+    pub failed_pages: Vec<PageFailure>,
+}
+
+impl ExtractedDocument {
+    fn text(text: String, source: FileExtractionSource) -> Self {
+        Self {
+            text,
+            is_markdown: source.is_markdown(),
+            ..Default::default()
+        }
+    }
+
+    /// The first and last page overlapping the byte range `start..end` of `text`.
+    pub fn page_range(&self, start: usize, end: usize) -> Option<(u32, u32)> {
+        let mut pages = self
+            .pages
+            .iter()
+            .filter(|page| page.start < end.max(start + 1) && start < page.end);
+        let first = pages.next()?;
+        let last = pages.next_back().unwrap_or(first);
+        Some((first.number, last.number))
+    }
+}
+
+/// Document information the file itself declares.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    /// Creation or publication date in unix seconds.
+    pub created_at: Option<i64>,
+    pub canonical_url: Option<String>,
+    /// BCP 47 language tag, such as `en-GB`.
+    pub language: Option<String>,
+}
+
+/// The byte range of one page in [`ExtractedDocument::text`]. Pages count from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageSegment {
+    pub number: u32,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageFailure {
+    pub number: u32,
+    pub reason: String,
+}
+
 pub struct Extractor;
 
 impl Extractor {
@@ -16,20 +154,94 @@ impl Extractor {
         path: P,
         source: FileExtractionSource,
     ) -> Result<String> {
+        let bytes = std::fs::read(&path)?;
+        let source = match source {
+            FileExtractionSource::OTHER => {
+                let filename = path.as_ref().to_string_lossy();
+                FileExtractionSource::detect(&bytes, &filename, "")
+            }
+            source => source,
+        };
         match source {
-            FileExtractionSource::PDF => Self::extract_pdf(path),
-            FileExtractionSource::OTHER => Self::extract_other(path),
+            FileExtractionSource::OTHER => Self::extract_other(path.as_ref()),
+            source => Ok(Self::extract(&bytes, source)?.text),
+        }
+    }
+
+    /// Extracts the text of an uploaded file in the format
+    /// [`FileExtractionSource::detect`] finds. HTML, EPUB and DOCX come out as Markdown.
+    pub fn extract_from_bytes(bytes: &[u8], filename: &str, mime_type: &str) -> Result<String> {
+        Ok(Self::extract_document(bytes, filename, mime_type)?.text)
+    }
+
+    /// Like [`Extractor::extract_from_bytes`], keeping the page boundaries, metadata and
+    /// failed pages of PDFs.
+    pub fn extract_document(
+        bytes: &[u8],
+        filename: &str,
+        mime_type: &str,
+    ) -> Result<ExtractedDocument> {
+        match FileExtractionSource::detect(bytes, filename, mime_type) {
+            FileExtractionSource::OTHER => Ok(ExtractedDocument::text(
+                Self::extract_other_bytes(bytes, filename)?,
+                FileExtractionSource::OTHER,
+            )),
+            source => Self::extract(bytes, source),
         }
     }
 
+    /// Fetches a web page and converts its main content to Markdown, leaving out
+    /// navigation, banners, sidebars and footers.
     pub async fn extract_from_website(url: &str) -> Result<String> {
-        let html_text = Scrapper::scrape(url).await?;
-        Ok(mdka::from_html(&html_text))
+        Ok(Self::extract_web_page(url).await?.text)
+    }
+
+    /// Like [`Extractor::extract_from_website`], keeping the title, byline, publication
+    /// date, canonical URL and language the page declares.
+    pub async fn extract_web_page(url: &str) -> Result<ExtractedDocument> {
+        Self::extract_web_page_with_options(url, &ScrapeOptions::default()).await
+    }
+
+    /// Like [`Extractor::extract_web_page`], fetching the page with `options`.
+    pub async fn extract_web_page_with_options(
+        url: &str,
+        options: &ScrapeOptions,
+    ) -> Result<ExtractedDocument> {
+        let html = Scrapper::scrape_with_options(url, options).await?;
+        Ok(Self::extract_article(&html, url))
+    }
+
+    /// Extracts the main content and metadata of an already fetched page. `url` is
+    /// where it was fetched from, for resolving relative links in its tags.
+    pub fn extract_article(html: &str, url: &str) -> ExtractedDocument {
+        readability::extract(html, Url::parse(url).ok().as_ref())
+    }
+
+    fn extract(bytes: &[u8], source: FileExtractionSource) -> Result<ExtractedDocument> {
+        let text = || {
+            let text = std::str::from_utf8(bytes).context("File is not UTF-8 text")?;
+            anyhow::Ok(text.strip_prefix('\u{feff}').unwrap_or(text))
+        };
+        let text = match source {
+            FileExtractionSource::PDF => return pdf::extract(bytes),
+            FileExtractionSource::DOCX => docx::extract(bytes),
+            FileExtractionSource::EPUB => epub::extract(bytes),
+            FileExtractionSource::HTML => Ok(mdka::from_html(text()?)),
+            FileExtractionSource::CSV => delimited::extract(text()?, b','),
+            FileExtractionSource::TSV => delimited::extract(text()?, b'\t'),
+            FileExtractionSource::JSON => json::extract(text()?),
+            FileExtractionSource::MARKDOWN | FileExtractionSource::TEXT => Ok(text()?.to_string()),
+            FileExtractionSource::OTHER => bail!("Unsupported file format"),
+        }?;
+        Ok(ExtractedDocument::text(text, source))
     }
 
-    fn extract_other<P: AsRef<Path>>(path: P) -> Result<String> {
+    #[cfg(feature = "pandoc")]
+    fn extract_other(path: &Path) -> Result<String> {
+        use pandoc::{OutputFormat, OutputKind, PandocOutput};
+
         let mut pandoc = pandoc::new();
-        pandoc.add_input(&path);
+        pandoc.add_input(path);
         pandoc.set_output_format(OutputFormat::Plain, Vec::new());
         pandoc.set_output(OutputKind::Pipe);
         if let PandocOutput::ToBuffer(buffer) = pandoc.execute()? {
@@ -38,22 +250,53 @@ impl Extractor {
         unreachable!()
     }
 
-    fn extract_pdf<P: AsRef<Path>>(path: P) -> Result<String> {
-        let document = Document::load(path)?;
-        let pages = document.get_pages();
-        let mut texts = Vec::new();
-        for (i, _) in pages.iter().enumerate() {
-            let page_number = (i + 1) as u32;
-            let text = document.extract_text(&[page_number]);
-            texts.push(text.unwrap_or_default());
-        }
-        Ok(texts.join(" "))
+    #[cfg(not(feature = "pandoc"))]
+    fn extract_other(_path: &Path) -> Result<String> {
+        bail!("Unsupported file format")
+    }
+
+    /// Pandoc picks its reader from the file extension, so the bytes are written to a
+    /// temporary file named like the upload.
+    #[cfg(feature = "pandoc")]
+    fn extract_other_bytes(bytes: &[u8], filename: &str) -> Result<String> {
+        use std::io::Write;
+
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .context("Unsupported file format")?;
+        let mut file = tempfile::Builder::new()
+            .suffix(&format!(".{extension}"))
+            .tempfile()?;
+        file.write_all(bytes)?;
+        Self::extract_other(file.path())
     }
+
+    #[cfg(not(feature = "pandoc"))]
+    fn extract_other_bytes(_bytes: &[u8], _filename: &str) -> Result<String> {
+        bail!("Unsupported file format")
+    }
+}
+
+fn looks_like_html(text: &str) -> bool {
+    let start = text
+        .trim_start()
+        .chars()
+        .take(14)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
+fn looks_like_json(text: &str) -> bool {
+    let text = text.trim_start();
+    (text.starts_with('{') || text.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(text).is_ok()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Extractor, FileExtractionSource};
+    use super::{ExtractedDocument, Extractor, FileExtractionSource, PageSegment};
     use anyhow::Result;
     use lopdf::{
         Document, Object, Stream,
@@ -61,7 +304,10 @@ mod tests {
         dictionary,
     };
     use std::io::Write;
+    use std::time::Duration;
     use tempfile::{Builder, NamedTempFile};
+    use umem_web_scrapper::ScrapeOptions;
+    use umem_web_scrapper::fixture::{FixtureResponse, FixtureServer, samples};
 
     fn write_temp_file(content: &str, ext: &str) -> Result<(NamedTempFile, String)> {
         let mut file = Builder::new().suffix(ext).tempfile()?;
@@ -137,11 +383,150 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_extract_from_bytes_passes_text_through() -> Result<()> {
+        let output = Extractor::extract_from_bytes(b"# Notes\n\nPlain.", "notes.md", "")?;
+        assert_eq!(output, "# Notes\n\nPlain.");
+        assert!(Extractor::extract_from_bytes(&[0xff, 0xfe], "blob", "").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_formats_are_detected() {
+        let docx = crate::archive::archive(&[("word/document.xml", b"<w:document/>")]);
+        let epub = crate::archive::archive(&[("META-INF/container.xml", b"<container/>")]);
+        let cases: [(&[u8], &str, &str, FileExtractionSource); 9] = [
+            (b"%PDF-1.7", "scan", "", FileExtractionSource::PDF),
+            (&docx, "upload.bin", "", FileExtractionSource::DOCX),
+            (
+                &epub,
+                "book",
+                "application/octet-stream",
+                FileExtractionSource::EPUB,
+            ),
+            (b"a,b", "table.csv", "", FileExtractionSource::CSV),
+            (
+                b"a\tb",
+                "upload",
+                "text/tab-separated-values",
+                FileExtractionSource::TSV,
+            ),
+            (
+                b"<!DOCTYPE html><p>x</p>",
+                "page",
+                "",
+                FileExtractionSource::HTML,
+            ),
+            (b"{\"a\": 1}", "data", "", FileExtractionSource::JSON),
+            (b"# Notes", "notes.md", "", FileExtractionSource::MARKDOWN),
+            (b"\x00\x01", "archive.xyz", "", FileExtractionSource::OTHER),
+        ];
+        for (bytes, filename, mime_type, expected) in cases {
+            assert_eq!(
+                FileExtractionSource::detect(bytes, filename, mime_type),
+                expected,
+                "{filename}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_html_is_converted_to_markdown() -> Result<()> {
+        let html = b"<html><body><h1>Title</h1><p>Body text.</p></body></html>";
+        let output = Extractor::extract_from_bytes(html, "page.html", "")?;
+        assert!(output.contains("# Title"));
+        assert!(output.contains("Body text."));
+        Ok(())
+    }
+
+    #[test]
+    fn test_page_range_covers_overlapping_pages() {
+        let document = ExtractedDocument {
+            text: "one\n\ntwo\n\nthree".to_string(),
+            pages: vec![
+                PageSegment {
+                    number: 1,
+                    start: 0,
+                    end: 3,
+                },
+                PageSegment {
+                    number: 2,
+                    start: 5,
+                    end: 8,
+                },
+                PageSegment {
+                    number: 4,
+                    start: 10,
+                    end: 15,
+                },
+            ],
+            ..Default::default()
+        };
+        assert_eq!(document.page_range(0, 3), Some((1, 1)));
+        assert_eq!(document.page_range(1, 7), Some((1, 2)));
+        assert_eq!(document.page_range(6, 15), Some((2, 4)));
+        assert_eq!(document.page_range(3, 5), None);
+        assert_eq!(ExtractedDocument::default().page_range(0, 1), None);
+    }
+
     #[tokio::test]
     async fn test_extract_from_books_website() -> Result<()> {
+        let server = FixtureServer::start().await;
+        server.route("/", FixtureResponse::html(samples::OPENAI_HOMEPAGE));
+        let output = Extractor::extract_from_website(&server.url("/")).await?;
+        assert!(output.starts_with("# What can I help with?"));
+        assert!(output.contains("## Recent news"));
+        assert!(output.contains("[Introducing GPT-5](/index/introducing-gpt-5/)"));
+        // The footer is not part of the main content
+        assert!(!output.contains("Help Center"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extract_from_website_past_a_bot_check() -> Result<()> {
+        let server = FixtureServer::start().await;
+        server.route_sequence(
+            "/",
+            vec![
+                FixtureResponse::html(samples::CLOUDFLARE_CHALLENGE),
+                FixtureResponse::html(samples::OPENAI_HOMEPAGE),
+            ],
+        );
+        let options = ScrapeOptions {
+            challenge_retry_delay: Duration::from_millis(10),
+        };
+        let page = Extractor::extract_web_page_with_options(&server.url("/"), &options).await?;
+        assert!(page.text.starts_with("# What can I help with?"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_extract_web_page_keeps_the_article() -> Result<()> {
+        let server = FixtureServer::start().await;
+        let path = "/catalogue/a-light-in-the-attic_1000/index.html";
+        server.route(path, FixtureResponse::html(samples::BOOK_PAGE));
+        let page = Extractor::extract_web_page(&server.url(path)).await?;
+        assert!(page.text.contains("Shel Silverstein"));
+        assert!(!page.text.contains("We love being scraped"));
+        assert_eq!(
+            page.metadata.title.as_deref(),
+            Some("A Light in the Attic | Books to Scrape")
+        );
+        assert_eq!(page.metadata.language.as_deref(), Some("en-us"));
+
+        assert!(
+            Extractor::extract_web_page(&server.url("/missing"))
+                .await
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "live-tests")]
+    #[tokio::test]
+    async fn test_extract_from_live_website() -> Result<()> {
         let url = "https://openai.com";
         let output = Extractor::extract_from_website(url).await?;
-        print!("{}", output);
         assert!(output.contains("Help"));
         Ok(())
     }
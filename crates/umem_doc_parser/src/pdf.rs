@@ -0,0 +1,230 @@
+use crate::{DocumentMetadata, ExtractedDocument, PageFailure, PageSegment};
+use anyhow::Result;
+use lopdf::{Document, Object, decode_text_string};
+use std::collections::HashMap;
+
+/// Extracts a PDF page by page. Pages are separated by a blank line, and the outline
+/// entries pointing at a page are put before its text as Markdown headings.
+pub fn extract(bytes: &[u8]) -> Result<ExtractedDocument> {
+    let document = Document::load_mem(bytes)?;
+    let outline = outline(&document);
+    let mut extracted = ExtractedDocument {
+        is_markdown: !outline.is_empty(),
+        metadata: metadata(&document),
+        ..Default::default()
+    };
+    for number in document.get_pages().into_keys() {
+        let mut text = String::new();
+        for heading in outline.get(&number).into_iter().flatten() {
+            text.push_str(heading);
+            text.push_str("\n\n");
+        }
+        let mut errors = Vec::new();
+        for chunk in document.extract_text_chunks(&[number]) {
+            match chunk {
+                Ok(chunk) => text.push_str(&chunk),
+                Err(error) => errors.push(error.to_string()),
+            }
+        }
+        if !errors.is_empty() {
+            extracted.failed_pages.push(PageFailure {
+                number,
+                reason: errors.join("; "),
+            });
+        }
+
+        let text = normalize(&text);
+        if text.is_empty() {
+            continue;
+        }
+        if !extracted.text.is_empty() {
+            extracted.text.push_str("\n\n");
+        }
+        let start = extracted.text.len();
+        extracted.text.push_str(&text);
+        extracted.pages.push(PageSegment {
+            number,
+            start,
+            end: extracted.text.len(),
+        });
+    }
+    Ok(extracted)
+}
+
+/// Outline titles by the page they point at, as Markdown headings of their depth.
+fn outline(document: &Document) -> HashMap<u32, Vec<String>> {
+    let mut headings: HashMap<u32, Vec<String>> = HashMap::new();
+    let Ok(toc) = document.get_toc() else {
+        return headings;
+    };
+    for entry in toc.toc {
+        let title = entry.title.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !title.is_empty() {
+            headings
+                .entry(entry.page as u32)
+                .or_default()
+                .push(format!("{} {title}", "#".repeat(entry.level.clamp(1, 6))));
+        }
+    }
+    headings
+}
+
+/// Reads the title, author and creation date of the document information dictionary.
+fn metadata(document: &Document) -> DocumentMetadata {
+    let info = document
+        .trailer
+        .get_deref(b"Info", document)
+        .and_then(Object::as_dict)
+        .ok();
+    let field = |key: &[u8]| info?.get_deref(key, document).ok();
+    let text = |key: &[u8]| {
+        field(key)
+            .and_then(|value| decode_text_string(value).ok())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    DocumentMetadata {
+        title: text(b"Title"),
+        author: text(b"Author"),
+        created_at: field(b"CreationDate")
+            .and_then(Object::as_datetime)
+            .and_then(|date| chrono::DateTime::<chrono::Local>::try_from(date).ok())
+            .map(|date| date.timestamp()),
+        ..Default::default()
+    }
+}
+
+/// Trims trailing spaces from every line and collapses runs of blank lines.
+fn normalize(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !normalized.is_empty() {
+            normalized.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        normalized.push_str(line);
+        blank_lines = 0;
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{
+        Object, Stream,
+        content::{Content, Operation},
+        dictionary,
+    };
+
+    /// A PDF with one page per entry of `pages`, an outline entry for the first page
+    /// and an information dictionary. `None` pages select a font by number, which fails.
+    fn pdf(pages: &[Option<&str>]) -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+            "Encoding" => "WinAnsiEncoding",
+        });
+        let mut kids = Vec::new();
+        for page in pages {
+            let content = match page {
+                Some(text) => Content {
+                    operations: vec![
+                        Operation::new("BT", vec![]),
+                        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                        Operation::new("Tj", vec![Object::string_literal(*text)]),
+                        Operation::new("ET", vec![]),
+                    ],
+                }
+                .encode()
+                .unwrap(),
+                None => b"BT 1 12 Tf (Lost) Tj ET".to_vec(),
+            };
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content));
+            kids.push(Object::from(doc.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            })));
+        }
+        let first_page = kids[0].clone();
+        let count = kids.len() as i64;
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => count,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+        let outlines_id = doc.new_object_id();
+        let item_id = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Introduction"),
+            "Parent" => outlines_id,
+            "Dest" => vec![first_page, "Fit".into()],
+        });
+        doc.objects.insert(
+            outlines_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Outlines",
+                "First" => item_id,
+                "Last" => item_id,
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Outlines" => outlines_id,
+        });
+        let info_id = doc.add_object(dictionary! {
+            "Title" => Object::string_literal("Field Guide"),
+            "Author" => Object::string_literal("A. Writer"),
+            "CreationDate" => Object::string_literal("D:20240102030405Z"),
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc.trailer.set("Info", info_id);
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_pages_metadata_and_failures_are_reported() -> Result<()> {
+        let extracted = extract(&pdf(&[Some("First page."), None, Some("Third page.")]))?;
+        assert_eq!(
+            extracted.text,
+            "# Introduction\n\nFirst page.\n\nThird page."
+        );
+        assert!(extracted.is_markdown);
+        assert_eq!(
+            extracted
+                .pages
+                .iter()
+                .map(|page| (page.number, &extracted.text[page.start..page.end]))
+                .collect::<Vec<_>>(),
+            vec![(1, "# Introduction\n\nFirst page."), (3, "Third page.")]
+        );
+        assert_eq!(
+            extracted
+                .failed_pages
+                .iter()
+                .map(|page| page.number)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(extracted.metadata.title.as_deref(), Some("Field Guide"));
+        assert_eq!(extracted.metadata.author.as_deref(), Some("A. Writer"));
+        assert_eq!(extracted.metadata.created_at, Some(1704164645));
+        Ok(())
+    }
+}
@@ -0,0 +1,662 @@
+//! Boilerplate removal for web pages, after Mozilla's Readability: navigation, banners
+//! and other page chrome are dropped, paragraphs score the elements around them, and
+//! the best scoring element is kept as the article.
+
+use crate::{DocumentMetadata, ExtractedDocument};
+use html5ever::{
+    parse_document,
+    serialize::{SerializeOpts, TraversalScope, serialize},
+    tendril::TendrilSink,
+};
+use markup5ever_rcdom::{Handle, Node, NodeData, RcDom, SerializableHandle};
+use std::{collections::HashMap, rc::Rc};
+use url::Url;
+
+/// Elements that never hold article text.
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "iframe", "svg", "canvas", "button", "select",
+    "input", "textarea", "dialog", "nav", "aside",
+];
+/// Landmark roles of page chrome.
+const SKIPPED_ROLES: &[&str] = &[
+    "navigation",
+    "banner",
+    "contentinfo",
+    "complementary",
+    "dialog",
+    "alertdialog",
+    "menu",
+    "menubar",
+    "search",
+];
+/// Class and id fragments of page chrome, unless they also match [`MAYBE_CONTENT`].
+const UNLIKELY: &[&str] = &[
+    "ad-break",
+    "advert",
+    "agegate",
+    "banner",
+    "breadcrumb",
+    "combx",
+    "comment",
+    "community",
+    "consent",
+    "cookie",
+    "disqus",
+    "footer",
+    "gdpr",
+    "header",
+    "menu",
+    "modal",
+    "nav",
+    "newsletter",
+    "pager",
+    "pagination",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sidebar",
+    "social",
+    "sponsor",
+    "subscribe",
+    "widget",
+];
+const MAYBE_CONTENT: &[&str] = &[
+    "article", "body", "column", "content", "main", "post", "story",
+];
+const POSITIVE: &[&str] = &[
+    "article", "blog", "body", "content", "entry", "main", "page", "post", "story", "text",
+];
+const NEGATIVE: &[&str] = &[
+    "comment", "contact", "foot", "hidden", "masthead", "meta", "promo", "related", "share",
+    "shopping", "sidebar", "sponsor", "tags", "tool", "widget",
+];
+const BLOCK_TAGS: &[&str] = &[
+    "address",
+    "article",
+    "blockquote",
+    "div",
+    "dl",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+];
+/// Paragraphs shorter than this many characters do not score their ancestors.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// Extracts the main content of `html` as Markdown along with the title, byline,
+/// publication date, canonical URL and language its tags declare. Relative canonical
+/// URLs are resolved against `url`. Pages without a recognizable article come out whole.
+pub fn extract(html: &str, url: Option<&Url>) -> ExtractedDocument {
+    let dom = parse_document(RcDom::default(), Default::default()).one(html);
+    let metadata = metadata(&dom.document, url);
+    strip(&dom.document);
+    let texts = Texts::measure(&dom.document);
+
+    let mut article = Vec::new();
+    for node in main_content(&dom.document, &texts) {
+        clean(&node, &texts);
+        let _ = serialize(
+            &mut article,
+            &SerializableHandle::from(node),
+            SerializeOpts {
+                traversal_scope: TraversalScope::IncludeNode,
+                ..Default::default()
+            },
+        );
+    }
+    let mut text = mdka::from_html(&String::from_utf8_lossy(&article))
+        .trim()
+        .to_string();
+    if text.is_empty() {
+        text = mdka::from_html(html).trim().to_string();
+    }
+    ExtractedDocument {
+        text,
+        is_markdown: true,
+        metadata,
+        ..Default::default()
+    }
+}
+
+/// The highest scoring element and the siblings that score close to it, in page order.
+fn main_content(document: &Handle, texts: &Texts) -> Vec<Handle> {
+    let body = descendants(document)
+        .into_iter()
+        .find(|node| tag(node) == Some("body"))
+        .unwrap_or_else(|| document.clone());
+
+    let mut candidates: Vec<(Handle, f32)> = Vec::new();
+    let mut index: HashMap<*const Node, usize> = HashMap::new();
+    for paragraph in descendants(&body).iter().filter(|node| is_paragraph(node)) {
+        let text = texts.get(paragraph);
+        let length = text.length();
+        if length < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let score = 1.0 + text.commas as f32 + (length / 100).min(3) as f32;
+        for (level, ancestor) in ancestors(paragraph).into_iter().take(3).enumerate() {
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                level => level as f32 * 3.0,
+            };
+            let i = *index.entry(Rc::as_ptr(&ancestor)).or_insert_with(|| {
+                candidates.push((ancestor.clone(), initial_score(&ancestor)));
+                candidates.len() - 1
+            });
+            candidates[i].1 += score / divider;
+        }
+    }
+    let final_score = |node: &Handle, score: f32| score * (1.0 - texts.get(node).link_density());
+    let best = candidates
+        .iter()
+        .map(|(node, score)| (node, final_score(node, *score)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b));
+    let Some((top, top_score)) = best else {
+        return vec![body];
+    };
+    let Some(parent) = parent(top) else {
+        return vec![top.clone()];
+    };
+
+    let threshold = (top_score * 0.2).max(10.0);
+    let siblings = parent.children.borrow();
+    siblings
+        .iter()
+        .filter(|sibling| {
+            if Rc::ptr_eq(sibling, top) {
+                return true;
+            }
+            let score = index.get(&Rc::as_ptr(sibling)).map(|&i| candidates[i].1);
+            if score.is_some_and(|score| final_score(sibling, score) >= threshold) {
+                return true;
+            }
+            let text = texts.get(sibling);
+            tag(sibling) == Some("p") && text.length() > 80 && text.link_density() < 0.25
+        })
+        .cloned()
+        .collect()
+}
+
+/// Removes comments and page chrome below `node`. Headers and footers inside an
+/// `<article>` belong to it and stay.
+fn strip(document: &Handle) {
+    // Pages are nested arbitrarily deep, so the tree is walked without recursion.
+    let mut stack = vec![(document.clone(), false)];
+    while let Some((node, in_article)) = stack.pop() {
+        let in_article = in_article || tag(&node) == Some("article");
+        node.children.borrow_mut().retain(|child| match child.data {
+            NodeData::Comment { .. } | NodeData::ProcessingInstruction { .. } => false,
+            NodeData::Element { .. } => !is_boilerplate(child, in_article),
+            _ => true,
+        });
+        stack.extend(
+            node.children
+                .borrow()
+                .iter()
+                .map(|child| (child.clone(), in_article)),
+        );
+    }
+}
+
+fn is_boilerplate(node: &Handle, in_article: bool) -> bool {
+    let tag = tag(node).unwrap_or_default();
+    if SKIPPED_TAGS.contains(&tag) || (matches!(tag, "header" | "footer") && !in_article) {
+        return true;
+    }
+    let hidden = attribute(node, "hidden").is_some()
+        || attribute(node, "aria-hidden").as_deref() == Some("true")
+        || attribute(node, "style").is_some_and(|style| {
+            style
+                .replace(' ', "")
+                .to_ascii_lowercase()
+                .contains("display:none")
+        });
+    let role = attribute(node, "role").unwrap_or_default();
+    if hidden || SKIPPED_ROLES.contains(&role.as_str()) {
+        return true;
+    }
+    if matches!(tag, "html" | "body" | "article" | "main" | "a") {
+        return false;
+    }
+    let names = class_and_id(node);
+    contains_any(&names, UNLIKELY) && !contains_any(&names, MAYBE_CONTENT)
+}
+
+/// Removes blocks inside the article that are mostly links, such as tag clouds and
+/// lists of related pages.
+fn clean(node: &Handle, texts: &Texts) {
+    let mut stack = vec![node.clone()];
+    while let Some(node) = stack.pop() {
+        node.children.borrow_mut().retain(|child| {
+            let is_link_block = matches!(
+                tag(child),
+                Some("div" | "section" | "ul" | "ol" | "table" | "p")
+            );
+            let text = texts.get(child);
+            !is_link_block || text.length() == 0 || text.link_density() <= 0.5
+        });
+        stack.extend(node.children.borrow().iter().cloned());
+    }
+}
+
+fn is_paragraph(node: &Handle) -> bool {
+    match tag(node) {
+        Some("p" | "pre" | "td" | "blockquote") => true,
+        // Text laid out in divs without any block inside counts as a paragraph.
+        Some("div") => !node
+            .children
+            .borrow()
+            .iter()
+            .any(|child| tag(child).is_some_and(|tag| BLOCK_TAGS.contains(&tag))),
+        _ => false,
+    }
+}
+
+fn initial_score(node: &Handle) -> f32 {
+    let tag_score = match tag(node).unwrap_or_default() {
+        "article" | "main" => 10.0,
+        "div" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    let names = class_and_id(node);
+    let mut class_score = 0.0;
+    if contains_any(&names, POSITIVE) {
+        class_score += 25.0;
+    }
+    if contains_any(&names, NEGATIVE) {
+        class_score -= 25.0;
+    }
+    tag_score + class_score
+}
+
+/// What scoring needs to know of the [`inner_text`] below a node.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct TextStats {
+    words: usize,
+    /// Characters of the words, without the spaces between them.
+    word_chars: usize,
+    commas: usize,
+    /// Characters of the text inside the links below the node.
+    link_chars: usize,
+}
+
+impl TextStats {
+    /// Characters of the text with whitespace collapsed.
+    fn length(&self) -> usize {
+        self.word_chars + self.words.saturating_sub(1)
+    }
+
+    /// Share of the text inside links.
+    fn link_density(&self) -> f32 {
+        match self.length() {
+            0 => 0.0,
+            length => self.link_chars as f32 / length as f32,
+        }
+    }
+}
+
+/// [`TextStats`] of every node of a document, measured in one pass.
+struct Texts(HashMap<*const Node, TextStats>);
+
+impl Texts {
+    fn measure(document: &Handle) -> Self {
+        let mut nodes = vec![document.clone()];
+        nodes.extend(descendants(document));
+        let mut stats = HashMap::with_capacity(nodes.len());
+        // Nodes come before their children, so backwards every child is measured first.
+        for node in nodes.iter().rev() {
+            let mut text = TextStats::default();
+            if let NodeData::Text { contents } = &node.data {
+                let contents = contents.borrow();
+                for word in contents.split_whitespace() {
+                    text.words += 1;
+                    text.word_chars += word.chars().count();
+                }
+                text.commas = contents.matches(',').count();
+            }
+            for child in node.children.borrow().iter() {
+                let child_text: TextStats = stats[&Rc::as_ptr(child)];
+                text.words += child_text.words;
+                text.word_chars += child_text.word_chars;
+                text.commas += child_text.commas;
+                text.link_chars += child_text.link_chars;
+                if tag(child) == Some("a") {
+                    text.link_chars += child_text.length();
+                }
+            }
+            stats.insert(Rc::as_ptr(node), text);
+        }
+        Self(stats)
+    }
+
+    fn get(&self, node: &Handle) -> TextStats {
+        self.0.get(&Rc::as_ptr(node)).copied().unwrap_or_default()
+    }
+}
+
+fn metadata(document: &Handle, url: Option<&Url>) -> DocumentMetadata {
+    let mut meta: HashMap<String, String> = HashMap::new();
+    let mut title = None;
+    let mut canonical = None;
+    let mut language = None;
+    let mut byline = None;
+    let mut published = None;
+    for node in descendants(document) {
+        let itemprop = attribute(&node, "itemprop").unwrap_or_default();
+        let rel = attribute(&node, "rel").unwrap_or_default();
+        match tag(&node) {
+            Some("html") => language = attribute(&node, "lang"),
+            Some("title") if title.is_none() => title = Some(inner_text(&node)),
+            Some("meta") => {
+                let key = ["property", "name", "http-equiv", "itemprop"]
+                    .into_iter()
+                    .find_map(|key| attribute(&node, key));
+                if let (Some(key), Some(content)) = (key, attribute(&node, "content")) {
+                    meta.entry(key.to_ascii_lowercase())
+                        .or_insert_with(|| collapse(&content));
+                }
+                continue;
+            }
+            Some("link") if rel.split_whitespace().any(|rel| rel == "canonical") => {
+                canonical = canonical.or_else(|| attribute(&node, "href"));
+                continue;
+            }
+            _ => {}
+        }
+        if itemprop == "datePublished" && published.is_none() {
+            published = attribute(&node, "datetime").or_else(|| Some(inner_text(&node)));
+        }
+        let is_byline = itemprop == "author"
+            || rel.split_whitespace().any(|rel| rel == "author")
+            || class_and_id(&node).contains("byline");
+        if is_byline && byline.is_none() {
+            let text = inner_text(&node);
+            if !text.is_empty() && text.chars().count() < 100 {
+                byline = Some(text);
+            }
+        }
+    }
+
+    let lookup = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|key| meta.get(*key).filter(|value| !value.is_empty()))
+            .cloned()
+    };
+    let author = lookup(&["author", "article:author", "dc.creator", "byl"])
+        .filter(|author| !author.starts_with("http"))
+        .or(byline)
+        .map(|author| {
+            let author = author.trim();
+            author
+                .strip_prefix("By ")
+                .or_else(|| author.strip_prefix("by "))
+                .unwrap_or(author)
+                .to_string()
+        });
+    let published = lookup(&[
+        "article:published_time",
+        "datepublished",
+        "date",
+        "dc.date",
+        "dc.date.issued",
+        "pubdate",
+    ])
+    .or(published);
+    let canonical = canonical
+        .or_else(|| lookup(&["og:url"]))
+        .and_then(|href| match url {
+            Some(url) => url.join(&href).ok(),
+            None => Url::parse(&href).ok(),
+        })
+        .map(|url| url.to_string());
+    let language = language
+        .filter(|language| !language.is_empty())
+        .or_else(|| lookup(&["content-language"]))
+        .or_else(|| lookup(&["og:locale"]).map(|locale| locale.replace('_', "-")));
+
+    DocumentMetadata {
+        title: lookup(&["og:title", "twitter:title", "dc.title"])
+            .or_else(|| title.map(|title| clean_title(&title)))
+            .filter(|title| !title.is_empty()),
+        author,
+        created_at: published.and_then(|date| parse_date(&date)),
+        canonical_url: canonical,
+        language,
+    }
+}
+
+/// Drops the site name from `Article | Site` style titles, unless what remains is
+/// under three words.
+fn clean_title(title: &str) -> String {
+    let separator = [" | ", " - ", " – ", " — ", " :: "]
+        .into_iter()
+        .filter_map(|separator| title.rfind(separator))
+        .max();
+    match separator {
+        Some(end) if title[..end].split_whitespace().count() >= 3 => title[..end].to_string(),
+        _ => title.to_string(),
+    }
+}
+
+/// Unix seconds of an RFC 3339, RFC 2822 or `YYYY-MM-DD` date.
+fn parse_date(date: &str) -> Option<i64> {
+    use chrono::{DateTime, NaiveDate};
+
+    let date = date.trim();
+    DateTime::parse_from_rfc3339(date)
+        .or_else(|_| DateTime::parse_from_rfc2822(date))
+        .map(|date| date.timestamp())
+        .ok()
+        .or_else(|| {
+            let day = NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()?;
+            Some(day.and_hms_opt(0, 0, 0)?.and_utc().timestamp())
+        })
+}
+
+fn tag(node: &Node) -> Option<&str> {
+    match &node.data {
+        NodeData::Element { name, .. } => Some(&name.local),
+        _ => None,
+    }
+}
+
+fn attribute(node: &Node, name: &str) -> Option<String> {
+    let NodeData::Element { attrs, .. } = &node.data else {
+        return None;
+    };
+    attrs
+        .borrow()
+        .iter()
+        .find(|attribute| &*attribute.name.local == name)
+        .map(|attribute| attribute.value.to_string())
+}
+
+fn class_and_id(node: &Node) -> String {
+    let class = attribute(node, "class").unwrap_or_default();
+    let id = attribute(node, "id").unwrap_or_default();
+    format!("{class} {id}").to_ascii_lowercase()
+}
+
+fn contains_any(names: &str, fragments: &[&str]) -> bool {
+    fragments.iter().any(|fragment| names.contains(fragment))
+}
+
+/// The text below `node` with whitespace collapsed.
+fn inner_text(node: &Handle) -> String {
+    let mut text = String::new();
+    for node in descendants(node) {
+        if let NodeData::Text { contents } = &node.data {
+            text.push_str(&contents.borrow());
+            text.push(' ');
+        }
+    }
+    collapse(&text)
+}
+
+fn collapse(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Every node below `node` in document order.
+fn descendants(node: &Handle) -> Vec<Handle> {
+    let mut nodes = Vec::new();
+    let mut stack = node
+        .children
+        .borrow()
+        .iter()
+        .rev()
+        .cloned()
+        .collect::<Vec<_>>();
+    while let Some(node) = stack.pop() {
+        stack.extend(node.children.borrow().iter().rev().cloned());
+        nodes.push(node);
+    }
+    nodes
+}
+
+fn parent(node: &Handle) -> Option<Handle> {
+    let parent = node.parent.take();
+    node.parent.set(parent.clone());
+    parent.and_then(|parent| parent.upgrade())
+}
+
+/// Element ancestors of `node`, nearest first.
+fn ancestors(node: &Handle) -> Vec<Handle> {
+    let mut ancestors = Vec::new();
+    let mut current = parent(node);
+    while let Some(node) = current {
+        if tag(&node).is_none() {
+            break;
+        }
+        current = parent(&node);
+        ancestors.push(node);
+    }
+    ancestors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE: &str = r#"<!DOCTYPE html>
+<html lang="en-GB">
+<head>
+  <title>Tending Sourdough Starters | The Bakery Blog</title>
+  <meta name="author" content="By Ada Baker">
+  <meta property="article:published_time" content="2024-03-05T08:30:00Z">
+  <link rel="canonical" href="/posts/sourdough">
+  <script>trackVisitor();</script>
+</head>
+<body>
+  <header><a href="/">Home</a> <a href="/shop">Shop</a> <a href="/about">About us</a></header>
+  <nav><ul><li><a href="/recipes">All recipes</a></li><li><a href="/tools">Tools</a></li></ul></nav>
+  <div class="cookie-banner">We use cookies to improve your experience. Accept all cookies?</div>
+  <div id="main">
+    <article class="post">
+      <h1>Tending sourdough starters</h1>
+      <p>A starter needs flour, water and patience, and it rewards regular feeding with a lively, bubbly texture.</p>
+      <p>Feed it every twelve hours at room temperature, discarding half before each feeding, so the yeast never runs out of food.</p>
+      <p>Once it doubles within six hours of a feeding, it is strong enough to raise a loaf of bread.</p>
+      <ul class="tags"><li><a href="/t/bread">bread</a></li><li><a href="/t/yeast">yeast</a></li></ul>
+    </article>
+    <div class="sidebar"><h3>Popular</h3><p><a href="/p/1">Ten cakes for spring, with icing and without</a></p></div>
+  </div>
+  <footer>Copyright 2024 The Bakery Blog. All rights reserved, including the recipes.</footer>
+</body>
+</html>"#;
+
+    #[test]
+    fn test_article_is_kept_without_page_chrome() {
+        let url = Url::parse("https://bakery.example/posts/sourdough?ref=home").unwrap();
+        let extracted = extract(ARTICLE, Some(&url));
+        let text = &extracted.text;
+        assert!(text.contains("# Tending sourdough starters"), "{text}");
+        assert!(text.contains("Feed it every twelve hours"));
+        assert!(text.contains("raise a loaf of bread"));
+        for boilerplate in [
+            "All recipes",
+            "About us",
+            "cookies",
+            "Ten cakes",
+            "Copyright",
+            "trackVisitor",
+            "yeast](/t",
+        ] {
+            assert!(!text.contains(boilerplate), "{boilerplate} in {text}");
+        }
+
+        assert_eq!(
+            extracted.metadata,
+            DocumentMetadata {
+                title: Some("Tending Sourdough Starters".to_string()),
+                author: Some("Ada Baker".to_string()),
+                created_at: Some(1709627400),
+                canonical_url: Some("https://bakery.example/posts/sourdough".to_string()),
+                language: Some("en-GB".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_text_stats_match_inner_text() {
+        let dom = parse_document(RcDom::default(), Default::default()).one(
+            "<p> Read <a href=\"/a\">the  first,</a> <a href=\"/b\"><b>second</b></a>\n page, </p>",
+        );
+        let texts = Texts::measure(&dom.document);
+        let paragraph = descendants(&dom.document)
+            .into_iter()
+            .find(|node| tag(node) == Some("p"))
+            .unwrap();
+        let text = texts.get(&paragraph);
+        assert_eq!(text.length(), inner_text(&paragraph).chars().count());
+        assert_eq!(text.commas, 2);
+        assert_eq!(text.link_chars, "the first,".len() + "second".len());
+    }
+
+    #[test]
+    fn test_deeply_nested_pages_do_not_overflow_the_stack() {
+        let depth = 5_000;
+        let html = format!(
+            "<html><body>{}<p>The text at the bottom of a page nested very deep.</p>{}</body></html>",
+            "<div>".repeat(depth),
+            "</div>".repeat(depth)
+        );
+        // Far less stack than a runtime worker has
+        let extracted = std::thread::Builder::new()
+            .stack_size(128 * 1024)
+            .spawn(move || extract(&html, None))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert!(extracted.text.contains("The text at the bottom"));
+    }
+
+    #[test]
+    fn test_text_in_divs_beats_link_lists() {
+        let links = (0..20)
+            .map(|i| format!("<div><a href=\"/{i}\">Link number {i} to another page</a></div>"))
+            .collect::<String>();
+        let html = format!(
+            "<html><body><div class=\"links\">{links}</div><div class=\"content\"><div>The meeting moved to Thursday, after the budget review, because the venue was booked.</div><div>Bring the quarterly figures, the draft plan and a laptop.</div></div></body></html>"
+        );
+        let extracted = extract(&html, None);
+        assert!(extracted.text.contains("The meeting moved to Thursday"));
+        assert!(!extracted.text.contains("Link number"));
+        assert_eq!(extracted.metadata, DocumentMetadata::default());
+    }
+}
@@ -0,0 +1,63 @@
+//! Helpers for reading the markup inside DOCX and EPUB archives with quick-xml. The
+//! reader is forgiving, as only readable text is pulled out and nothing is validated.
+
+use quick_xml::{
+    Reader,
+    events::{BytesStart, BytesText},
+};
+
+pub(crate) fn reader(xml: &str) -> Reader<&[u8]> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().check_end_names = false;
+    reader
+}
+
+/// Name of `element` without its namespace prefix.
+pub(crate) fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+/// Value of the attribute whose local name is `name`.
+pub(crate) fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|attribute| attribute.key.local_name().as_ref() == name.as_bytes())
+        .and_then(|attribute| attribute.unescape_value().ok())
+        .map(|value| value.into_owned())
+}
+
+/// Unescaped `text`, keeping entities XML does not define, such as `&nbsp;`, as written.
+pub(crate) fn text(text: &BytesText) -> String {
+    match text.unescape() {
+        Ok(text) => text.into_owned(),
+        Err(_) => String::from_utf8_lossy(text).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quick_xml::events::Event;
+
+    #[test]
+    fn test_reads_tags_attributes_and_entities() {
+        let xml = r#"<?xml version="1.0"?><!-- note --><w:p a="1" w:val='Heading1'><w:t>Fish &amp; chips &#x2014; &#8364;5</w:t><w:br/></w:p>"#;
+        let mut reader = reader(xml);
+        let mut texts = Vec::new();
+        let mut starts = Vec::new();
+        loop {
+            match reader.read_event().unwrap() {
+                Event::Start(element) | Event::Empty(element) => {
+                    starts.push(local_name(&element));
+                    texts.extend(attribute(&element, "val"));
+                }
+                Event::Text(t) => texts.push(text(&t)),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        assert_eq!(starts, vec!["p", "t", "br"]);
+        assert_eq!(texts, vec!["Heading1", "Fish & chips \u{2014} \u{20ac}5"]);
+    }
+}
@@ -0,0 +1,419 @@
+use crate::{Embedder, EmbeddingError};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures::future::try_join_all;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::{mpsc, oneshot, Semaphore},
+    task::JoinHandle,
+};
+
+const COALESCE_QUEUE: usize = 1024;
+
+/// Limits applied by [`BatchingEmbedder`].
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    /// Texts per request to the backend.
+    pub max_batch_size: usize,
+    /// Estimated tokens per request to the backend.
+    pub max_batch_tokens: usize,
+    /// Estimated tokens per text; longer texts are truncated.
+    pub max_input_tokens: usize,
+    /// Backend requests in flight at once.
+    pub max_concurrency: usize,
+    /// How long a single embedding waits for others to share its request.
+    pub coalesce_window: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        // Workers AI accepts up to 100 texts per bge-m3 request, with 8192 tokens each.
+        Self {
+            max_batch_size: 100,
+            max_batch_tokens: 60_000,
+            max_input_tokens: 8192,
+            max_concurrency: 4,
+            coalesce_window: Duration::from_millis(5),
+        }
+    }
+}
+
+impl BatchConfig {
+    /// Reads `EMBEDDING_MAX_BATCH_SIZE`, `EMBEDDING_MAX_BATCH_TOKENS`,
+    /// `EMBEDDING_MAX_INPUT_TOKENS`, `EMBEDDING_MAX_CONCURRENCY` and
+    /// `EMBEDDING_COALESCE_WINDOW_MS`, keeping the defaults for unset variables.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            max_batch_size: env_usize("EMBEDDING_MAX_BATCH_SIZE", default.max_batch_size),
+            max_batch_tokens: env_usize("EMBEDDING_MAX_BATCH_TOKENS", default.max_batch_tokens),
+            max_input_tokens: env_usize("EMBEDDING_MAX_INPUT_TOKENS", default.max_input_tokens),
+            max_concurrency: env_usize("EMBEDDING_MAX_CONCURRENCY", default.max_concurrency),
+            coalesce_window: Duration::from_millis(env_usize(
+                "EMBEDDING_COALESCE_WINDOW_MS",
+                default.coalesce_window.as_millis() as usize,
+            ) as u64),
+        }
+    }
+}
+
+pub(crate) fn env_usize(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(default)
+}
+
+/// Rough token count, about four characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+fn truncate(text: &str, max_tokens: usize) -> &str {
+    match text.char_indices().nth(max_tokens.saturating_mul(4)) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// Splits `texts` into consecutive batches within the size and token limits.
+fn plan_batches<'t>(texts: &[&'t str], config: &BatchConfig) -> Vec<Vec<&'t str>> {
+    let mut batches = Vec::new();
+    let mut batch = Vec::new();
+    let mut batch_tokens = 0;
+
+    for text in texts {
+        let text = truncate(text, config.max_input_tokens);
+        let tokens = estimate_tokens(text);
+        if !batch.is_empty()
+            && (batch.len() >= config.max_batch_size
+                || batch_tokens + tokens > config.max_batch_tokens)
+        {
+            batches.push(std::mem::take(&mut batch));
+            batch_tokens = 0;
+        }
+        batch.push(text);
+        batch_tokens += tokens;
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+    batches
+}
+
+struct Pending {
+    text: String,
+    reply: oneshot::Sender<Result<Vec<f32>>>,
+}
+
+/// Wraps an [`Embedder`] so bulk requests are split into backend-sized batches that run
+/// concurrently, and, once [`BatchingEmbedder::spawn_coalescer`] has started the
+/// micro-batching task, concurrent single embeddings share micro-batches.
+pub struct BatchingEmbedder<E> {
+    inner: Arc<E>,
+    config: BatchConfig,
+    permits: Arc<Semaphore>,
+    coalescer: Mutex<Option<mpsc::Sender<Pending>>>,
+}
+
+impl<E: Embedder + Send + Sync + 'static> BatchingEmbedder<E> {
+    pub fn new(inner: E, config: BatchConfig) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            permits: Arc::new(Semaphore::new(config.max_concurrency.max(1))),
+            config,
+            coalescer: Mutex::new(None),
+        }
+    }
+
+    /// Starts the micro-batching task on the current runtime, replacing any earlier one.
+    /// Until it runs, and after its runtime shuts down, single embeddings are sent on
+    /// their own.
+    pub fn spawn_coalescer(&self) -> JoinHandle<()> {
+        let (sender, receiver) = mpsc::channel(COALESCE_QUEUE);
+        *self.coalescer.lock().unwrap() = Some(sender);
+        tokio::spawn(coalesce(
+            Arc::clone(&self.inner),
+            Arc::clone(&self.permits),
+            self.config.clone(),
+            receiver,
+        ))
+    }
+
+    fn coalescer(&self) -> Option<mpsc::Sender<Pending>> {
+        self.coalescer
+            .lock()
+            .unwrap()
+            .as_ref()
+            .filter(|sender| !sender.is_closed())
+            .cloned()
+    }
+}
+
+async fn embed_batch<E: Embedder>(
+    inner: &E,
+    permits: &Semaphore,
+    texts: Vec<&str>,
+) -> Result<Vec<Vec<f32>>> {
+    let _permit = permits.acquire().await?;
+    let expected = texts.len();
+    let embeddings = inner.generate_embeddings_bulk(texts).await?;
+    if embeddings.len() != expected {
+        bail!(
+            "Embedding backend returned {} embeddings for {} texts",
+            embeddings.len(),
+            expected
+        );
+    }
+    Ok(embeddings)
+}
+
+/// Collects single embedding requests until the batch is full or `coalesce_window`
+/// passes, then embeds them together in the background.
+async fn coalesce<E: Embedder + Send + Sync + 'static>(
+    inner: Arc<E>,
+    permits: Arc<Semaphore>,
+    config: BatchConfig,
+    mut receiver: mpsc::Receiver<Pending>,
+) {
+    let mut carry = None;
+    loop {
+        let first = match carry.take() {
+            Some(pending) => pending,
+            None => match receiver.recv().await {
+                Some(pending) => pending,
+                None => return,
+            },
+        };
+
+        let mut batch_tokens = estimate_tokens(&first.text);
+        let mut batch = vec![first];
+        let window = tokio::time::sleep(config.coalesce_window);
+        tokio::pin!(window);
+
+        while batch.len() < config.max_batch_size {
+            tokio::select! {
+                biased;
+                pending = receiver.recv() => match pending {
+                    Some(pending) => {
+                        let tokens = estimate_tokens(&pending.text);
+                        if batch_tokens + tokens > config.max_batch_tokens {
+                            carry = Some(pending);
+                            break;
+                        }
+                        batch_tokens += tokens;
+                        batch.push(pending);
+                    }
+                    None => break,
+                },
+                _ = &mut window => break,
+            }
+        }
+
+        let inner = Arc::clone(&inner);
+        let permits = Arc::clone(&permits);
+        tokio::spawn(async move {
+            let texts = batch.iter().map(|pending| pending.text.as_str()).collect();
+            match embed_batch(inner.as_ref(), &permits, texts).await {
+                Ok(embeddings) => {
+                    for (pending, embedding) in batch.into_iter().zip(embeddings) {
+                        let _ = pending.reply.send(Ok(embedding));
+                    }
+                }
+                Err(e) => {
+                    // Typed backend errors are kept so every caller can classify them.
+                    let typed = e.downcast_ref::<EmbeddingError>().cloned();
+                    let message = format!("{e:#}");
+                    for pending in batch {
+                        let error = match &typed {
+                            Some(typed) => anyhow::Error::new(typed.clone()),
+                            None => anyhow!(message.clone()),
+                        };
+                        let _ = pending.reply.send(Err(error));
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl<E: Embedder + Send + Sync + 'static> Embedder for BatchingEmbedder<E> {
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn dimensions(&self) -> usize {
+        self.inner.dimensions()
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+        let text = truncate(text, self.config.max_input_tokens);
+        let Some(coalescer) = self.coalescer() else {
+            let mut embeddings =
+                embed_batch(self.inner.as_ref(), &self.permits, vec![text]).await?;
+            return Ok(embeddings.remove(0));
+        };
+        let (reply, response) = oneshot::channel();
+        let pending = Pending {
+            text: text.to_string(),
+            reply,
+        };
+        coalescer
+            .send(pending)
+            .await
+            .map_err(|_| anyhow!("Embedding coalescer stopped"))?;
+        response.await.context("Embedding request dropped")?
+    }
+
+    async fn generate_embeddings_bulk<'em>(&self, texts: Vec<&'em str>) -> Result<Vec<Vec<f32>>> {
+        // Batches beyond the concurrency limit wait for a permit in `embed_batch`.
+        let batches = plan_batches(&texts, &self.config)
+            .into_iter()
+            .map(|batch| embed_batch(self.inner.as_ref(), &self.permits, batch));
+        let embeddings = try_join_all(batches).await?;
+        Ok(embeddings.into_iter().flatten().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+
+    /// Embeds each text as `[len]` and records the batches it was called with.
+    #[derive(Default)]
+    struct FakeEmbedder {
+        batches: Mutex<Vec<Vec<String>>>,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Embedder for FakeEmbedder {
+        fn model(&self) -> &str {
+            "fake"
+        }
+
+        fn dimensions(&self) -> usize {
+            1
+        }
+
+        async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+            Ok(vec![text.len() as f32])
+        }
+
+        async fn generate_embeddings_bulk<'em>(
+            &self,
+            texts: Vec<&'em str>,
+        ) -> Result<Vec<Vec<f32>>> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            self.batches
+                .lock()
+                .unwrap()
+                .push(texts.iter().map(|text| text.to_string()).collect());
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(texts.iter().map(|text| vec![text.len() as f32]).collect())
+        }
+    }
+
+    fn config() -> BatchConfig {
+        BatchConfig {
+            max_batch_size: 3,
+            max_batch_tokens: 100,
+            max_input_tokens: 10,
+            max_concurrency: 2,
+            coalesce_window: Duration::from_millis(20),
+        }
+    }
+
+    #[test]
+    fn test_plan_batches_respects_size_and_tokens() {
+        let config = BatchConfig {
+            max_batch_tokens: 15,
+            ..config()
+        };
+        let long = "x".repeat(36);
+        let texts = [
+            "a",
+            "b",
+            "c",
+            "d",
+            long.as_str(),
+            long.as_str(),
+            long.as_str(),
+        ];
+        let batches = plan_batches(&texts, &config);
+        let sizes = batches.iter().map(Vec::len).collect::<Vec<_>>();
+        // Three short texts fill a batch; the long ones are 9 tokens each.
+        assert_eq!(sizes, vec![3, 2, 1, 1]);
+    }
+
+    #[test]
+    fn test_plan_batches_truncates_long_inputs() {
+        let long = "y".repeat(100);
+        let batches = plan_batches(&[long.as_str()], &config());
+        assert_eq!(batches[0][0].len(), 40);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_preserves_order_and_limits_concurrency() {
+        let embedder = BatchingEmbedder::new(FakeEmbedder::default(), config());
+        let texts = (1..=10).map(|i| "z".repeat(i)).collect::<Vec<_>>();
+
+        let embeddings = embedder
+            .generate_embeddings_bulk(texts.iter().map(String::as_str).collect())
+            .await
+            .unwrap();
+
+        let lengths = embeddings.iter().map(|e| e[0] as usize).collect::<Vec<_>>();
+        assert_eq!(lengths, (1..=10).collect::<Vec<_>>());
+        assert_eq!(embedder.inner.batches.lock().unwrap().len(), 4);
+        assert!(embedder.inner.max_in_flight.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_single_embeddings_are_coalesced() {
+        let embedder = Arc::new(BatchingEmbedder::new(FakeEmbedder::default(), config()));
+        embedder.spawn_coalescer();
+
+        let calls = (1..=3).map(|i| {
+            let embedder = Arc::clone(&embedder);
+            tokio::spawn(async move { embedder.generate_embedding(&"w".repeat(i)).await })
+        });
+        let embeddings = futures::future::join_all(calls).await;
+
+        for (i, embedding) in embeddings.into_iter().enumerate() {
+            assert_eq!(embedding.unwrap().unwrap(), vec![(i + 1) as f32]);
+        }
+        assert_eq!(embedder.inner.batches.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_single_embeddings_outlive_the_coalescer_runtime() {
+        let embedder = BatchingEmbedder::new(FakeEmbedder::default(), config());
+        let first = tokio::runtime::Runtime::new().unwrap();
+        {
+            let _runtime = first.enter();
+            embedder.spawn_coalescer();
+        }
+        drop(first);
+
+        let second = tokio::runtime::Runtime::new().unwrap();
+        let embedding = second.block_on(embedder.generate_embedding("abc")).unwrap();
+        assert_eq!(embedding, vec![3.0]);
+    }
+}
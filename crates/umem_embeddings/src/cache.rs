@@ -0,0 +1,607 @@
+use crate::Embedder;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use futures::future::join_all;
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::SystemTime,
+};
+use tokio::sync::OnceCell;
+use tracing::{debug, warn};
+
+/// SHA-256 of the model id and the text.
+type Key = [u8; 32];
+
+/// Where and how much [`CachingEmbedder`] caches.
+#[derive(Clone, Debug)]
+pub struct CacheConfig {
+    /// Directory of the on-disk tier, `None` to keep embeddings in memory only.
+    pub dir: Option<PathBuf>,
+    /// Size of the on-disk tier; the least recently used entries are evicted beyond it.
+    pub max_disk_bytes: u64,
+    /// Embeddings kept in memory, `0` to read every hit from disk.
+    pub memory_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        // A bge-m3 embedding is 4 KiB, so this is about 16 MiB in memory.
+        Self {
+            dir: dirs::cache_dir().map(|dir| dir.join("umem").join("embeddings")),
+            max_disk_bytes: 1024 * 1024 * 1024,
+            memory_entries: 4096,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Reads `EMBEDDING_CACHE_DIR`, `EMBEDDING_CACHE_MAX_DISK_MB` and
+    /// `EMBEDDING_CACHE_MEMORY_ENTRIES`, keeping the defaults for unset variables.
+    /// A size of `0` disables that tier.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let max_disk_mb = env_u64("EMBEDDING_CACHE_MAX_DISK_MB", default.max_disk_bytes >> 20);
+        Self {
+            dir: std::env::var("EMBEDDING_CACHE_DIR")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .or(default.dir)
+                .filter(|_| max_disk_mb > 0),
+            max_disk_bytes: max_disk_mb << 20,
+            memory_entries: env_u64(
+                "EMBEDDING_CACHE_MEMORY_ENTRIES",
+                default.memory_entries as u64,
+            ) as usize,
+        }
+    }
+}
+
+fn env_u64(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Cache counters since startup.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub memory_hits: u64,
+    pub disk_hits: u64,
+    pub misses: u64,
+    pub disk_bytes: u64,
+}
+
+impl CacheStats {
+    pub fn hit_ratio(&self) -> f64 {
+        let hits = self.memory_hits + self.disk_hits;
+        match hits + self.misses {
+            0 => 0.0,
+            total => hits as f64 / total as f64,
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} memory hits, {} disk hits, {} misses ({:.1}% hit ratio), {} bytes on disk",
+            self.memory_hits,
+            self.disk_hits,
+            self.misses,
+            self.hit_ratio() * 100.0,
+            self.disk_bytes
+        )
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    memory_hits: AtomicU64,
+    disk_hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Wraps an [`Embedder`] so texts already embedded by the same model are served from an
+/// in-memory LRU or a local on-disk store instead of the backend.
+///
+/// The cache never fails a request: disk errors are logged and treated as misses.
+pub struct CachingEmbedder<E> {
+    inner: E,
+    memory: Option<Mutex<LruCache<Key, Vec<f32>>>>,
+    disk: Option<Arc<DiskCache>>,
+    counters: Counters,
+}
+
+impl<E: Embedder> CachingEmbedder<E> {
+    pub fn new(inner: E, config: CacheConfig) -> Self {
+        let disk = config
+            .dir
+            .and_then(|dir| match DiskCache::open(&dir, config.max_disk_bytes) {
+                Ok(disk) => Some(Arc::new(disk)),
+                Err(e) => {
+                    warn!("Embedding disk cache at {} disabled: {}", dir.display(), e);
+                    None
+                }
+            });
+        Self {
+            inner,
+            memory: NonZeroUsize::new(config.memory_entries)
+                .map(|entries| Mutex::new(LruCache::new(entries))),
+            disk,
+            counters: Counters::default(),
+        }
+    }
+
+    /// The wrapped embedder, for calls that must reach the backend.
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            memory_hits: self.counters.memory_hits.load(Ordering::Relaxed),
+            disk_hits: self.counters.disk_hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed),
+            disk_bytes: self.disk.as_ref().map_or(0, |disk| disk.used_bytes()),
+        }
+    }
+
+    fn key(&self, text: &str) -> Key {
+        let mut hasher = Sha256::new();
+        hasher.update(self.inner.model().as_bytes());
+        hasher.update([0]);
+        hasher.update(text.as_bytes());
+        hasher.finalize().into()
+    }
+
+    async fn lookup(&self, key: &Key) -> Option<Vec<f32>> {
+        if let Some(memory) = &self.memory {
+            if let Some(embedding) = memory.lock().unwrap().get(key) {
+                self.counters.memory_hits.fetch_add(1, Ordering::Relaxed);
+                return Some(embedding.clone());
+            }
+        }
+        if let Some(disk) = &self.disk {
+            if let Some(embedding) = disk.get(key).await {
+                self.counters.disk_hits.fetch_add(1, Ordering::Relaxed);
+                if let Some(memory) = &self.memory {
+                    memory.lock().unwrap().put(*key, embedding.clone());
+                }
+                return Some(embedding);
+            }
+        }
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+        None
+    }
+
+    async fn store(&self, key: Key, embedding: &[f32]) {
+        if let Some(memory) = &self.memory {
+            memory.lock().unwrap().put(key, embedding.to_vec());
+        }
+        if let Some(disk) = &self.disk {
+            disk.put(&key, embedding).await;
+        }
+    }
+}
+
+#[async_trait]
+impl<E: Embedder + Send + Sync> Embedder for CachingEmbedder<E> {
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn dimensions(&self) -> usize {
+        self.inner.dimensions()
+    }
+
+    fn is_available(&self) -> bool {
+        self.inner.is_available()
+    }
+
+    async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+        let key = self.key(text);
+        if let Some(embedding) = self.lookup(&key).await {
+            return Ok(embedding);
+        }
+        let embedding = self.inner.generate_embedding(text).await?;
+        self.store(key, &embedding).await;
+        Ok(embedding)
+    }
+
+    async fn generate_embeddings_bulk<'em>(&self, texts: Vec<&'em str>) -> Result<Vec<Vec<f32>>> {
+        let keys = texts.iter().map(|text| self.key(text)).collect::<Vec<_>>();
+        let mut embeddings = join_all(keys.iter().map(|key| self.lookup(key))).await;
+
+        // Each distinct missing text is embedded once, however often it repeats.
+        let mut missing = HashMap::<Key, Vec<usize>>::new();
+        let mut missing_texts = Vec::new();
+        for (index, embedding) in embeddings.iter().enumerate() {
+            if embedding.is_none() {
+                let positions = missing.entry(keys[index]).or_default();
+                if positions.is_empty() {
+                    missing_texts.push((keys[index], texts[index]));
+                }
+                positions.push(index);
+            }
+        }
+
+        if !missing_texts.is_empty() {
+            let generated = self
+                .inner
+                .generate_embeddings_bulk(missing_texts.iter().map(|(_, text)| *text).collect())
+                .await?;
+            if generated.len() != missing_texts.len() {
+                bail!(
+                    "Embedding backend returned {} embeddings for {} texts",
+                    generated.len(),
+                    missing_texts.len()
+                );
+            }
+            for ((key, _), embedding) in missing_texts.into_iter().zip(generated) {
+                self.store(key, &embedding).await;
+                for index in &missing[&key] {
+                    embeddings[*index] = Some(embedding.clone());
+                }
+            }
+        }
+
+        Ok(embeddings.into_iter().flatten().collect())
+    }
+}
+
+/// One file of little-endian `f32`s per embedding, under a two-character fan-out
+/// directory. Files are written under a temporary name and renamed into place, so
+/// readers never see a partial embedding. Hits refresh a file's modification time,
+/// which eviction orders by.
+struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Measured by walking `dir` off the runtime before the first write.
+    used_bytes: OnceCell<AtomicU64>,
+    evicting: AtomicBool,
+}
+
+impl DiskCache {
+    fn open(dir: &Path, max_bytes: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            max_bytes,
+            used_bytes: OnceCell::new(),
+            evicting: AtomicBool::new(false),
+        })
+    }
+
+    fn used_bytes(&self) -> u64 {
+        self.used_bytes
+            .get()
+            .map_or(0, |used| used.load(Ordering::Relaxed))
+    }
+
+    async fn measured_bytes(&self) -> &AtomicU64 {
+        self.used_bytes
+            .get_or_init(|| async {
+                let dir = self.dir.clone();
+                let used = tokio::task::spawn_blocking(move || entries(&dir))
+                    .await
+                    .map_err(io::Error::other)
+                    .and_then(|entries| entries);
+                match used {
+                    Ok(entries) => AtomicU64::new(entries.iter().map(|entry| entry.len).sum()),
+                    Err(e) => {
+                        // Eviction measures again, so a failed walk only delays it.
+                        warn!("Failed to measure the embedding disk cache: {}", e);
+                        AtomicU64::new(0)
+                    }
+                }
+            })
+            .await
+    }
+
+    fn path(&self, key: &Key) -> PathBuf {
+        let name = key
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        self.dir.join(&name[..2]).join(name)
+    }
+
+    async fn get(&self, key: &Key) -> Option<Vec<f32>> {
+        let path = self.path(key);
+        let bytes = match tokio::fs::read(&path).await {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Failed to read cached embedding {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        if bytes.is_empty() || bytes.len() % 4 != 0 {
+            warn!("Removing corrupt cached embedding {}", path.display());
+            let _ = tokio::fs::remove_file(&path).await;
+            return None;
+        }
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = touch(&path) {
+                debug!("Failed to touch cached embedding {}: {}", path.display(), e);
+            }
+        });
+        Some(
+            bytes
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                .collect(),
+        )
+    }
+
+    async fn put(self: &Arc<Self>, key: &Key, embedding: &[f32]) {
+        let path = self.path(key);
+        let bytes = embedding
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+        let len = bytes.len() as u64;
+        let used_bytes = self.measured_bytes().await;
+        if let Err(e) = write_atomically(&path, bytes).await {
+            warn!("Failed to cache embedding {}: {}", path.display(), e);
+            return;
+        }
+
+        let used = used_bytes.fetch_add(len, Ordering::Relaxed) + len;
+        if used > self.max_bytes && !self.evicting.swap(true, Ordering::AcqRel) {
+            let disk = Arc::clone(self);
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = disk.evict() {
+                    warn!("Failed to evict cached embeddings: {}", e);
+                }
+                disk.evicting.store(false, Ordering::Release);
+            });
+        }
+    }
+
+    /// Removes the least recently used entries until the cache is back under 90% of
+    /// its size.
+    fn evict(&self) -> io::Result<()> {
+        let mut entries = entries(&self.dir)?;
+        entries.sort_by_key(|entry| entry.used);
+        let target = self.max_bytes / 10 * 9;
+        let mut used = entries.iter().map(|entry| entry.len).sum::<u64>();
+        let mut evicted = 0;
+
+        for entry in entries {
+            if used <= target {
+                break;
+            }
+            match fs::remove_file(&entry.path) {
+                Ok(()) => {
+                    used -= entry.len;
+                    evicted += 1;
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => used -= entry.len,
+                Err(e) => return Err(e),
+            }
+        }
+
+        if let Some(used_bytes) = self.used_bytes.get() {
+            used_bytes.store(used, Ordering::Relaxed);
+        }
+        debug!(
+            "Evicted {} cached embeddings, {} bytes remain",
+            evicted, used
+        );
+        Ok(())
+    }
+}
+
+async fn write_atomically(path: &Path, bytes: Vec<u8>) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+    let temporary = path.with_extension(format!("tmp{}", fastrand::u64(..)));
+    tokio::fs::write(&temporary, bytes).await?;
+    if let Err(e) = tokio::fs::rename(&temporary, path).await {
+        let _ = tokio::fs::remove_file(&temporary).await;
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn touch(path: &Path) -> io::Result<()> {
+    fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(SystemTime::now())
+}
+
+struct Entry {
+    path: PathBuf,
+    len: u64,
+    used: SystemTime,
+}
+
+fn entries(dir: &Path) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for fan_out in fs::read_dir(dir)? {
+        let fan_out = fan_out?;
+        if !fan_out.file_type()?.is_dir() {
+            continue;
+        }
+        for file in fs::read_dir(fan_out.path())? {
+            let file = file?;
+            let metadata = file.metadata()?;
+            if metadata.is_file() {
+                entries.push(Entry {
+                    path: file.path(),
+                    len: metadata.len(),
+                    used: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::atomic::AtomicUsize, time::Duration};
+
+    /// Embeds each text as `[len]` and counts the texts it was asked for.
+    #[derive(Default)]
+    struct CountingEmbedder {
+        embedded: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Embedder for CountingEmbedder {
+        fn model(&self) -> &str {
+            "test-model"
+        }
+
+        fn dimensions(&self) -> usize {
+            1
+        }
+
+        async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+            self.embedded.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![text.len() as f32])
+        }
+
+        async fn generate_embeddings_bulk<'em>(
+            &self,
+            texts: Vec<&'em str>,
+        ) -> Result<Vec<Vec<f32>>> {
+            self.embedded.fetch_add(texts.len(), Ordering::SeqCst);
+            Ok(texts.iter().map(|text| vec![text.len() as f32]).collect())
+        }
+    }
+
+    fn config(dir: &Path) -> CacheConfig {
+        CacheConfig {
+            dir: Some(dir.to_path_buf()),
+            max_disk_bytes: 1024 * 1024,
+            memory_entries: 16,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_repeated_texts_are_served_from_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let embedder = CachingEmbedder::new(CountingEmbedder::default(), config(dir.path()));
+
+        assert_eq!(
+            embedder.generate_embedding("hello").await.unwrap(),
+            vec![5.0]
+        );
+        assert_eq!(
+            embedder.generate_embedding("hello").await.unwrap(),
+            vec![5.0]
+        );
+
+        assert_eq!(embedder.inner().embedded.load(Ordering::SeqCst), 1);
+        let stats = embedder.stats();
+        assert_eq!((stats.memory_hits, stats.misses), (1, 1));
+        assert_eq!(stats.disk_bytes, 4);
+    }
+
+    #[tokio::test]
+    async fn test_disk_tier_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = CachingEmbedder::new(CountingEmbedder::default(), config(dir.path()));
+        first.generate_embedding("persisted").await.unwrap();
+
+        let second = CachingEmbedder::new(CountingEmbedder::default(), config(dir.path()));
+        assert_eq!(
+            second.generate_embedding("persisted").await.unwrap(),
+            vec![9.0]
+        );
+        assert_eq!(second.inner().embedded.load(Ordering::SeqCst), 0);
+        assert_eq!(second.stats().disk_hits, 1);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_embeds_only_distinct_misses() {
+        let dir = tempfile::tempdir().unwrap();
+        let embedder = CachingEmbedder::new(CountingEmbedder::default(), config(dir.path()));
+        embedder.generate_embedding("a").await.unwrap();
+
+        let embeddings = embedder
+            .generate_embeddings_bulk(vec!["a", "bb", "ccc", "bb"])
+            .await
+            .unwrap();
+
+        assert_eq!(embeddings, vec![vec![1.0], vec![2.0], vec![3.0], vec![2.0]]);
+        // "a" came from the cache and "bb" was embedded once.
+        assert_eq!(embedder.inner().embedded.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_eviction_keeps_disk_under_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = Arc::new(DiskCache::open(dir.path(), 40).unwrap());
+        for i in 0..5u8 {
+            disk.put(&[i; 32], &[0.0; 4]).await;
+        }
+        disk.evict().unwrap();
+
+        assert_eq!(disk.used_bytes(), 32);
+        assert_eq!(entries(dir.path()).unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_eviction_keeps_recently_read_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = Arc::new(DiskCache::open(dir.path(), 1024).unwrap());
+        for i in 0..5u8 {
+            disk.put(&[i; 32], &[0.0; 4]).await;
+            let written = SystemTime::now() - Duration::from_secs(60 * (10 - i as u64));
+            fs::File::options()
+                .write(true)
+                .open(disk.path(&[i; 32]))
+                .unwrap()
+                .set_modified(written)
+                .unwrap();
+        }
+        let stale = SystemTime::now() - Duration::from_secs(60);
+        disk.get(&[0; 32]).await.unwrap();
+        // The touch runs in the background.
+        while fs::metadata(disk.path(&[0; 32]))
+            .unwrap()
+            .modified()
+            .unwrap()
+            < stale
+        {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let disk = DiskCache::open(dir.path(), 40).unwrap();
+        disk.evict().unwrap();
+
+        assert!(disk.path(&[0; 32]).exists());
+        assert!(disk.path(&[4; 32]).exists());
+        assert!(!disk.path(&[1; 32]).exists());
+    }
+
+    #[tokio::test]
+    async fn test_existing_entries_are_measured_before_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = Arc::new(DiskCache::open(dir.path(), 1024).unwrap());
+        first.put(&[1; 32], &[0.0; 2]).await;
+
+        let second = Arc::new(DiskCache::open(dir.path(), 1024).unwrap());
+        assert_eq!(second.used_bytes(), 0);
+        second.put(&[2; 32], &[0.0; 2]).await;
+        assert_eq!(second.used_bytes(), 16);
+    }
+}
@@ -1,76 +1,137 @@
-use crate::{client, Embedder};
-use anyhow::{bail, Result};
+use crate::{
+    resilience::{http_client, retry_after, Resilience, ResilienceConfig},
+    Embedder, EmbeddingError, ProviderError,
+};
+use anyhow::Result;
 use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 const CF_BAAI_BGE_M3_EMBEDER_NAME: &str = "@cf/baai/bge-m3";
+/// The model Workers AI serves as `@cf/baai/bge-m3`.
+const BGE_M3_MODEL: &str = "BAAI/bge-m3";
+const BGE_M3_DIMENSIONS: usize = 1024;
 
 pub struct CfBaaiBgeM3Embeder {
     model_name: &'static str,
     account_id: String,
     api_token: String,
+    client: Client,
+    resilience: Resilience,
 }
 
 impl CfBaaiBgeM3Embeder {
-    pub fn new(account_id: String, api_token: String) -> Self {
+    pub fn new(account_id: String, api_token: String, config: ResilienceConfig) -> Self {
         Self {
             model_name: CF_BAAI_BGE_M3_EMBEDER_NAME,
             account_id,
             api_token,
+            client: http_client(&config),
+            resilience: Resilience::new(config),
         }
     }
-}
 
-#[async_trait]
-impl Embedder for CfBaaiBgeM3Embeder {
-    async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+    async fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
         let url = format!(
             "https://api.cloudflare.com/client/v4/accounts/{}/ai/run/{}",
             self.account_id, self.model_name
         );
-        let request_body = EmbeddingRequest { text: vec![text] };
-        let response = client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .json(&request_body)
-            .send()
-            .await?;
-        let mut embedding_response: EmbeddingResponse = response.json().await?;
-        if !embedding_response.success {
-            bail!("{:?}", embedding_response.errors);
-        }
-        Ok(std::mem::take(&mut embedding_response.result.data[0]))
+        let request_body = EmbeddingRequest { text: texts };
+        self.resilience
+            .run(|| async {
+                let response = self
+                    .client
+                    .post(&url)
+                    .bearer_auth(&self.api_token)
+                    .json(&request_body)
+                    .send()
+                    .await?;
+                let status = response.status();
+                let retry_after = retry_after(response.headers());
+                let body = response.bytes().await?;
+                parse_response(status, retry_after, &body, texts.len())
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl Embedder for CfBaaiBgeM3Embeder {
+    fn model(&self) -> &str {
+        BGE_M3_MODEL
+    }
+
+    fn dimensions(&self) -> usize {
+        BGE_M3_DIMENSIONS
+    }
+
+    fn is_available(&self) -> bool {
+        !self.resilience.is_open()
+    }
+
+    async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+        let mut embeddings = self.embed(&[text]).await?;
+        Ok(embeddings.swap_remove(0))
     }
 
     async fn generate_embeddings_bulk<'em>(&self, texts: Vec<&'em str>) -> Result<Vec<Vec<f32>>> {
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/accounts/{}/ai/run/{}",
-            self.account_id, self.model_name
-        );
-        let request_body = EmbeddingRequest { text: texts };
-        let response = client
-            .post(&url)
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .json(&request_body)
-            .send()
-            .await?;
-        let embedding_response: EmbeddingResponse = response.json().await?;
-        if !embedding_response.success {
-            bail!("{:?}", embedding_response.errors);
-        }
-        Ok(embedding_response.result.data)
+        Ok(self.embed(&texts).await?)
+    }
+}
+
+/// Turns a Workers AI response into embeddings or a typed error. Error bodies are not
+/// always JSON, so a body that does not parse still yields the HTTP status.
+fn parse_response(
+    status: StatusCode,
+    retry_after: Option<Duration>,
+    body: &[u8],
+    expected: usize,
+) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(EmbeddingError::RateLimited { retry_after });
+    }
+    let response = serde_json::from_slice::<EmbeddingResponse>(body);
+    if !status.is_success() {
+        return Err(EmbeddingError::Provider {
+            status: status.as_u16(),
+            errors: response.map(|response| response.errors).unwrap_or_default(),
+            retry_after,
+        });
+    }
+
+    let response = response.map_err(|e| EmbeddingError::InvalidResponse(e.to_string()))?;
+    if !response.success {
+        return Err(EmbeddingError::Provider {
+            status: status.as_u16(),
+            errors: response.errors,
+            retry_after,
+        });
+    }
+    let data = response
+        .result
+        .map(|result| result.data)
+        .ok_or_else(|| EmbeddingError::InvalidResponse("missing result".to_string()))?;
+    if data.len() != expected {
+        return Err(EmbeddingError::InvalidResponse(format!(
+            "{} embeddings for {} texts",
+            data.len(),
+            expected
+        )));
     }
+    Ok(data)
 }
 
 #[derive(Serialize)]
 struct EmbeddingRequest<'em> {
-    text: Vec<&'em str>,
+    text: &'em [&'em str],
 }
 
 #[derive(Deserialize)]
 struct EmbeddingResponse {
-    result: EmbeddingResult,
-    errors: Vec<String>,
+    result: Option<EmbeddingResult>,
+    #[serde(default)]
+    errors: Vec<ProviderError>,
     success: bool,
 }
 
@@ -78,3 +139,70 @@ struct EmbeddingResponse {
 struct EmbeddingResult {
     data: Vec<Vec<f32>>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderMap;
+    use std::time::SystemTime;
+
+    #[test]
+    fn test_parses_embeddings() {
+        let body = br#"{"result":{"shape":[2,2],"data":[[0.1,0.2],[0.3,0.4]]},"success":true,"errors":[],"messages":[]}"#;
+        let data = parse_response(StatusCode::OK, None, body, 2).unwrap();
+        assert_eq!(data, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+    }
+
+    #[test]
+    fn test_provider_errors_are_typed() {
+        let body = br#"{"result":null,"success":false,"errors":[{"code":5006,"message":"Invalid input"}],"messages":[]}"#;
+        let error = parse_response(StatusCode::BAD_REQUEST, None, body, 1).unwrap_err();
+        match &error {
+            EmbeddingError::Provider { status, errors, .. } => {
+                assert_eq!(*status, 400);
+                assert_eq!(errors[0].code, 5006);
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_non_json_server_errors_are_retryable() {
+        let error =
+            parse_response(StatusCode::BAD_GATEWAY, None, b"<html>502</html>", 1).unwrap_err();
+        assert!(matches!(
+            error,
+            EmbeddingError::Provider { status: 502, ref errors, .. } if errors.is_empty()
+        ));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_rate_limit_honors_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, "7".parse().unwrap());
+        let error = parse_response(StatusCode::TOO_MANY_REQUESTS, retry_after(&headers), b"", 1)
+            .unwrap_err();
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(7)));
+        assert!(error.is_retryable());
+    }
+
+    #[test]
+    fn test_rate_limit_honors_retry_after_dates() {
+        let mut headers = HeaderMap::new();
+        let date = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(120));
+        headers.insert(reqwest::header::RETRY_AFTER, date.parse().unwrap());
+        let error = parse_response(StatusCode::TOO_MANY_REQUESTS, retry_after(&headers), b"", 1)
+            .unwrap_err();
+        let delay = error.retry_after().unwrap();
+        // HTTP dates have whole second precision.
+        assert!(delay > Duration::from_secs(118) && delay <= Duration::from_secs(120));
+
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+}
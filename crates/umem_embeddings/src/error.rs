@@ -0,0 +1,123 @@
+use serde::Deserialize;
+use std::{fmt, time::Duration};
+
+/// An error reported by an embedding provider, such as an entry of the Cloudflare API
+/// `errors` array.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ProviderError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/// Failures of an embedding backend, classified so callers can tell bad input apart
+/// from failures worth retrying.
+///
+/// The error is `Clone` so one failed backend request can be reported to every caller
+/// that shared it.
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum EmbeddingError {
+    #[error("Embedding backend rate limited the request")]
+    RateLimited { retry_after: Option<Duration> },
+    #[error("Embedding backend returned {status}: {}", join(.errors))]
+    Provider {
+        status: u16,
+        errors: Vec<ProviderError>,
+        retry_after: Option<Duration>,
+    },
+    #[error("Embedding request timed out")]
+    Timeout,
+    #[error("Embedding request failed: {message}")]
+    Transport { message: String, retryable: bool },
+    #[error("Invalid embedding response: {0}")]
+    InvalidResponse(String),
+    #[error("Embedding backend circuit is open")]
+    CircuitOpen { retry_after: Duration },
+}
+
+fn join(errors: &[ProviderError]) -> String {
+    if errors.is_empty() {
+        return "no error details".to_string();
+    }
+    errors
+        .iter()
+        .map(ProviderError::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl EmbeddingError {
+    /// Whether the same request may succeed if retried later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RateLimited { .. } | Self::Timeout | Self::CircuitOpen { .. } => true,
+            Self::Provider { status, .. } => *status >= 500,
+            Self::Transport { retryable, .. } => *retryable,
+            Self::InvalidResponse(_) => false,
+        }
+    }
+
+    /// Whether the provider rejected the text itself, so no other provider would accept it.
+    pub fn is_input_rejected(&self) -> bool {
+        matches!(
+            self,
+            Self::Provider {
+                status: 400 | 413 | 422,
+                ..
+            }
+        )
+    }
+
+    /// How long the backend asked callers to wait, if it said.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after } | Self::Provider { retry_after, .. } => *retry_after,
+            Self::CircuitOpen { retry_after } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for EmbeddingError {
+    fn from(error: reqwest::Error) -> Self {
+        // Request URLs carry the Cloudflare account id.
+        let error = error.without_url();
+        if error.is_timeout() {
+            return Self::Timeout;
+        }
+        if error.is_decode() {
+            return Self::InvalidResponse(error.to_string());
+        }
+        let mut message = error.to_string();
+        let mut source = std::error::Error::source(&error);
+        while let Some(cause) = source {
+            message.push_str(&format!(": {cause}"));
+            source = cause.source();
+        }
+        Self::Transport {
+            retryable: !error.is_builder(),
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_transport_errors_drop_the_request_url() {
+        // Nothing listens on port 1.
+        let error = reqwest::get("http://127.0.0.1:1/client/v4/accounts/account-id/ai/run")
+            .await
+            .unwrap_err();
+        let error = EmbeddingError::from(error);
+        assert!(matches!(error, EmbeddingError::Transport { .. }));
+        assert!(!error.to_string().contains("account-id"));
+    }
+}
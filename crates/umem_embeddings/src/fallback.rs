@@ -0,0 +1,278 @@
+use crate::{Embedder, EmbeddingError};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(30);
+
+/// How long a failed provider is skipped, from `EMBEDDING_FALLBACK_COOL_DOWN_SECS`.
+pub fn cool_down_from_env() -> Duration {
+    std::env::var("EMBEDDING_FALLBACK_COOL_DOWN_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_COOL_DOWN)
+}
+
+pub type BoxedEmbedder = Box<dyn Embedder + Send + Sync>;
+
+struct Provider {
+    name: String,
+    embedder: BoxedEmbedder,
+    cooling_until: Mutex<Option<Instant>>,
+}
+
+impl Provider {
+    fn is_cooling(&self, now: Instant) -> bool {
+        self.cooling_until
+            .lock()
+            .unwrap()
+            .is_some_and(|until| now < until)
+    }
+
+    fn mark_failed(&self, cool_down: Duration) {
+        *self.cooling_until.lock().unwrap() = Some(Instant::now() + cool_down);
+    }
+
+    fn mark_healthy(&self) {
+        if self.cooling_until.lock().unwrap().take().is_some() {
+            info!("Embedding provider {} recovered", self.name);
+        }
+    }
+}
+
+/// Tries embedding providers in order until one answers.
+///
+/// Every provider must declare the same model and dimensions as the first, so callers
+/// get interchangeable embeddings whichever provider served them. A provider that
+/// fails is skipped for `cool_down`, unless every provider is cooling down. Text a
+/// provider rejects is not offered to the others.
+pub struct FallbackEmbedder {
+    providers: Vec<Provider>,
+    cool_down: Duration,
+}
+
+impl FallbackEmbedder {
+    pub fn new(providers: Vec<(String, BoxedEmbedder)>, cool_down: Duration) -> Result<Self> {
+        let Some((primary_name, primary)) = providers.first() else {
+            bail!("At least one embedding provider is required");
+        };
+        for (name, embedder) in &providers[1..] {
+            if embedder.model() != primary.model() || embedder.dimensions() != primary.dimensions()
+            {
+                bail!(
+                    "Embedding provider {} serves {} ({} dimensions), incompatible with {} serving {} ({} dimensions)",
+                    name,
+                    embedder.model(),
+                    embedder.dimensions(),
+                    primary_name,
+                    primary.model(),
+                    primary.dimensions()
+                );
+            }
+        }
+
+        Ok(Self {
+            providers: providers
+                .into_iter()
+                .map(|(name, embedder)| Provider {
+                    name,
+                    embedder,
+                    cooling_until: Mutex::new(None),
+                })
+                .collect(),
+            cool_down,
+        })
+    }
+
+    /// Providers that are not cooling down come first, in their configured order.
+    fn candidates(&self) -> impl Iterator<Item = &Provider> {
+        let now = Instant::now();
+        let (ready, cooling): (Vec<_>, Vec<_>) = self
+            .providers
+            .iter()
+            .partition(|provider| !provider.is_cooling(now));
+        ready.into_iter().chain(cooling)
+    }
+}
+
+#[async_trait]
+impl Embedder for FallbackEmbedder {
+    fn model(&self) -> &str {
+        self.providers[0].embedder.model()
+    }
+
+    fn dimensions(&self) -> usize {
+        self.providers[0].embedder.dimensions()
+    }
+
+    /// Available while any provider is neither cooling down nor refusing calls.
+    fn is_available(&self) -> bool {
+        let now = Instant::now();
+        self.providers
+            .iter()
+            .any(|provider| !provider.is_cooling(now) && provider.embedder.is_available())
+    }
+
+    async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+        let mut embeddings = self.generate_embeddings_bulk(vec![text]).await?;
+        Ok(embeddings.swap_remove(0))
+    }
+
+    async fn generate_embeddings_bulk<'em>(&self, texts: Vec<&'em str>) -> Result<Vec<Vec<f32>>> {
+        let mut last_error = None;
+        for provider in self.candidates() {
+            let error = match provider
+                .embedder
+                .generate_embeddings_bulk(texts.clone())
+                .await
+            {
+                Ok(embeddings)
+                    if embeddings.len() == texts.len()
+                        && embeddings.iter().all(|e| e.len() == self.dimensions()) =>
+                {
+                    provider.mark_healthy();
+                    return Ok(embeddings);
+                }
+                Ok(_) => anyhow!(
+                    "Embedding provider {} returned embeddings of the wrong shape",
+                    provider.name
+                ),
+                Err(e) => {
+                    let rejected = e
+                        .downcast_ref::<EmbeddingError>()
+                        .is_some_and(EmbeddingError::is_input_rejected);
+                    if rejected {
+                        return Err(e);
+                    }
+                    e
+                }
+            };
+
+            warn!(
+                "Embedding provider {} failed, skipping it for {:?}: {:#}",
+                provider.name, self.cool_down, error
+            );
+            provider.mark_failed(self.cool_down);
+            last_error = Some(error);
+        }
+        Err(last_error.expect("FallbackEmbedder has at least one provider"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Fails with `error` when set, otherwise embeds each text as `[value]`.
+    struct FakeProvider {
+        model: &'static str,
+        value: f32,
+        error: Option<EmbeddingError>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Embedder for FakeProvider {
+        fn model(&self) -> &str {
+            self.model
+        }
+
+        fn dimensions(&self) -> usize {
+            1
+        }
+
+        async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+            Ok(self.generate_embeddings_bulk(vec![text]).await?.remove(0))
+        }
+
+        async fn generate_embeddings_bulk<'em>(
+            &self,
+            texts: Vec<&'em str>,
+        ) -> Result<Vec<Vec<f32>>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match &self.error {
+                Some(error) => Err(error.clone().into()),
+                None => Ok(texts.iter().map(|_| vec![self.value]).collect()),
+            }
+        }
+    }
+
+    fn provider(
+        value: f32,
+        error: Option<EmbeddingError>,
+    ) -> (Arc<AtomicUsize>, (String, BoxedEmbedder)) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let embedder = FakeProvider {
+            model: "bge-m3",
+            value,
+            error,
+            calls: Arc::clone(&calls),
+        };
+        (calls, (format!("provider-{value}"), Box::new(embedder)))
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_and_skips_failed_provider_during_cool_down() {
+        let (primary_calls, primary) = provider(1.0, Some(EmbeddingError::Timeout));
+        let (_, secondary) = provider(2.0, None);
+        let embedder = FallbackEmbedder::new(vec![primary, secondary], DEFAULT_COOL_DOWN).unwrap();
+
+        assert_eq!(embedder.generate_embedding("a").await.unwrap(), vec![2.0]);
+        assert_eq!(embedder.generate_embedding("b").await.unwrap(), vec![2.0]);
+        assert_eq!(primary_calls.load(Ordering::SeqCst), 1);
+        assert!(embedder.is_available());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_input_is_not_offered_to_other_providers() {
+        let rejected = EmbeddingError::Provider {
+            status: 413,
+            errors: Vec::new(),
+            retry_after: None,
+        };
+        let (_, primary) = provider(1.0, Some(rejected));
+        let (secondary_calls, secondary) = provider(2.0, None);
+        let embedder = FallbackEmbedder::new(vec![primary, secondary], DEFAULT_COOL_DOWN).unwrap();
+
+        assert!(embedder.generate_embedding("too long").await.is_err());
+        assert_eq!(secondary_calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cooling_providers_are_tried_when_nothing_else_is_left() {
+        let (calls, only) = provider(1.0, Some(EmbeddingError::Timeout));
+        let embedder = FallbackEmbedder::new(vec![only], DEFAULT_COOL_DOWN).unwrap();
+
+        assert!(embedder.is_available());
+        assert!(embedder.generate_embedding("a").await.is_err());
+        assert!(!embedder.is_available());
+        assert!(embedder.generate_embedding("b").await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_incompatible_providers_are_rejected() {
+        let (_, primary) = provider(1.0, None);
+        let other = FakeProvider {
+            model: "text-embedding-3-small",
+            value: 2.0,
+            error: None,
+            calls: Arc::default(),
+        };
+        let result = FallbackEmbedder::new(
+            vec![primary, ("openai".to_string(), Box::new(other))],
+            DEFAULT_COOL_DOWN,
+        );
+        assert!(result.is_err());
+    }
+}
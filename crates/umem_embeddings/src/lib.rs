@@ -1,16 +1,31 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use lazy_static::lazy_static;
-use reqwest::Client;
+mod batching;
+mod cache;
 mod cf_baai_bge_m3;
+mod error;
+mod fallback;
+mod openai_compatible;
+mod resilience;
+pub use batching::{estimate_tokens, BatchConfig, BatchingEmbedder};
+pub use cache::{CacheConfig, CacheStats, CachingEmbedder};
 pub use cf_baai_bge_m3::CfBaaiBgeM3Embeder;
-
-lazy_static! {
-    static ref client: Client = Client::new();
-}
+pub use error::{EmbeddingError, ProviderError};
+pub use fallback::{cool_down_from_env, BoxedEmbedder, FallbackEmbedder};
+pub use openai_compatible::OpenAiCompatibleEmbedder;
+pub use resilience::{Resilience, ResilienceConfig};
 
 #[async_trait]
 pub trait Embedder {
+    /// Identifies the model, so embeddings from different models are never mixed up.
+    fn model(&self) -> &str;
+    /// Length of the embeddings the model produces.
+    fn dimensions(&self) -> usize;
+    /// Whether the backend is expected to answer, judged from recent calls without
+    /// making one.
+    fn is_available(&self) -> bool {
+        true
+    }
     async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>>;
     async fn generate_embeddings_bulk<'em>(&self, texts: Vec<&'em str>) -> Result<Vec<Vec<f32>>>;
 }
@@ -0,0 +1,216 @@
+use crate::{
+    resilience::{http_client, retry_after, Resilience, ResilienceConfig},
+    Embedder, EmbeddingError, ProviderError,
+};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Embeds through any server implementing the OpenAI `/embeddings` API, such as OpenAI
+/// itself, Text Embeddings Inference or a local Ollama.
+pub struct OpenAiCompatibleEmbedder {
+    url: String,
+    api_key: Option<String>,
+    /// The name the server knows the model by.
+    request_model: String,
+    model: String,
+    dimensions: usize,
+    client: Client,
+    resilience: Resilience,
+}
+
+impl OpenAiCompatibleEmbedder {
+    /// `model` and `dimensions` declare what the server returns for `request_model`,
+    /// which decides the providers it may stand in for.
+    pub fn new(
+        base_url: &str,
+        api_key: Option<String>,
+        request_model: String,
+        model: String,
+        dimensions: usize,
+        config: ResilienceConfig,
+    ) -> Self {
+        Self {
+            url: format!("{}/embeddings", base_url.trim_end_matches('/')),
+            api_key,
+            request_model,
+            model,
+            dimensions,
+            client: http_client(&config),
+            resilience: Resilience::new(config),
+        }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Reads the providers configured as `EMBEDDING_FALLBACK_<N>_URL`, `_API_KEY`,
+    /// `_MODEL`, `_DECLARED_MODEL` and `_DECLARED_DIMENSIONS` for `N` counting from 1,
+    /// stopping at the first missing URL.
+    pub fn all_from_env(config: &ResilienceConfig) -> Result<Vec<Self>> {
+        let mut providers = Vec::new();
+        for n in 1.. {
+            let var = |name: &str| std::env::var(format!("EMBEDDING_FALLBACK_{n}_{name}")).ok();
+            let Some(url) = var("URL") else {
+                break;
+            };
+            let model = var("DECLARED_MODEL")
+                .with_context(|| format!("EMBEDDING_FALLBACK_{n}_DECLARED_MODEL not set"))?;
+            let dimensions = var("DECLARED_DIMENSIONS")
+                .with_context(|| format!("EMBEDDING_FALLBACK_{n}_DECLARED_DIMENSIONS not set"))?
+                .parse()
+                .with_context(|| format!("Invalid EMBEDDING_FALLBACK_{n}_DECLARED_DIMENSIONS"))?;
+            providers.push(Self::new(
+                &url,
+                var("API_KEY"),
+                var("MODEL").unwrap_or_else(|| model.clone()),
+                model,
+                dimensions,
+                config.clone(),
+            ));
+        }
+        Ok(providers)
+    }
+
+    async fn embed(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+        let request_body = EmbeddingRequest {
+            model: &self.request_model,
+            input: texts,
+        };
+        self.resilience
+            .run(|| async {
+                let mut request = self.client.post(&self.url).json(&request_body);
+                if let Some(api_key) = &self.api_key {
+                    request = request.bearer_auth(api_key);
+                }
+                let response = request.send().await?;
+                let status = response.status();
+                let retry_after = retry_after(response.headers());
+                let body = response.bytes().await?;
+                parse_response(status, retry_after, &body, texts.len())
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl Embedder for OpenAiCompatibleEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn is_available(&self) -> bool {
+        !self.resilience.is_open()
+    }
+
+    async fn generate_embedding<'em>(&self, text: &'em str) -> Result<Vec<f32>> {
+        let mut embeddings = self.embed(&[text]).await?;
+        Ok(embeddings.swap_remove(0))
+    }
+
+    async fn generate_embeddings_bulk<'em>(&self, texts: Vec<&'em str>) -> Result<Vec<Vec<f32>>> {
+        Ok(self.embed(&texts).await?)
+    }
+}
+
+fn parse_response(
+    status: StatusCode,
+    retry_after: Option<Duration>,
+    body: &[u8],
+    expected: usize,
+) -> Result<Vec<Vec<f32>>, EmbeddingError> {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(EmbeddingError::RateLimited { retry_after });
+    }
+    if !status.is_success() {
+        let errors = serde_json::from_slice::<ErrorResponse>(body)
+            .map(|response| {
+                vec![ProviderError {
+                    code: status.as_u16().into(),
+                    message: response.error.message,
+                }]
+            })
+            .unwrap_or_default();
+        return Err(EmbeddingError::Provider {
+            status: status.as_u16(),
+            errors,
+            retry_after,
+        });
+    }
+
+    let mut response = serde_json::from_slice::<EmbeddingResponse>(body)
+        .map_err(|e| EmbeddingError::InvalidResponse(e.to_string()))?;
+    if response.data.len() != expected {
+        return Err(EmbeddingError::InvalidResponse(format!(
+            "{} embeddings for {} texts",
+            response.data.len(),
+            expected
+        )));
+    }
+    response.data.sort_by_key(|data| data.index);
+    Ok(response
+        .data
+        .into_iter()
+        .map(|data| data.embedding)
+        .collect())
+}
+
+#[derive(Serialize)]
+struct EmbeddingRequest<'em> {
+    model: &'em str,
+    input: &'em [&'em str],
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: usize,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+#[derive(Deserialize)]
+struct ErrorBody {
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embeddings_are_returned_in_input_order() {
+        let body = br#"{"object":"list","data":[{"object":"embedding","index":1,"embedding":[2.0]},{"object":"embedding","index":0,"embedding":[1.0]}],"model":"bge-m3"}"#;
+        let data = parse_response(StatusCode::OK, None, body, 2).unwrap();
+        assert_eq!(data, vec![vec![1.0], vec![2.0]]);
+    }
+
+    #[test]
+    fn test_error_body_is_typed() {
+        let body = br#"{"error":{"message":"model not found","type":"invalid_request_error","code":null}}"#;
+        let error = parse_response(StatusCode::NOT_FOUND, None, body, 1).unwrap_err();
+        match error {
+            EmbeddingError::Provider { status, errors, .. } => {
+                assert_eq!(status, 404);
+                assert_eq!(errors[0].message, "model not found");
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{batching::env_usize, EmbeddingError};
+use reqwest::{header::HeaderMap, Client};
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::warn;
+
+/// Timeouts, retries and circuit breaking applied to embedding backend requests.
+#[derive(Clone, Debug)]
+pub struct ResilienceConfig {
+    /// Whole-request timeout, including reading the response body.
+    pub request_timeout: Duration,
+    pub connect_timeout: Duration,
+    /// Retries after the first attempt for rate limits, 5xx responses and transport errors.
+    pub max_retries: usize,
+    /// First backoff delay; it doubles with every retry up to `max_delay`.
+    pub base_delay: Duration,
+    /// Longest wait between attempts. A `Retry-After` beyond it fails the request instead.
+    pub max_delay: Duration,
+    /// Consecutive failed attempts that open the circuit.
+    pub failure_threshold: usize,
+    /// How long an open circuit fails requests before letting a probe through.
+    pub open_duration: Duration,
+}
+
+impl Default for ResilienceConfig {
+    fn default() -> Self {
+        Self {
+            request_timeout: Duration::from_secs(30),
+            connect_timeout: Duration::from_secs(5),
+            max_retries: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            failure_threshold: 5,
+            open_duration: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ResilienceConfig {
+    /// Reads `EMBEDDING_REQUEST_TIMEOUT_SECS`, `EMBEDDING_MAX_RETRIES`,
+    /// `EMBEDDING_CIRCUIT_FAILURE_THRESHOLD` and `EMBEDDING_CIRCUIT_OPEN_SECS`, keeping
+    /// the defaults for unset variables.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            request_timeout: Duration::from_secs(env_usize(
+                "EMBEDDING_REQUEST_TIMEOUT_SECS",
+                default.request_timeout.as_secs() as usize,
+            ) as u64),
+            max_retries: std::env::var("EMBEDDING_MAX_RETRIES")
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default.max_retries),
+            failure_threshold: env_usize(
+                "EMBEDDING_CIRCUIT_FAILURE_THRESHOLD",
+                default.failure_threshold,
+            ),
+            open_duration: Duration::from_secs(env_usize(
+                "EMBEDDING_CIRCUIT_OPEN_SECS",
+                default.open_duration.as_secs() as usize,
+            ) as u64),
+            ..default
+        }
+    }
+
+    /// Exponential backoff with full jitter for the retry after `attempt`.
+    fn backoff(&self, attempt: usize) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay);
+        ceiling.mul_f64(fastrand::f64())
+    }
+}
+
+/// An HTTP client with the request and connect timeouts of `config`.
+pub(crate) fn http_client(config: &ResilienceConfig) -> Client {
+    Client::builder()
+        .timeout(config.request_timeout)
+        .connect_timeout(config.connect_timeout)
+        .build()
+        .expect("Failed to build the embedding HTTP client")
+}
+
+/// Reads a `Retry-After` header given in seconds or as an HTTP date. Dates in the
+/// past mean retry now.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+enum Circuit {
+    Closed {
+        failures: usize,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single probe is in flight; it is abandoned if it has not finished by `until`.
+    HalfOpen {
+        until: Instant,
+    },
+}
+
+/// Retries transient failures and stops calling a backend that keeps failing.
+pub struct Resilience {
+    config: ResilienceConfig,
+    circuit: Mutex<Circuit>,
+}
+
+impl Resilience {
+    pub fn new(config: ResilienceConfig) -> Self {
+        Self {
+            config,
+            circuit: Mutex::new(Circuit::Closed { failures: 0 }),
+        }
+    }
+
+    /// Runs `attempt` until it succeeds, fails with a permanent error or runs out of
+    /// retries. Waits for the backend's `Retry-After` when it sent one.
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> Result<T, EmbeddingError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, EmbeddingError>>,
+    {
+        let mut retries = 0;
+        loop {
+            self.acquire()?;
+            let error = match attempt().await {
+                Ok(value) => {
+                    self.record(true);
+                    return Ok(value);
+                }
+                Err(error) => error,
+            };
+            // Rejected input says nothing about the backend's health.
+            self.record(!error.is_retryable());
+
+            if !error.is_retryable() || retries >= self.config.max_retries {
+                return Err(error);
+            }
+            let delay = match error.retry_after() {
+                Some(retry_after) if retry_after > self.config.max_delay => return Err(error),
+                Some(retry_after) => retry_after,
+                None => self.config.backoff(retries),
+            };
+            warn!("Retrying embedding request in {:?}: {}", delay, error);
+            tokio::time::sleep(delay).await;
+            retries += 1;
+        }
+    }
+
+    /// Whether calls are currently refused because the backend kept failing.
+    pub fn is_open(&self) -> bool {
+        match *self.circuit.lock().unwrap() {
+            Circuit::Closed { .. } => false,
+            Circuit::Open { until } | Circuit::HalfOpen { until } => Instant::now() < until,
+        }
+    }
+
+    fn acquire(&self) -> Result<(), EmbeddingError> {
+        let mut circuit = self.circuit.lock().unwrap();
+        let now = Instant::now();
+        match *circuit {
+            Circuit::Closed { .. } => Ok(()),
+            Circuit::Open { until } | Circuit::HalfOpen { until } if now < until => {
+                Err(EmbeddingError::CircuitOpen {
+                    retry_after: until - now,
+                })
+            }
+            Circuit::Open { .. } | Circuit::HalfOpen { .. } => {
+                *circuit = Circuit::HalfOpen {
+                    until: now + self.config.open_duration,
+                };
+                Ok(())
+            }
+        }
+    }
+
+    fn record(&self, healthy: bool) {
+        let mut circuit = self.circuit.lock().unwrap();
+        *circuit = match (&*circuit, healthy) {
+            (_, true) => Circuit::Closed { failures: 0 },
+            (Circuit::Closed { failures }, false)
+                if failures + 1 < self.config.failure_threshold =>
+            {
+                Circuit::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (_, false) => {
+                warn!(
+                    "Embedding backend circuit opened for {:?}",
+                    self.config.open_duration
+                );
+                Circuit::Open {
+                    until: Instant::now() + self.config.open_duration,
+                }
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config() -> ResilienceConfig {
+        ResilienceConfig {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(50),
+            failure_threshold: 3,
+            open_duration: Duration::from_secs(60),
+            ..ResilienceConfig::default()
+        }
+    }
+
+    fn server_error() -> EmbeddingError {
+        EmbeddingError::Provider {
+            status: 503,
+            errors: Vec::new(),
+            retry_after: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let resilience = Resilience::new(config());
+        let calls = AtomicUsize::new(0);
+
+        let result = resilience
+            .run(|| async {
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => Err(EmbeddingError::RateLimited {
+                        retry_after: Some(Duration::from_millis(1)),
+                    }),
+                    1 => Err(server_error()),
+                    _ => Ok("embedded"),
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), "embedded");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_failures_are_not_retried() {
+        let resilience = Resilience::new(config());
+        let calls = AtomicUsize::new(0);
+
+        let result: Result<(), _> = resilience
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(EmbeddingError::Provider {
+                    status: 400,
+                    errors: Vec::new(),
+                    retry_after: None,
+                })
+            })
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(EmbeddingError::Provider { status: 400, .. })
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_long_retry_after_fails_immediately() {
+        let resilience = Resilience::new(config());
+        let calls = AtomicUsize::new(0);
+
+        let result: Result<(), _> = resilience
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(EmbeddingError::RateLimited {
+                    retry_after: Some(Duration::from_secs(60)),
+                })
+            })
+            .await;
+
+        assert_eq!(
+            result.unwrap_err().retry_after(),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_after_repeated_failures() {
+        let resilience = Resilience::new(config());
+        let calls = AtomicUsize::new(0);
+        let failing = || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(server_error())
+        };
+
+        // Three failed attempts open the circuit.
+        assert!(matches!(
+            resilience.run(failing).await,
+            Err(EmbeddingError::Provider { .. })
+        ));
+        assert!(resilience.is_open());
+        assert!(matches!(
+            resilience.run(failing).await,
+            Err(EmbeddingError::CircuitOpen { .. })
+        ));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tonic::{body::BoxBody, server::NamedService, Request, Status};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::debug;
+use umem_auth::{Principal, Scope, TokenValidator};
+
+const AUTHORIZATION_HEADER: &str = "authorization";
+
+/// Authenticates every call with the bearer token or API key in the `authorization`
+/// metadata, the same credentials the MCP server accepts.
+///
+/// tonic interceptors are synchronous and token validation may have to fetch keys, so
+/// this is a tower layer rather than a `tonic::service::Interceptor`.
+#[derive(Clone)]
+pub struct AuthLayer {
+    validator: Arc<TokenValidator>,
+}
+
+impl AuthLayer {
+    pub fn new(validator: Arc<TokenValidator>) -> Self {
+        Self { validator }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            validator: Arc::clone(&self.validator),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    validator: Arc<TokenValidator>,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for AuthService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<ReqBody>) -> Self::Future {
+        // The ready service is the one that must handle the call.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let validator = Arc::clone(&self.validator);
+
+        Box::pin(async move {
+            match authenticate(&validator, request.headers()).await {
+                Ok(principal) => {
+                    request.extensions_mut().insert(principal);
+                    inner.call(request).await
+                }
+                Err(status) => Ok(status.into_http()),
+            }
+        })
+    }
+}
+
+impl<S: NamedService> NamedService for AuthService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+async fn authenticate(
+    validator: &TokenValidator,
+    headers: &http::HeaderMap,
+) -> Result<Principal, Status> {
+    let header = headers
+        .get(AUTHORIZATION_HEADER)
+        .ok_or_else(|| Status::unauthenticated("Missing authorization metadata."))?;
+
+    let token = header
+        .to_str()
+        .ok()
+        .and_then(|s| s.strip_prefix("Bearer "))
+        .ok_or_else(|| Status::unauthenticated("Malformed authorization header."))?;
+
+    let principal = umem_auth::authenticate(validator, token)
+        .await
+        .map_err(|e| {
+            debug!("Rejected gRPC credentials: {}", e);
+            Status::unauthenticated("Invalid credentials.")
+        })?;
+
+    debug!("Authenticated gRPC request for {}", principal.user_id);
+    Ok(principal)
+}
+
+/// Returns the caller of `request` if it holds `scope`.
+#[allow(clippy::result_large_err)]
+pub fn authorize<T>(request: &Request<T>, scope: Scope) -> Result<Principal, Status> {
+    let principal = request
+        .extensions()
+        .get::<Principal>()
+        .ok_or_else(|| Status::unauthenticated("Request is not authenticated."))?;
+
+    if !principal.allows(scope) {
+        return Err(Status::permission_denied(format!(
+            "Missing required scope {}",
+            scope
+        )));
+    }
+
+    Ok(principal.clone())
+}
+
+/// Binds `user_id` to the caller's tenant.
+///
+/// An empty `user_id` defaults to the caller; another tenant may only be named by
+/// principals holding the `admin` scope.
+#[allow(clippy::result_large_err)]
+pub fn bind_tenant(principal: &Principal, user_id: &mut String) -> Result<(), Status> {
+    if user_id.is_empty() {
+        *user_id = principal.user_id.clone();
+    } else if *user_id != principal.user_id && !principal.allows(Scope::Admin) {
+        return Err(Status::permission_denied(
+            "user_id does not match the authenticated user.",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+    use umem_auth::Scopes;
+
+    fn principal(scopes: &[&str]) -> Principal {
+        Principal {
+            user_id: "user-1".to_string(),
+            scopes: Scopes::from_raw(scopes.iter().copied()),
+        }
+    }
+
+    #[test]
+    fn test_authorize_requires_principal_and_scope() {
+        let request = Request::new(());
+        let status = authorize(&request, Scope::MemoriesRead).unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let mut request = Request::new(());
+        request
+            .extensions_mut()
+            .insert(principal(&["memories:read"]));
+        assert!(authorize(&request, Scope::MemoriesRead).is_ok());
+        let status = authorize(&request, Scope::MemoriesDelete).unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+    }
+
+    #[test]
+    fn test_bind_tenant_defaults_to_caller() {
+        let mut user_id = String::new();
+        bind_tenant(&principal(&["memories:read"]), &mut user_id).unwrap();
+        assert_eq!(user_id, "user-1");
+    }
+
+    #[test]
+    fn test_bind_tenant_rejects_other_tenants() {
+        let mut user_id = "user-2".to_string();
+        let status = bind_tenant(&principal(&["memories:read"]), &mut user_id).unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        bind_tenant(&principal(&["admin"]), &mut user_id).unwrap();
+        assert_eq!(user_id, "user-2");
+    }
+}
@@ -0,0 +1,165 @@
+use prost::{Message, Name};
+use std::collections::HashMap;
+use tonic::{Code, Status};
+use tracing::error;
+use umem_controller::ControllerError;
+use umem_proto_generated::rpc;
+
+const ERROR_DOMAIN: &str = "umem.evenscribe.com";
+
+/// Maps a controller failure to a gRPC status carrying `google.rpc` error details, so
+/// clients can tell invalid input apart from failures worth retrying.
+pub fn to_status(error: ControllerError) -> Status {
+    let status = to_rpc_status(error);
+    let code = Code::from_i32(status.code);
+    Status::with_details(code, status.message.clone(), status.encode_to_vec().into())
+}
+
+/// The `google.rpc.Status` form of `error`, also used for per-item failures.
+pub fn to_rpc_status(error: ControllerError) -> rpc::Status {
+    let message = error.to_string();
+    let mut details = Vec::new();
+
+    let code = match &error {
+        ControllerError::Validation { field, description } => {
+            push(
+                &mut details,
+                &rpc::BadRequest {
+                    field_violations: vec![rpc::bad_request::FieldViolation {
+                        field: field.to_string(),
+                        description: description.clone(),
+                    }],
+                },
+            );
+            Code::InvalidArgument
+        }
+        ControllerError::NotFound {
+            resource_type,
+            resource_name,
+        } => {
+            push(
+                &mut details,
+                &rpc::ResourceInfo {
+                    resource_type: resource_type.to_string(),
+                    resource_name: resource_name.clone(),
+                    ..Default::default()
+                },
+            );
+            Code::NotFound
+        }
+        ControllerError::EmbeddingUnavailable(e) => {
+            error!("Embedding backend unavailable: {:#}", e);
+            push(&mut details, &error_info("EMBEDDING_BACKEND_UNAVAILABLE"));
+            Code::Unavailable
+        }
+        ControllerError::VectorStoreUnavailable(e) => {
+            error!("Vector store unavailable: {:#}", e);
+            push(&mut details, &error_info("VECTOR_STORE_UNAVAILABLE"));
+            Code::Unavailable
+        }
+        ControllerError::RateLimited { retry_after, .. } => {
+            push(&mut details, &error_info("RATE_LIMITED"));
+            if let Some(retry_after) = retry_after {
+                push(
+                    &mut details,
+                    &rpc::RetryInfo {
+                        retry_delay: prost_types::Duration::try_from(*retry_after).ok(),
+                    },
+                );
+            }
+            Code::ResourceExhausted
+        }
+        ControllerError::Internal(e) => {
+            error!("Internal error: {:?}", e);
+            return rpc::Status {
+                code: Code::Internal as i32,
+                message: "Internal error.".to_string(),
+                details,
+            };
+        }
+    };
+
+    rpc::Status {
+        code: code as i32,
+        message,
+        details,
+    }
+}
+
+fn error_info(reason: &str) -> rpc::ErrorInfo {
+    rpc::ErrorInfo {
+        reason: reason.to_string(),
+        domain: ERROR_DOMAIN.to_string(),
+        metadata: HashMap::new(),
+    }
+}
+
+fn push<M: Name>(details: &mut Vec<prost_types::Any>, message: &M) {
+    details.push(prost_types::Any::from_msg(message).expect("error detail encoding failed"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn decode_details(status: &Status) -> rpc::Status {
+        rpc::Status::decode(status.details()).unwrap()
+    }
+
+    #[test]
+    fn test_validation_maps_to_invalid_argument() {
+        let status = to_status(ControllerError::validation("content", "cannot be empty"));
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let details = decode_details(&status);
+        let bad_request = details.details[0].to_msg::<rpc::BadRequest>().unwrap();
+        assert_eq!(bad_request.field_violations[0].field, "content");
+    }
+
+    #[test]
+    fn test_not_found_carries_resource_info() {
+        let status = to_status(ControllerError::memory_not_found("m-1"));
+        assert_eq!(status.code(), Code::NotFound);
+
+        let details = decode_details(&status);
+        let resource_info = details.details[0].to_msg::<rpc::ResourceInfo>().unwrap();
+        assert_eq!(resource_info.resource_name, "m-1");
+    }
+
+    #[test]
+    fn test_rate_limited_carries_retry_info() {
+        let status = to_status(ControllerError::RateLimited {
+            backend: "vector store",
+            retry_after: Some(Duration::from_secs(2)),
+        });
+        assert_eq!(status.code(), Code::ResourceExhausted);
+
+        let details = decode_details(&status);
+        assert_eq!(
+            details.details[0]
+                .to_msg::<rpc::ErrorInfo>()
+                .unwrap()
+                .reason,
+            "RATE_LIMITED"
+        );
+        let retry_info = details.details[1].to_msg::<rpc::RetryInfo>().unwrap();
+        assert_eq!(retry_info.retry_delay.unwrap().seconds, 2);
+    }
+
+    #[test]
+    fn test_backend_failures_are_unavailable() {
+        let status = to_status(ControllerError::EmbeddingUnavailable(anyhow::anyhow!(
+            "error sending request for url (https://api.cloudflare.com/client/v4/accounts/secret-account/ai/run)"
+        )));
+        assert_eq!(status.code(), Code::Unavailable);
+        assert_eq!(status.message(), "Embedding backend unavailable");
+    }
+
+    #[test]
+    fn test_internal_errors_are_not_leaked() {
+        let status = to_status(ControllerError::Internal(anyhow::anyhow!("secret detail")));
+        assert_eq!(status.code(), Code::Internal);
+        assert!(!status.message().contains("secret"));
+    }
+}
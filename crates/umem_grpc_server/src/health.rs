@@ -0,0 +1,107 @@
+use crate::{auth::AuthService, qdrant::QdrantServiceImpl};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tonic_health::{server::HealthReporter, ServingStatus};
+use tracing::{debug, info, warn};
+use umem_controller::{MemoryController, Result};
+use umem_proto_generated::generated::memory_service_server::MemoryServiceServer;
+
+type MemoryService = AuthService<MemoryServiceServer<QdrantServiceImpl>>;
+
+const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Interval between backend probes, from `HEALTH_CHECK_INTERVAL_SECS`.
+pub fn interval_from_env() -> Duration {
+    std::env::var("HEALTH_CHECK_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL)
+}
+
+/// Probes Qdrant and the embedding backend every `interval` and publishes the result
+/// for the memory service and the server as a whole (`""`).
+///
+/// Both are reported `NOT_SERVING` once `shutdown` fires, so load balancers stop
+/// routing new calls while in-flight ones drain.
+pub async fn monitor(
+    mut reporter: HealthReporter,
+    interval: Duration,
+    shutdown: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    let mut current = None;
+
+    loop {
+        tokio::select! {
+            biased;
+            _ = shutdown.cancelled() => break,
+            _ = ticker.tick() => {}
+        }
+
+        let (vector_store, embedder) = tokio::join!(
+            MemoryController::check_vector_store(),
+            MemoryController::check_embedder()
+        );
+        if let Err(e) = &vector_store {
+            warn!("Vector store health check failed: {}", e);
+        }
+        if let Err(e) = &embedder {
+            warn!("Embedder health check failed: {}", e);
+        }
+
+        let status = serving_status(&vector_store, &embedder);
+        if current != Some(status) {
+            info!("Memory service health changed to {:?}", status);
+            current = Some(status);
+        }
+        report(&mut reporter, status).await;
+        debug!(
+            "Embedding cache: {}",
+            MemoryController::embedding_cache_stats()
+        );
+    }
+
+    report(&mut reporter, ServingStatus::NotServing).await;
+}
+
+async fn report(reporter: &mut HealthReporter, status: ServingStatus) {
+    reporter
+        .set_service_status(<MemoryService as tonic::server::NamedService>::NAME, status)
+        .await;
+    reporter.set_service_status("", status).await;
+}
+
+fn serving_status(vector_store: &Result<()>, embedder: &Result<()>) -> ServingStatus {
+    if vector_store.is_ok() && embedder.is_ok() {
+        ServingStatus::Serving
+    } else {
+        ServingStatus::NotServing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use umem_controller::ControllerError;
+
+    fn unavailable() -> Result<()> {
+        Err(ControllerError::VectorStoreUnavailable(anyhow::anyhow!(
+            "down"
+        )))
+    }
+
+    #[test]
+    fn test_serving_only_when_all_backends_answer() {
+        assert_eq!(serving_status(&Ok(()), &Ok(())), ServingStatus::Serving);
+        assert_eq!(
+            serving_status(&unavailable(), &Ok(())),
+            ServingStatus::NotServing
+        );
+        assert_eq!(
+            serving_status(&Ok(()), &unavailable()),
+            ServingStatus::NotServing
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::{future::Future, time::Duration};
+use tokio::sync::mpsc;
+use tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+use tracing::{debug, warn};
+use umem_proto_generated::generated;
+
+/// Memories embedded and upserted together.
+pub const BATCH_SIZE: usize = 64;
+/// A partial batch is flushed once the client has been idle this long.
+pub const FLUSH_INTERVAL: Duration = Duration::from_millis(250);
+/// Results buffered for a client that reads them slower than they are produced.
+pub const RESULT_BUFFER: usize = BATCH_SIZE * 2;
+
+pub type IngestResult = Result<generated::AddMemoryResult, Status>;
+
+/// Reads memories from `inbound` in batches of up to `batch_size`, hands each batch to
+/// `store` and sends one result per memory to `results`, in the order the memories
+/// arrived. A partial batch is flushed when the client pauses for `flush_interval`.
+///
+/// The next batch is only read once the previous one is stored and its results are
+/// accepted by `results`, so a slow backend, or a client that stops reading results,
+/// throttles the upload through HTTP/2 flow control.
+pub async fn ingest<S, F, Fut>(
+    mut inbound: S,
+    results: mpsc::Sender<IngestResult>,
+    batch_size: usize,
+    flush_interval: Duration,
+    mut store: F,
+) where
+    S: Stream<Item = Result<generated::Memory, Status>> + Unpin,
+    F: FnMut(Vec<generated::Memory>) -> Fut,
+    Fut: Future<Output = Vec<generated::AddMemoryResult>>,
+{
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut ingested = 0;
+
+    loop {
+        let next = if batch.is_empty() {
+            Some(inbound.next().await)
+        } else {
+            tokio::time::timeout(flush_interval, inbound.next())
+                .await
+                .ok()
+        };
+
+        let flush = match next {
+            // The client paused with a partial batch pending.
+            None => true,
+            Some(Some(Ok(memory))) => {
+                batch.push(memory);
+                batch.len() >= batch_size
+            }
+            Some(Some(Err(status))) => {
+                warn!("Ingest stream failed: {}", status);
+                break;
+            }
+            Some(None) => break,
+        };
+
+        if flush {
+            ingested += batch.len();
+            let memories = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+            if !send_all(&results, store(memories).await).await {
+                return;
+            }
+        }
+    }
+
+    if !batch.is_empty() {
+        ingested += batch.len();
+        send_all(&results, store(batch).await).await;
+    }
+    debug!("Ingest stream finished after {} memories", ingested);
+}
+
+/// Returns `false` once the client has gone away.
+async fn send_all(
+    results: &mpsc::Sender<IngestResult>,
+    batch_results: Vec<generated::AddMemoryResult>,
+) -> bool {
+    for result in batch_results {
+        if results.send(Ok(result)).await.is_err() {
+            debug!("Ingest client disconnected");
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio_stream::wrappers::ReceiverStream;
+
+    fn memory(content: &str) -> generated::Memory {
+        generated::Memory {
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn inbound(count: usize) -> impl Stream<Item = Result<generated::Memory, Status>> + Unpin {
+        tokio_stream::iter((0..count).map(|i| memory(&i.to_string())).map(Ok))
+    }
+
+    fn stored(memory: generated::Memory) -> generated::AddMemoryResult {
+        generated::AddMemoryResult {
+            result: Some(generated::add_memory_result::Result::Memory(memory)),
+        }
+    }
+
+    fn content(result: IngestResult) -> String {
+        match result.unwrap().result {
+            Some(generated::add_memory_result::Result::Memory(memory)) => memory.content,
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_preserves_order() {
+        let inbound = inbound(5);
+        let (tx, rx) = mpsc::channel(16);
+        let batches = Arc::new(Mutex::new(Vec::new()));
+
+        let recorded = Arc::clone(&batches);
+        ingest(inbound, tx, 2, FLUSH_INTERVAL, move |memories| {
+            recorded.lock().unwrap().push(memories.len());
+            async move { memories.into_iter().map(stored).collect() }
+        })
+        .await;
+
+        assert_eq!(*batches.lock().unwrap(), vec![2, 2, 1]);
+        let contents = ReceiverStream::new(rx)
+            .map(content)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(contents, vec!["0", "1", "2", "3", "4"]);
+    }
+
+    #[tokio::test]
+    async fn test_flushes_partial_batch_when_client_pauses() {
+        let (inbound_tx, inbound_rx) = mpsc::channel(4);
+        let (tx, mut rx) = mpsc::channel(4);
+
+        tokio::spawn(ingest(
+            ReceiverStream::new(inbound_rx),
+            tx,
+            10,
+            Duration::from_millis(10),
+            |memories: Vec<generated::Memory>| async move { memories.into_iter().map(stored).collect() },
+        ));
+
+        inbound_tx.send(Ok(memory("first"))).await.unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("partial batch was not flushed")
+            .unwrap();
+        assert_eq!(content(result), "first");
+    }
+
+    #[tokio::test]
+    async fn test_stops_when_client_disconnects() {
+        let inbound = inbound(10);
+        let (tx, rx) = mpsc::channel(1);
+        drop(rx);
+        let calls = Arc::new(Mutex::new(0));
+
+        let counted = Arc::clone(&calls);
+        ingest(inbound, tx, 2, FLUSH_INTERVAL, move |memories| {
+            *counted.lock().unwrap() += 1;
+            async move { memories.into_iter().map(stored).collect() }
+        })
+        .await;
+
+        assert_eq!(*calls.lock().unwrap(), 1);
+    }
+}
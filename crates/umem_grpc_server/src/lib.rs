@@ -1,22 +1,63 @@
 use anyhow::Result;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use tonic::transport::Server;
+use tower_layer::Layer;
 use tracing::info;
+use umem_auth::TokenValidator;
+use umem_controller::MAX_DOCUMENT_BYTES;
 use umem_proto_generated::generated;
 
+mod auth;
+mod error;
+mod health;
+mod ingest;
 mod qdrant;
 
 pub struct MemoryServiceGrpc;
 
 impl MemoryServiceGrpc {
-    pub async fn run_server(addr: &str) -> Result<()> {
+    /// Serves the memory service, health checks and reflection until `shutdown` fires,
+    /// then stops accepting connections and waits for in-flight calls to finish.
+    pub async fn run_server(
+        addr: &str,
+        validator: Arc<TokenValidator>,
+        shutdown: CancellationToken,
+    ) -> Result<()> {
         let addr = addr.parse()?;
+
+        let (health_reporter, health_service) = tonic_health::server::health_reporter();
+        tokio::spawn(health::monitor(
+            health_reporter,
+            health::interval_from_env(),
+            shutdown.clone(),
+        ));
+
+        // grpcurl and most tools still speak v1alpha, newer clients use v1.
+        let reflection = || {
+            tonic_reflection::server::Builder::configure()
+                .register_encoded_file_descriptor_set(generated::FILE_DESCRIPTOR_SET)
+        };
+        let reflection_v1 = reflection().build_v1()?;
+        let reflection_v1alpha = reflection().build_v1alpha()?;
+
         info!("Memory gRPC Server listening on {}", addr);
         Server::builder()
-            .add_service(generated::memory_service_server::MemoryServiceServer::new(
-                qdrant::QdrantServiceImpl,
-            ))
-            .serve(addr)
+            .add_service(health_service)
+            .add_service(reflection_v1)
+            .add_service(reflection_v1alpha)
+            .add_service(
+                auth::AuthLayer::new(validator).layer(
+                    generated::memory_service_server::MemoryServiceServer::new(
+                        qdrant::QdrantServiceImpl,
+                    )
+                    // Room for the largest document plus the rest of the request.
+                    .max_decoding_message_size(MAX_DOCUMENT_BYTES + 64 * 1024),
+                ),
+            )
+            .serve_with_shutdown(addr, shutdown.cancelled_owned())
             .await?;
+        info!("Memory gRPC Server stopped");
         Ok(())
     }
 }
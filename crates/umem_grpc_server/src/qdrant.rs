@@ -1,6 +1,10 @@
-use tonic::{Request, Response, Status};
+use crate::{auth, error, ingest};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status, Streaming};
+use umem_auth::{Principal, Scope};
 use umem_controller::MemoryController;
-use umem_proto_generated::generated;
+use umem_proto_generated::{generated, rpc};
 
 #[derive(Debug, Default)]
 pub struct QdrantServiceImpl;
@@ -10,46 +14,103 @@ impl generated::memory_service_server::MemoryService for QdrantServiceImpl {
     async fn add_memory(
         &self,
         request: Request<generated::Memory>,
-    ) -> Result<Response<()>, Status> {
-        let memory = request.into_inner();
-
-        if memory.content.is_empty() {
-            return Err(Status::internal("Memory content is empty."));
-        }
+    ) -> Result<Response<generated::Memory>, Status> {
+        let principal = auth::authorize(&request, Scope::MemoriesWrite)?;
+        let mut memory = request.into_inner();
+        auth::bind_tenant(&principal, &mut memory.user_id)?;
 
-        MemoryController::add_memory(memory)
+        let memory = MemoryController::add_memory(memory)
             .await
-            .map_err(|e| Status::internal(format!("Failed to add memory: {}", e)))?;
+            .map_err(error::to_status)?;
 
-        Ok(Response::new(()))
+        Ok(Response::new(memory))
     }
 
     async fn add_memory_bulk(
         &self,
         request: Request<generated::MemoryBulk>,
-    ) -> Result<Response<()>, Status> {
-        let memory_bulk = request.into_inner();
-
-        if memory_bulk.memories.is_empty() {
-            return Err(Status::internal("Memories is empty."));
+    ) -> Result<Response<generated::AddMemoryBulkResponse>, Status> {
+        let principal = auth::authorize(&request, Scope::MemoriesWrite)?;
+        let mut memory_bulk = request.into_inner();
+        for memory in memory_bulk.memories.iter_mut() {
+            auth::bind_tenant(&principal, &mut memory.user_id)?;
         }
 
-        MemoryController::add_memory_bulk(memory_bulk)
+        let results = MemoryController::add_memory_bulk(memory_bulk)
             .await
-            .map_err(|e| Status::internal(format!("Failed to add memory bulk: {}", e)))?;
+            .map_err(error::to_status)?
+            .into_iter()
+            .map(|result| add_memory_result(result.map_err(error::to_rpc_status)))
+            .collect();
 
-        Ok(Response::new(()))
+        Ok(Response::new(generated::AddMemoryBulkResponse { results }))
+    }
+
+    type IngestMemoriesStream = ReceiverStream<ingest::IngestResult>;
+
+    async fn ingest_memories(
+        &self,
+        request: Request<Streaming<generated::Memory>>,
+    ) -> Result<Response<Self::IngestMemoriesStream>, Status> {
+        let principal = auth::authorize(&request, Scope::MemoriesWrite)?;
+        let inbound = request.into_inner();
+        let (results, receiver) = mpsc::channel(ingest::RESULT_BUFFER);
+
+        tokio::spawn(ingest::ingest(
+            inbound,
+            results,
+            ingest::BATCH_SIZE,
+            ingest::FLUSH_INTERVAL,
+            move |memories| {
+                let principal = principal.clone();
+                async move { store_batch(&principal, memories).await }
+            },
+        ));
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn ingest_document(
+        &self,
+        request: Request<generated::IngestDocumentParameters>,
+    ) -> Result<Response<generated::IngestDocumentResponse>, Status> {
+        let principal = auth::authorize(&request, Scope::MemoriesWrite)?;
+        let mut ingest_document_parameters = request.into_inner();
+        auth::bind_tenant(&principal, &mut ingest_document_parameters.user_id)?;
+
+        let response = MemoryController::ingest_document(ingest_document_parameters)
+            .await
+            .map_err(error::to_status)?;
+
+        Ok(Response::new(response))
+    }
+
+    async fn ingest_url(
+        &self,
+        request: Request<generated::IngestUrlParameters>,
+    ) -> Result<Response<generated::IngestDocumentResponse>, Status> {
+        let principal = auth::authorize(&request, Scope::MemoriesWrite)?;
+        let mut ingest_url_parameters = request.into_inner();
+        auth::bind_tenant(&principal, &mut ingest_url_parameters.user_id)?;
+
+        let response = MemoryController::ingest_url(ingest_url_parameters)
+            .await
+            .map_err(error::to_status)?;
+
+        Ok(Response::new(response))
     }
 
     async fn update_memory(
         &self,
         request: Request<generated::UpdateMemoryParameters>,
     ) -> Result<Response<()>, Status> {
-        let update_memory_parameters = request.into_inner();
+        let principal = auth::authorize(&request, Scope::MemoriesWrite)?;
+        let mut update_memory_parameters = request.into_inner();
+        auth::bind_tenant(&principal, &mut update_memory_parameters.user_id)?;
 
         MemoryController::update_memory(update_memory_parameters)
             .await
-            .map_err(|e| Status::internal(format!("Failed to update memory: {}", e)))?;
+            .map_err(error::to_status)?;
 
         Ok(Response::new(()))
     }
@@ -58,11 +119,28 @@ impl generated::memory_service_server::MemoryService for QdrantServiceImpl {
         &self,
         request: Request<generated::DeleteMemoryParameters>,
     ) -> Result<Response<()>, Status> {
-        let delete_memory_parameters = request.into_inner();
+        let principal = auth::authorize(&request, Scope::MemoriesDelete)?;
+        let mut delete_memory_parameters = request.into_inner();
+        auth::bind_tenant(&principal, &mut delete_memory_parameters.user_id)?;
 
         MemoryController::delete_memory(delete_memory_parameters)
             .await
-            .map_err(|e| Status::internal(format!("Failed to delete memory: {}", e)))?;
+            .map_err(error::to_status)?;
+
+        Ok(Response::new(()))
+    }
+
+    async fn delete_document(
+        &self,
+        request: Request<generated::DeleteDocumentParameters>,
+    ) -> Result<Response<()>, Status> {
+        let principal = auth::authorize(&request, Scope::MemoriesDelete)?;
+        let mut delete_document_parameters = request.into_inner();
+        auth::bind_tenant(&principal, &mut delete_document_parameters.user_id)?;
+
+        MemoryController::delete_document(delete_document_parameters)
+            .await
+            .map_err(error::to_status)?;
 
         Ok(Response::new(()))
     }
@@ -70,30 +148,117 @@ impl generated::memory_service_server::MemoryService for QdrantServiceImpl {
     async fn get_memories_by_query(
         &self,
         request: Request<generated::GetMemoriesByQueryParameters>,
-    ) -> Result<Response<generated::MemoryBulk>, Status> {
-        let get_memories_by_query_parameters = request.into_inner();
+    ) -> Result<Response<generated::MemorySearchResponse>, Status> {
+        let principal = auth::authorize(&request, Scope::MemoriesRead)?;
+        let mut get_memories_by_query_parameters = request.into_inner();
+        auth::bind_tenant(&principal, &mut get_memories_by_query_parameters.user_id)?;
 
-        let generated::MemoryBulk { memories } =
-            MemoryController::get_memories_by_query(get_memories_by_query_parameters)
-                .await
-                .map_err(|e| Status::internal(format!("Failed to get memories by query: {}", e)))?;
+        let response = MemoryController::get_memories_by_query(get_memories_by_query_parameters)
+            .await
+            .map_err(error::to_status)?;
 
-        Ok(Response::new(generated::MemoryBulk { memories }))
+        Ok(Response::new(response))
     }
 
     async fn get_memories_by_user_id(
         &self,
         request: Request<generated::GetMemoriesByUserIdParameters>,
     ) -> Result<Response<generated::MemoryBulk>, Status> {
-        let get_memories_by_user_id = request.into_inner();
+        let principal = auth::authorize(&request, Scope::MemoriesRead)?;
+        let mut get_memories_by_user_id = request.into_inner();
+        auth::bind_tenant(&principal, &mut get_memories_by_user_id.user_id)?;
 
         let generated::MemoryBulk { memories } =
             MemoryController::get_memories_by_user_id(get_memories_by_user_id)
                 .await
-                .map_err(|e| {
-                    Status::internal(format!("Failed to get memories by user ID: {}", e))
-                })?;
+                .map_err(error::to_status)?;
 
         Ok(Response::new(generated::MemoryBulk { memories }))
     }
+
+    async fn recall_context(
+        &self,
+        request: Request<generated::RecallContextParameters>,
+    ) -> Result<Response<generated::MemoryContext>, Status> {
+        let principal = auth::authorize(&request, Scope::MemoriesRead)?;
+        let mut recall_context_parameters = request.into_inner();
+        auth::bind_tenant(&principal, &mut recall_context_parameters.user_id)?;
+
+        let recall_context = MemoryController::recall_context(recall_context_parameters)
+            .await
+            .map_err(error::to_status)?;
+
+        Ok(Response::new(recall_context))
+    }
+
+    async fn get_embedding_cache_stats(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<generated::EmbeddingCacheStats>, Status> {
+        auth::authorize(&request, Scope::Admin)?;
+        let stats = MemoryController::embedding_cache_stats();
+
+        Ok(Response::new(generated::EmbeddingCacheStats {
+            memory_hits: stats.memory_hits,
+            disk_hits: stats.disk_hits,
+            misses: stats.misses,
+            disk_bytes: stats.disk_bytes,
+        }))
+    }
+}
+
+fn add_memory_result(result: Result<generated::Memory, rpc::Status>) -> generated::AddMemoryResult {
+    generated::AddMemoryResult {
+        result: Some(match result {
+            Ok(memory) => generated::add_memory_result::Result::Memory(memory),
+            Err(status) => generated::add_memory_result::Result::Error(status),
+        }),
+    }
+}
+
+/// Stores one batch of an ingest stream. Memories naming another tenant, or rejected
+/// by the controller, fail individually; a backend failure fails the whole batch.
+async fn store_batch(
+    principal: &Principal,
+    memories: Vec<generated::Memory>,
+) -> Vec<generated::AddMemoryResult> {
+    let mut outcomes = Vec::with_capacity(memories.len());
+    let mut accepted = Vec::with_capacity(memories.len());
+    for mut memory in memories {
+        match auth::bind_tenant(principal, &mut memory.user_id) {
+            Ok(()) => {
+                accepted.push(memory);
+                outcomes.push(None);
+            }
+            Err(status) => outcomes.push(Some(Err(rpc::Status {
+                code: status.code() as i32,
+                message: status.message().to_string(),
+                details: Vec::new(),
+            }))),
+        }
+    }
+
+    let accepted_count = accepted.len();
+    let mut stored = match accepted_count {
+        0 => Vec::new(),
+        _ => match MemoryController::add_memory_bulk(generated::MemoryBulk { memories: accepted })
+            .await
+        {
+            Ok(results) => results
+                .into_iter()
+                .map(|result| result.map_err(error::to_rpc_status))
+                .collect(),
+            Err(e) => vec![Err(error::to_rpc_status(e)); accepted_count],
+        },
+    }
+    .into_iter();
+
+    outcomes
+        .into_iter()
+        .map(|outcome| {
+            add_memory_result(
+                outcome.unwrap_or_else(|| stored.next().expect("one result per accepted memory")),
+            )
+        })
+        .collect()
 }
@@ -1,12 +1,11 @@
 pub mod service;
-mod token;
 
 use anyhow::Result;
 use axum::{
     Json, Router,
     body::Body,
     extract::State,
-    http::{HeaderValue, Request, StatusCode},
+    http::{Request, StatusCode},
     middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::get,
@@ -23,25 +22,30 @@ use tower_http::{
     trace::{DefaultMakeSpan, DefaultOnRequest, DefaultOnResponse, TraceLayer},
 };
 use tracing::{Level, error, info};
+use umem_auth::{ProviderMetadata, Scope, TokenValidator};
 
 const BIND_ADDRESS: &str = "0.0.0.0:3000";
-const REMOTE_ADDRESS: &str = "https://m.evenscribe.com";
-pub const USER_ID_HEADER: &str = "x-evenscribe-header";
+const DEFAULT_RESOURCE_URL: &str = "https://m.evenscribe.com";
 
-#[derive(Clone, Debug)]
 struct McpAppState {
-    jwks: Arc<token::Jwks>,
+    validator: Arc<TokenValidator>,
+    provider_metadata: ProviderMetadata,
+    resource_url: String,
 }
 
 impl McpAppState {
-    async fn new() -> Self {
-        let jwks_url = std::env::var("JWKS_URL").expect("JWKS_URL not set.");
-        println!("Using JWKS URL: {}", jwks_url);
-        let jwks = token::get_jwks(jwks_url)
-            .await
-            .unwrap_or_else(|e| panic!("THIS IS THE ERRROR BTW, {}", e));
+    fn new(validator: Arc<TokenValidator>, provider_metadata: ProviderMetadata) -> Self {
+        let resource_url =
+            std::env::var("MCP_RESOURCE_URL").unwrap_or_else(|_| DEFAULT_RESOURCE_URL.to_string());
+        info!(
+            "Using OIDC issuer {} for resource {}",
+            validator.issuer(),
+            resource_url
+        );
         Self {
-            jwks: Arc::new(jwks),
+            validator,
+            provider_metadata,
+            resource_url,
         }
     }
 }
@@ -67,62 +71,50 @@ async fn validate_token_middleware(
         }
     };
 
-    let token_data = match token::check_token(token, &Arc::clone(&token_store.jwks)).await {
-        Ok(claims) => claims,
+    let principal = match umem_auth::authenticate(&token_store.validator, token).await {
+        Ok(principal) => principal,
         Err(_) => return StatusCode::UNAUTHORIZED.into_response(),
     };
 
-    let _ = request.headers_mut().insert(
-        USER_ID_HEADER,
-        HeaderValue::from_str(&token_data.claims.sub).unwrap(),
-    );
+    request.extensions_mut().insert(principal);
 
     next.run(request).await
 }
 
-async fn oauth_protected_resource_server() -> impl IntoResponse {
-    let workos_authkit_url = match std::env::var("WORKOS_AUTHKIT_URL") {
-        Ok(url) => url,
-        Err(_) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "WORKOS_AUTHKIT_URL not set",
-            )
-                .into_response();
-        }
-    };
-    let metadata = json!({ // More equity for this line
-        "resource": REMOTE_ADDRESS,
-        "authorization_servers": [workos_authkit_url],
+fn scopes_supported() -> Vec<&'static str> {
+    Scope::ALL.iter().map(Scope::as_str).collect()
+}
+
+async fn oauth_protected_resource_server(
+    State(app_state): State<Arc<McpAppState>>,
+) -> impl IntoResponse {
+    let metadata = json!({
+        "resource": app_state.resource_url,
+        "authorization_servers": [app_state.validator.issuer()],
         "bearer_methods_supported": ["header"],
+        "scopes_supported": scopes_supported(),
     });
     (StatusCode::OK, Json(metadata)).into_response()
 }
 
-async fn oauth_authorization_server() -> impl IntoResponse {
-    let workos_authkit_url = std::env::var("WORKOS_AUTHKIT_URL")
-        .map_err(|_| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "WORKOS_AUTHKIT_URL not set",
-            )
-        })
-        .unwrap();
-
-    let metadata = json!({
-    "authorization_endpoint": format!("{}/oauth2/authorize", workos_authkit_url),
-    "code_challenge_methods_supported": [ "S256" ],
-    "grant_types_supported": [ "authorization_code", "refresh_token" ],
-    "introspection_endpoint": format!("{}/oauth2/introspection", workos_authkit_url),
-    "issuer": workos_authkit_url,
-    "jwks_uri": format!("{}/oauth2/jwks", workos_authkit_url),
-    "registration_endpoint": format!("{}/oauth2/register", workos_authkit_url),
-    "scopes_supported": [ "email", "offline_access", "openid", "profile" ],
-    "response_modes_supported": [ "query" ],
-    "response_types_supported": [ "code" ],
-    "token_endpoint": format!("{}/oauth2/token", workos_authkit_url),
-    "token_endpoint_auth_methods_supported": [ "none", "client_secret_post", "client_secret_basic" ]
-    });
+async fn oauth_authorization_server(
+    State(app_state): State<Arc<McpAppState>>,
+) -> impl IntoResponse {
+    let mut metadata = app_state.provider_metadata.clone();
+    let mut scopes = metadata
+        .other
+        .get("scopes_supported")
+        .and_then(|scopes| scopes.as_array())
+        .cloned()
+        .unwrap_or_default();
+    for scope in scopes_supported() {
+        if !scopes.iter().any(|s| s == scope) {
+            scopes.push(scope.into());
+        }
+    }
+    metadata
+        .other
+        .insert("scopes_supported".to_string(), scopes.into());
 
     Response::builder()
         .status(StatusCode::OK)
@@ -163,12 +155,12 @@ fn build_stream_http(app_state: Arc<McpAppState>) -> Router {
         )
 }
 
-fn build_sse(addr: SocketAddr, app_state: Arc<McpAppState>) -> Router {
+fn build_sse(addr: SocketAddr, app_state: Arc<McpAppState>, ct: CancellationToken) -> Router {
     let sse_config = SseServerConfig {
         bind: addr,
         sse_path: "/mcp/sse".to_string(),
         post_path: "/mcp/message".to_string(),
-        ct: CancellationToken::new(),
+        ct,
         sse_keep_alive: Some(Duration::from_secs(15)),
     };
 
@@ -214,11 +206,17 @@ fn build_auth_router(app_state: Arc<McpAppState>) -> Router {
         .with_state(app_state)
 }
 
-pub async fn run_server() -> Result<()> {
+/// Serves MCP until `shutdown` fires, then stops accepting connections, closes SSE
+/// streams and waits for in-flight requests to finish.
+pub async fn run_server(
+    validator: Arc<TokenValidator>,
+    provider_metadata: ProviderMetadata,
+    shutdown: CancellationToken,
+) -> Result<()> {
     let addr = BIND_ADDRESS.parse()?;
-    let app_state = Arc::new(McpAppState::new().await);
+    let app_state = Arc::new(McpAppState::new(validator, provider_metadata));
 
-    let protected_sse_router = build_sse(addr, Arc::clone(&app_state));
+    let protected_sse_router = build_sse(addr, Arc::clone(&app_state), shutdown.child_token());
     let streamable_router = build_stream_http(Arc::clone(&app_state));
     let oauth_server_router = build_auth_router(Arc::clone(&app_state));
 
@@ -227,11 +225,12 @@ pub async fn run_server() -> Result<()> {
 
     info!("MCP OAuth Server started on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    let server = axum::serve(listener, app);
+    let server = axum::serve(listener, app).with_graceful_shutdown(shutdown.cancelled_owned());
 
     if let Err(e) = server.await {
         error!("Server error: {}", e);
     }
 
+    info!("MCP Server stopped");
     Ok(())
 }
@@ -1,6 +1,6 @@
-use crate::USER_ID_HEADER;
 use anyhow::Result;
 use axum::http::request::Parts;
+use base64::{Engine, engine::general_purpose::STANDARD};
 use rmcp::{
     handler::server::{
         router::tool::ToolRouter,
@@ -9,33 +9,133 @@ use rmcp::{
     model::{ErrorData as McpError, *},
     schemars, tool, tool_handler, tool_router,
 };
-use tracing::debug;
-use umem_controller::MemoryController;
+use serde_json::json;
+use tracing::{debug, error};
+use umem_auth::{Principal, Scope};
+use umem_controller::{ControllerError, MemoryController};
 use umem_proto_generated::generated;
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct AddMemoryRequest {
     pub text: String,
+    /// Retrying with the same key updates the memory stored by the first attempt
+    /// instead of adding a duplicate.
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct IngestDocumentRequest {
+    /// File name with its extension, which decides how the text is extracted.
+    pub filename: String,
+    /// The file content, base64 encoded.
+    pub content_base64: String,
+    pub mime_type: Option<String>,
+    /// Defaults to the file name.
+    pub title: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct IngestUrlRequest {
+    /// An http or https URL of a public web page.
+    pub url: String,
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
 pub struct GetMemoriesByQueryRequest {
     pub query: String,
+    /// Return the document each matching document chunk belongs to.
+    pub include_document: Option<bool>,
+    /// Chunks to return on each side of a matching document chunk, at most 5.
+    pub neighbor_chunks: Option<u32>,
+    /// Return each matching document chunk merged with its neighbors into one text.
+    pub merge_window: Option<bool>,
+}
+
+#[derive(Debug, serde::Deserialize, schemars::JsonSchema)]
+pub struct RecallContextRequest {
+    pub query: String,
+    /// Maximum number of tokens the returned context block may use.
+    pub token_budget: Option<u32>,
 }
 
+/// Implementation defined JSON-RPC server errors, next to the ones rmcp provides.
+pub const PERMISSION_DENIED: ErrorCode = ErrorCode(-32003);
+pub const UNAVAILABLE: ErrorCode = ErrorCode(-32004);
+pub const RATE_LIMITED: ErrorCode = ErrorCode(-32005);
+
 #[derive(Clone, Default)]
 pub struct McpService {
     tool_router: ToolRouter<Self>,
 }
 
-fn extract_user_id(parts: Parts) -> String {
-    parts
-        .headers
-        .get(USER_ID_HEADER)
-        .expect("Missing user ID header")
-        .to_str()
-        .expect("Invalid user ID header value")
-        .to_owned()
+fn authorize(parts: &Parts, scope: Scope) -> Result<String, McpError> {
+    let principal = parts
+        .extensions
+        .get::<Principal>()
+        .ok_or_else(|| McpError::new(ErrorCode::INVALID_REQUEST, "Missing credentials", None))?;
+    if !principal.allows(scope) {
+        return Err(McpError::new(
+            PERMISSION_DENIED,
+            format!("Missing required scope {}", scope),
+            Some(json!({ "reason": "PERMISSION_DENIED", "retryable": false })),
+        ));
+    }
+    Ok(principal.user_id.clone())
+}
+
+/// Maps a controller failure to an MCP error. `data.retryable` tells clients whether
+/// the same call may succeed later.
+fn to_mcp_error(error: ControllerError) -> McpError {
+    let message = error.to_string();
+    let retryable = error.is_retryable();
+    match error {
+        ControllerError::Validation { field, .. } => McpError::invalid_params(
+            message,
+            Some(json!({ "field": field, "retryable": retryable })),
+        ),
+        ControllerError::NotFound {
+            resource_type,
+            resource_name,
+        } => McpError::resource_not_found(
+            message,
+            Some(json!({
+                "resource_type": resource_type,
+                "resource_name": resource_name,
+                "retryable": retryable,
+            })),
+        ),
+        ControllerError::EmbeddingUnavailable(e) => {
+            error!("Embedding backend unavailable: {:#}", e);
+            McpError::new(
+                UNAVAILABLE,
+                message,
+                Some(json!({ "reason": "EMBEDDING_BACKEND_UNAVAILABLE", "retryable": retryable })),
+            )
+        }
+        ControllerError::VectorStoreUnavailable(e) => {
+            error!("Vector store unavailable: {:#}", e);
+            McpError::new(
+                UNAVAILABLE,
+                message,
+                Some(json!({ "reason": "VECTOR_STORE_UNAVAILABLE", "retryable": retryable })),
+            )
+        }
+        ControllerError::RateLimited { retry_after, .. } => McpError::new(
+            RATE_LIMITED,
+            message,
+            Some(json!({
+                "reason": "RATE_LIMITED",
+                "retryable": retryable,
+                "retry_after_seconds": retry_after.map(|retry_after| retry_after.as_secs()),
+            })),
+        ),
+        ControllerError::Internal(e) => {
+            error!("Internal error: {:?}", e);
+            McpError::internal_error("Internal error", None)
+        }
+    }
 }
 
 impl McpService {
@@ -60,25 +160,22 @@ impl McpService {
     async fn add_memory(
         &self,
         Extension(parts): Extension<Parts>,
-        Parameters(AddMemoryRequest { text }): Parameters<AddMemoryRequest>,
+        Parameters(AddMemoryRequest {
+            text,
+            idempotency_key,
+        }): Parameters<AddMemoryRequest>,
     ) -> Result<CallToolResult, McpError> {
         debug!("add_memory tool called with text: {}", text);
-        let user_id = extract_user_id(parts);
-        if text.is_empty() {
-            return Err(McpError::new(
-                ErrorCode::INVALID_REQUEST,
-                "Memory content cannot be empty",
-                None,
-            ));
-        }
+        let user_id = authorize(&parts, Scope::MemoriesWrite)?;
 
         let memory = MemoryController::add_memory(generated::Memory {
             user_id,
             content: text,
+            idempotency_key: idempotency_key.unwrap_or_default(),
             ..Default::default()
         })
         .await
-        .unwrap();
+        .map_err(to_mcp_error)?;
 
         Ok(CallToolResult::success(vec![Annotated::new(
             RawContent::Text(RawTextContent {
@@ -88,6 +185,97 @@ impl McpService {
         )]))
     }
 
+    #[tool(
+        name = "ingest_document",
+        description = "Store a document (PDF, DOCX, EPUB, HTML, CSV, TSV, JSON, Markdown or plain text) in umem. The text is extracted, split into chunks and every chunk is saved as a memory linked to the returned document, so later queries find the relevant passages. WHEN TO USE: when the user shares a file whose contents should be remembered. Uploading the same file again replaces its earlier chunks. Chunks of a PDF carry the pages they come from, and pages whose text could not be extracted are listed after the document."
+    )]
+    async fn ingest_document(
+        &self,
+        Extension(parts): Extension<Parts>,
+        Parameters(IngestDocumentRequest {
+            filename,
+            content_base64,
+            mime_type,
+            title,
+            tags,
+        }): Parameters<IngestDocumentRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        debug!("ingest_document tool called with filename: {}", filename);
+        let user_id = authorize(&parts, Scope::MemoriesWrite)?;
+        let content = STANDARD.decode(content_base64.trim()).map_err(|e| {
+            McpError::invalid_params(
+                format!("Invalid content_base64: {e}"),
+                Some(json!({ "field": "content_base64", "retryable": false })),
+            )
+        })?;
+
+        let response = MemoryController::ingest_document(generated::IngestDocumentParameters {
+            user_id,
+            filename,
+            mime_type: mime_type.unwrap_or_default(),
+            content,
+            title: title.unwrap_or_default(),
+            tags: tags.unwrap_or_default(),
+            ..Default::default()
+        })
+        .await
+        .map_err(to_mcp_error)?;
+
+        let mut content = vec![Annotated::new(
+            RawContent::Text(RawTextContent {
+                text: serde_json::to_string(&response.document).unwrap(),
+            }),
+            None,
+        )];
+        if !response.failed_pages.is_empty() {
+            content.push(Annotated::new(
+                RawContent::Text(RawTextContent {
+                    text: json!({ "failed_pages": response.failed_pages }).to_string(),
+                }),
+                None,
+            ));
+        }
+        Ok(CallToolResult::success(content))
+    }
+
+    #[tool(
+        name = "ingest_url",
+        description = "Store a web page in umem. The page is fetched, its main content is converted to Markdown without navigation, banners or footers, split into chunks at its headings and every chunk is saved as a memory tagged with the page URL and title. WHEN TO USE: when the user shares a link whose contents should be remembered. Ingesting the same URL again replaces its earlier chunks with the current page, or reports it unchanged when the page has not changed since."
+    )]
+    async fn ingest_url(
+        &self,
+        Extension(parts): Extension<Parts>,
+        Parameters(IngestUrlRequest { url, tags }): Parameters<IngestUrlRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        debug!("ingest_url tool called with url: {}", url);
+        let user_id = authorize(&parts, Scope::MemoriesWrite)?;
+
+        let response = MemoryController::ingest_url(generated::IngestUrlParameters {
+            user_id,
+            url,
+            tags: tags.unwrap_or_default(),
+            ..Default::default()
+        })
+        .await
+        .map_err(to_mcp_error)?;
+
+        let mut content = vec![Annotated::new(
+            RawContent::Text(RawTextContent {
+                text: serde_json::to_string(&response.document).unwrap(),
+            }),
+            None,
+        )];
+        if response.unchanged {
+            content.push(Annotated::new(
+                RawContent::Text(RawTextContent {
+                    text: json!({ "unchanged": true }).to_string(),
+                }),
+                None,
+            ));
+        }
+        Ok(CallToolResult::success(content))
+    }
+
     #[tool(
         name = "get_memory",
         description = "Get all memories for the current user. Retrieves the user's persistent memory store containing important context, preferences, and historical interactions. This tool should be called at the beginning of conversations to load relevant contextual information and provide personalized responses based on past interactions. After using this information, remember to save new important details using add_memory."
@@ -97,11 +285,11 @@ impl McpService {
         Extension(parts): Extension<Parts>,
     ) -> Result<CallToolResult, McpError> {
         let parameters = generated::GetMemoriesByUserIdParameters {
-            user_id: extract_user_id(parts),
+            user_id: authorize(&parts, Scope::MemoriesRead)?,
         };
         let memory_bulk: String = MemoryController::get_memories_by_user_id(parameters)
             .await
-            .unwrap()
+            .map_err(to_mcp_error)?
             .memories
             .iter()
             .map(|mem| serde_json::to_string(mem).unwrap())
@@ -115,23 +303,39 @@ impl McpService {
 
     #[tool(
         name = "get_memory_by_query",
-        description = "Get memories for the current user related to a query. This tool enables targeted retrieval of specific memories from the persistence layer using semantic search capabilities. WHEN TO USE: (1) When responding to questions that may benefit from past context, (2) Before generating responses that should consider historical preferences or interactions, (3) When references to previous conversations are made, or (4) When topic-specific context would improve response quality. IMPLEMENTATION: The query parameter accepts natural language or keywords—umem automatically performs hybrid semantic and keyword matching to retrieve the most relevant memories. BEST PRACTICE: Use focused, specific queries rather than generic ones for better results. After retrieving memories, consider saving new insights with add_memory to maintain an up-to-date persistence layer."
+        description = "Get memories for the current user related to a query. This tool enables targeted retrieval of specific memories from the persistence layer using semantic search capabilities. WHEN TO USE: (1) When responding to questions that may benefit from past context, (2) Before generating responses that should consider historical preferences or interactions, (3) When references to previous conversations are made, or (4) When topic-specific context would improve response quality. IMPLEMENTATION: The query parameter accepts natural language or keywords—umem automatically performs hybrid semantic and keyword matching to retrieve the most relevant memories. BEST PRACTICE: Use focused, specific queries rather than generic ones for better results. After retrieving memories, consider saving new insights with add_memory to maintain an up-to-date persistence layer. DOCUMENTS: Memories with a document_id are chunks of an ingested file or web page. Set include_document, neighbor_chunks or merge_window to get their surrounding context, returned as one JSON object per matching chunk after the memories."
     )]
     async fn get_memory_by_query(
         &self,
         Extension(parts): Extension<Parts>,
-        Parameters(GetMemoriesByQueryRequest { query }): Parameters<GetMemoriesByQueryRequest>,
+        Parameters(GetMemoriesByQueryRequest {
+            query,
+            include_document,
+            neighbor_chunks,
+            merge_window,
+        }): Parameters<GetMemoriesByQueryRequest>,
     ) -> Result<CallToolResult, McpError> {
         let parameters = generated::GetMemoriesByQueryParameters {
-            user_id: extract_user_id(parts),
+            user_id: authorize(&parts, Scope::MemoriesRead)?,
             query,
+            include_document: include_document.unwrap_or_default(),
+            neighbor_chunks: neighbor_chunks.unwrap_or_default(),
+            merge_window: merge_window.unwrap_or_default(),
         };
-        let memory_bulk: String = MemoryController::get_memories_by_query(parameters)
+        let response = MemoryController::get_memories_by_query(parameters)
             .await
-            .unwrap()
+            .map_err(to_mcp_error)?;
+        // Contexts follow the memories, one JSON object per line like them.
+        let memory_bulk: String = response
             .memories
             .iter()
             .map(|mem| serde_json::to_string(mem).unwrap())
+            .chain(
+                response
+                    .contexts
+                    .iter()
+                    .map(|context| serde_json::to_string(context).unwrap()),
+            )
             .collect::<Vec<String>>()
             .join("\n");
         Ok(CallToolResult::success(vec![Annotated::new(
@@ -139,6 +343,34 @@ impl McpService {
             None,
         )]))
     }
+
+    #[tool(
+        name = "recall_context",
+        description = "Recall the user's memories most relevant to a query, packed into a single formatted context block that fits within a token budget. Memories are ranked by semantic relevance, priority and recency, and the best ones are included until the budget is spent. WHEN TO USE: Prefer this over get_memory_by_query when the memories will be placed directly into a prompt and the available context is limited. The token_budget parameter is optional and defaults to 2000 tokens."
+    )]
+    async fn recall_context(
+        &self,
+        Extension(parts): Extension<Parts>,
+        Parameters(RecallContextRequest {
+            query,
+            token_budget,
+        }): Parameters<RecallContextRequest>,
+    ) -> Result<CallToolResult, McpError> {
+        let parameters = generated::RecallContextParameters {
+            user_id: authorize(&parts, Scope::MemoriesRead)?,
+            query,
+            token_budget: token_budget.unwrap_or_default(),
+        };
+        let recall_context = MemoryController::recall_context(parameters)
+            .await
+            .map_err(to_mcp_error)?;
+        Ok(CallToolResult::success(vec![Annotated::new(
+            RawContent::Text(RawTextContent {
+                text: recall_context.context,
+            }),
+            None,
+        )]))
+    }
 }
 
 #[tool_handler]
@@ -1,55 +0,0 @@
-use anyhow::Result;
-use jsonwebtoken::{DecodingKey, TokenData, decode_header};
-use serde::{Deserialize, Serialize};
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub exp: usize,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Jwk {
-    pub kid: String,
-    pub kty: String,
-    pub alg: String,
-    pub n: String,
-    pub e: String,
-}
-
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Jwks {
-    pub keys: Vec<Jwk>,
-}
-
-pub async fn get_jwks(jwks_url: String) -> Result<Jwks> {
-    let jwks_resp = reqwest::get(jwks_url).await?;
-    Ok(jwks_resp.json().await?)
-}
-
-pub async fn check_token(token: &str, keys: &Jwks) -> Result<TokenData<Claims>, String> {
-    let header = decode_header(token).unwrap();
-    let kid = header.kid.ok_or("No kid found in token header")?;
-
-    let client_id = match std::env::var("WORKOS_CLIENT_ID") {
-        Ok(id) => id,
-        Err(_) => return Err("WORKOS_CLIENT_ID environment variable not set".to_string()),
-    };
-
-    let jwk = keys
-        .keys
-        .iter()
-        .find(|k| k.kid == kid)
-        .ok_or("No matching kid found in jwks")?;
-
-    let decoding_key = DecodingKey::from_rsa_components(&jwk.n, &jwk.e)
-        .map_err(|op| format!("Decoding Key Error: {:?}", op))?;
-
-    let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS256);
-    validation.set_audience(&[client_id.as_str()]);
-
-    let token_data = jsonwebtoken::decode::<Claims>(token, &decoding_key, &validation)
-        .map_err(|op| format!("JWT Decode Error: {:?}", op))?;
-
-    Ok(token_data)
-}
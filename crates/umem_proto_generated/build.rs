@@ -1,17 +1,35 @@
 extern crate rmcp;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR")?);
     tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("umem_descriptor.bin"))
         // .out_dir("./src/")
         .type_attribute("Memory", "use crate::schemars;")
         .type_attribute(
             "Memory",
             "#[derive(schemars::JsonSchema, serde::Serialize, serde::Deserialize)]",
         )
+        // Stored payloads predate newer fields.
+        .type_attribute("Memory", "#[serde(default)]")
+        .type_attribute(
+            "Document",
+            "#[derive(schemars::JsonSchema, serde::Serialize, serde::Deserialize)]",
+        )
+        .type_attribute("Document", "#[serde(default)]")
+        .type_attribute("ChunkContext", "#[derive(serde::Serialize)]")
+        .type_attribute("PageFailure", "#[derive(serde::Serialize)]")
         .type_attribute(
             "UpdateMemoryParameters",
             "#[derive(schemars::JsonSchema, serde::Serialize, serde::Deserialize)]",
         )
-        .compile_protos(&["proto/memory.proto"], &["proto"])?;
+        .compile_protos(
+            &[
+                "proto/memory.proto",
+                "proto/google/rpc/status.proto",
+                "proto/google/rpc/error_details.proto",
+            ],
+            &["proto"],
+        )?;
     Ok(())
 }
@@ -9,6 +9,39 @@ impl QdrantIdentifiable for generated::Memory {
     }
 }
 
+impl QdrantIdentifiable for generated::Document {
+    fn get_id(&self) -> impl Into<PointId> {
+        self.document_id.clone()
+    }
+}
+
 pub mod generated {
     tonic::include_proto!("memory");
+
+    /// Encoded descriptors of every umem proto, served by gRPC reflection.
+    pub const FILE_DESCRIPTOR_SET: &[u8] = tonic::include_file_descriptor_set!("umem_descriptor");
+}
+
+pub mod google {
+    /// Error detail messages of the `google.rpc` richer error model.
+    pub mod rpc {
+        tonic::include_proto!("google.rpc");
+
+        macro_rules! impl_name {
+            ($($message:ident),*) => {$(
+                impl prost::Name for $message {
+                    const NAME: &'static str = stringify!($message);
+                    const PACKAGE: &'static str = "google.rpc";
+
+                    fn type_url() -> String {
+                        format!("type.googleapis.com/{}", <Self as prost::Name>::full_name())
+                    }
+                }
+            )*};
+        }
+
+        impl_name!(ErrorInfo, RetryInfo, BadRequest, ResourceInfo);
+    }
 }
+
+pub use google::rpc;
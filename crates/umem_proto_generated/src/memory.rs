@@ -1,8 +1,8 @@
 // This file is @generated by prost-build.
 use crate::schemars;
-#[derive(
-    schemars::JsonSchema, serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message,
-)]
+#[derive(schemars::JsonSchema, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Memory {
     /// tenant
     #[prost(string, tag = "1")]
@@ -19,12 +19,164 @@ pub struct Memory {
     pub created_at: i64,
     #[prost(int64, tag = "7")]
     pub updated_at: i64,
+    /// Optional on add. Retried writes with the same key upsert the same memory_id
+    /// instead of creating duplicates.
+    #[prost(string, tag = "8")]
+    pub idempotency_key: ::prost::alloc::string::String,
+    /// set on the chunks of an ingested document
+    #[prost(string, tag = "9")]
+    pub document_id: ::prost::alloc::string::String,
+    /// position of the chunk within its document
+    #[prost(uint32, tag = "10")]
+    pub chunk_index: u32,
+    /// Byte offsets of the chunk in the text extracted from its document.
+    #[prost(uint32, tag = "11")]
+    pub chunk_start: u32,
+    #[prost(uint32, tag = "12")]
+    pub chunk_end: u32,
+    /// First and last page of a paginated document the chunk comes from, counting
+    /// from 1. Unset for formats without pages.
+    #[prost(uint32, tag = "13")]
+    pub page_start: u32,
+    #[prost(uint32, tag = "14")]
+    pub page_end: u32,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct MemoryBulk {
     #[prost(message, repeated, tag = "1")]
     pub memories: ::prost::alloc::vec::Vec<Memory>,
 }
+/// Outcome of one memory of an AddMemoryBulk call.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddMemoryResult {
+    #[prost(oneof = "add_memory_result::Result", tags = "1, 2")]
+    pub result: ::core::option::Option<add_memory_result::Result>,
+}
+/// Nested message and enum types in `AddMemoryResult`.
+pub mod add_memory_result {
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Result {
+        /// the stored memory, with its memory_id and timestamps
+        #[prost(message, tag = "1")]
+        Memory(super::Memory),
+        #[prost(message, tag = "2")]
+        Error(super::super::google::rpc::Status),
+    }
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddMemoryBulkResponse {
+    /// in request order
+    #[prost(message, repeated, tag = "1")]
+    pub results: ::prost::alloc::vec::Vec<AddMemoryResult>,
+}
+/// A file or web page ingested with IngestDocument or IngestUrl. Its text is
+/// stored as chunk memories carrying its document_id.
+#[derive(schemars::JsonSchema, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Document {
+    #[prost(string, tag = "1")]
+    pub document_id: ::prost::alloc::string::String,
+    /// tenant
+    #[prost(string, tag = "2")]
+    pub user_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub title: ::prost::alloc::string::String,
+    /// file name the document was uploaded as, or its URL
+    #[prost(string, tag = "4")]
+    pub source: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub mime_type: ::prost::alloc::string::String,
+    /// hex SHA-256 of the uploaded bytes, or of the text kept from a web page
+    #[prost(string, tag = "6")]
+    pub content_hash: ::prost::alloc::string::String,
+    #[prost(int64, tag = "7")]
+    pub created_at: i64,
+    #[prost(uint32, tag = "8")]
+    pub chunk_count: u32,
+    /// as the file declares it
+    #[prost(string, tag = "9")]
+    pub author: ::prost::alloc::string::String,
+    /// creation date the file declares, unset when it has none
+    #[prost(int64, tag = "10")]
+    pub authored_at: i64,
+    /// pages with text, for paginated formats
+    #[prost(uint32, tag = "11")]
+    pub page_count: u32,
+    /// of web pages that declare one
+    #[prost(string, tag = "12")]
+    pub canonical_url: ::prost::alloc::string::String,
+    /// BCP 47 tag the document declares, such as en-GB
+    #[prost(string, tag = "13")]
+    pub language: ::prost::alloc::string::String,
+    /// sent by the server of a web page, for conditional re-fetching
+    #[prost(string, tag = "14")]
+    pub etag: ::prost::alloc::string::String,
+    /// sent by the server of a web page, for conditional re-fetching
+    #[prost(string, tag = "15")]
+    pub last_modified: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IngestDocumentParameters {
+    /// tenant
+    #[prost(string, tag = "1")]
+    pub user_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub filename: ::prost::alloc::string::String,
+    /// optional, detected from the file name and content
+    #[prost(string, tag = "3")]
+    pub mime_type: ::prost::alloc::string::String,
+    #[prost(bytes = "vec", tag = "4")]
+    pub content: ::prost::alloc::vec::Vec<u8>,
+    /// defaults to the title the file declares, then the file name
+    #[prost(string, tag = "5")]
+    pub title: ::prost::alloc::string::String,
+    /// given to every chunk
+    #[prost(int32, tag = "6")]
+    pub priority: i32,
+    /// given to every chunk
+    #[prost(string, repeated, tag = "7")]
+    pub tags: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IngestUrlParameters {
+    /// tenant
+    #[prost(string, tag = "1")]
+    pub user_id: ::prost::alloc::string::String,
+    /// http or https, resolving to a public address
+    #[prost(string, tag = "2")]
+    pub url: ::prost::alloc::string::String,
+    /// given to every chunk
+    #[prost(int32, tag = "3")]
+    pub priority: i32,
+    /// given to every chunk
+    #[prost(string, repeated, tag = "4")]
+    pub tags: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IngestDocumentResponse {
+    #[prost(message, optional, tag = "1")]
+    pub document: ::core::option::Option<Document>,
+    /// in document order
+    #[prost(message, repeated, tag = "2")]
+    pub chunks: ::prost::alloc::vec::Vec<Memory>,
+    /// pages left out of the chunks
+    #[prost(message, repeated, tag = "3")]
+    pub failed_pages: ::prost::alloc::vec::Vec<PageFailure>,
+    /// the text was already stored as is; chunks is empty
+    #[prost(bool, tag = "4")]
+    pub unchanged: bool,
+    /// chunks whose text was new; the others kept their stored embeddings
+    #[prost(uint32, tag = "5")]
+    pub embedded_chunks: u32,
+}
+#[derive(serde::Serialize, Clone, PartialEq, ::prost::Message)]
+pub struct PageFailure {
+    #[prost(uint32, tag = "1")]
+    pub page: u32,
+    #[prost(string, tag = "2")]
+    pub reason: ::prost::alloc::string::String,
+}
 #[derive(
     schemars::JsonSchema, serde::Serialize, serde::Deserialize, Clone, PartialEq, ::prost::Message,
 )]
@@ -37,11 +189,25 @@ pub struct UpdateMemoryParameters {
     pub priority: i32,
     #[prost(string, repeated, tag = "4")]
     pub tags: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// tenant
+    #[prost(string, tag = "5")]
+    pub user_id: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct DeleteMemoryParameters {
     #[prost(string, tag = "1")]
     pub memory_id: ::prost::alloc::string::String,
+    /// tenant
+    #[prost(string, tag = "2")]
+    pub user_id: ::prost::alloc::string::String,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteDocumentParameters {
+    #[prost(string, tag = "1")]
+    pub document_id: ::prost::alloc::string::String,
+    /// tenant
+    #[prost(string, tag = "2")]
+    pub user_id: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetMemoriesByQueryParameters {
@@ -49,12 +215,77 @@ pub struct GetMemoriesByQueryParameters {
     pub user_id: ::prost::alloc::string::String,
     #[prost(string, tag = "2")]
     pub query: ::prost::alloc::string::String,
+    /// Options for hits on document chunks, answered in MemorySearchResponse.contexts.
+    #[prost(bool, tag = "3")]
+    pub include_document: bool,
+    /// chunks to return on each side of a hit, at most 5
+    #[prost(uint32, tag = "4")]
+    pub neighbor_chunks: u32,
+    /// merge each hit with its neighbors, one on each side by default
+    #[prost(bool, tag = "5")]
+    pub merge_window: bool,
+}
+/// Context around a search hit on a document chunk.
+#[derive(serde::Serialize, Clone, PartialEq, ::prost::Message)]
+pub struct ChunkContext {
+    /// the hit
+    #[prost(string, tag = "1")]
+    pub memory_id: ::prost::alloc::string::String,
+    /// with include_document
+    #[prost(message, optional, tag = "2")]
+    pub document: ::core::option::Option<Document>,
+    /// in document order, with neighbor_chunks
+    #[prost(message, repeated, tag = "3")]
+    pub neighbors: ::prost::alloc::vec::Vec<Memory>,
+    /// with merge_window
+    #[prost(string, tag = "4")]
+    pub window: ::prost::alloc::string::String,
+}
+/// Wire compatible with MemoryBulk.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MemorySearchResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub memories: ::prost::alloc::vec::Vec<Memory>,
+    /// in the order of their hits
+    #[prost(message, repeated, tag = "2")]
+    pub contexts: ::prost::alloc::vec::Vec<ChunkContext>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetMemoriesByUserIdParameters {
     #[prost(string, tag = "1")]
     pub user_id: ::prost::alloc::string::String,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecallContextParameters {
+    #[prost(string, tag = "1")]
+    pub user_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub query: ::prost::alloc::string::String,
+    /// 0 uses the server default
+    #[prost(uint32, tag = "3")]
+    pub token_budget: u32,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MemoryContext {
+    /// formatted block ready to be placed in a prompt
+    #[prost(string, tag = "1")]
+    pub context: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub memories: ::prost::alloc::vec::Vec<Memory>,
+    #[prost(uint32, tag = "3")]
+    pub token_count: u32,
+}
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct EmbeddingCacheStats {
+    #[prost(uint64, tag = "1")]
+    pub memory_hits: u64,
+    #[prost(uint64, tag = "2")]
+    pub disk_hits: u64,
+    #[prost(uint64, tag = "3")]
+    pub misses: u64,
+    #[prost(uint64, tag = "4")]
+    pub disk_bytes: u64,
+}
 /// Generated client implementations.
 pub mod memory_service_client {
     #![allow(
@@ -149,7 +380,7 @@ pub mod memory_service_client {
         pub async fn add_memory(
             &mut self,
             request: impl tonic::IntoRequest<super::Memory>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+        ) -> std::result::Result<tonic::Response<super::Memory>, tonic::Status> {
             self.inner.ready().await.map_err(|e| {
                 tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
             })?;
@@ -163,7 +394,8 @@ pub mod memory_service_client {
         pub async fn add_memory_bulk(
             &mut self,
             request: impl tonic::IntoRequest<super::MemoryBulk>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+        ) -> std::result::Result<tonic::Response<super::AddMemoryBulkResponse>, tonic::Status>
+        {
             self.inner.ready().await.map_err(|e| {
                 tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
             })?;
@@ -174,6 +406,59 @@ pub mod memory_service_client {
                 .insert(GrpcMethod::new("memory.MemoryService", "AddMemoryBulk"));
             self.inner.unary(req, path, codec).await
         }
+        /// Streams memories in for large imports; one result per memory comes back in
+        /// the order the memories were sent.
+        pub async fn ingest_memories(
+            &mut self,
+            request: impl tonic::IntoStreamingRequest<Message = super::Memory>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::AddMemoryResult>>,
+            tonic::Status,
+        > {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/memory.MemoryService/IngestMemories");
+            let mut req = request.into_streaming_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("memory.MemoryService", "IngestMemories"));
+            self.inner.streaming(req, path, codec).await
+        }
+        /// Extracts the text of a file, splits it into chunks and stores each chunk as a
+        /// memory linked to the returned document.
+        pub async fn ingest_document(
+            &mut self,
+            request: impl tonic::IntoRequest<super::IngestDocumentParameters>,
+        ) -> std::result::Result<tonic::Response<super::IngestDocumentResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/memory.MemoryService/IngestDocument");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("memory.MemoryService", "IngestDocument"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Fetches a web page and stores it like a document, chunked by its headings.
+        /// Ingesting the same URL again replaces the chunks stored for it before.
+        pub async fn ingest_url(
+            &mut self,
+            request: impl tonic::IntoRequest<super::IngestUrlParameters>,
+        ) -> std::result::Result<tonic::Response<super::IngestDocumentResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/memory.MemoryService/IngestUrl");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("memory.MemoryService", "IngestUrl"));
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn update_memory(
             &mut self,
             request: impl tonic::IntoRequest<super::UpdateMemoryParameters>,
@@ -202,11 +487,27 @@ pub mod memory_service_client {
                 .insert(GrpcMethod::new("memory.MemoryService", "DeleteMemory"));
             self.inner.unary(req, path, codec).await
         }
+        /// Deletes a document together with all of its chunks.
+        pub async fn delete_document(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DeleteDocumentParameters>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/memory.MemoryService/DeleteDocument");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("memory.MemoryService", "DeleteDocument"));
+            self.inner.unary(req, path, codec).await
+        }
         /// Qdrant Queries
         pub async fn get_memories_by_query(
             &mut self,
             request: impl tonic::IntoRequest<super::GetMemoriesByQueryParameters>,
-        ) -> std::result::Result<tonic::Response<super::MemoryBulk>, tonic::Status> {
+        ) -> std::result::Result<tonic::Response<super::MemorySearchResponse>, tonic::Status>
+        {
             self.inner.ready().await.map_err(|e| {
                 tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
             })?;
@@ -237,6 +538,41 @@ pub mod memory_service_client {
             ));
             self.inner.unary(req, path, codec).await
         }
+        pub async fn recall_context(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RecallContextParameters>,
+        ) -> std::result::Result<tonic::Response<super::MemoryContext>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/memory.MemoryService/RecallContext");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("memory.MemoryService", "RecallContext"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// Administration
+        /// Counters of this server's embedding cache since it started.
+        pub async fn get_embedding_cache_stats(
+            &mut self,
+            request: impl tonic::IntoRequest<()>,
+        ) -> std::result::Result<tonic::Response<super::EmbeddingCacheStats>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::unknown(format!("Service was not ready: {}", e.into()))
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/memory.MemoryService/GetEmbeddingCacheStats",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut().insert(GrpcMethod::new(
+                "memory.MemoryService",
+                "GetEmbeddingCacheStats",
+            ));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -256,11 +592,34 @@ pub mod memory_service_server {
         async fn add_memory(
             &self,
             request: tonic::Request<super::Memory>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+        ) -> std::result::Result<tonic::Response<super::Memory>, tonic::Status>;
         async fn add_memory_bulk(
             &self,
             request: tonic::Request<super::MemoryBulk>,
-        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+        ) -> std::result::Result<tonic::Response<super::AddMemoryBulkResponse>, tonic::Status>;
+        /// Server streaming response type for the IngestMemories method.
+        type IngestMemoriesStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::AddMemoryResult, tonic::Status>,
+            > + std::marker::Send
+            + 'static;
+        /// Streams memories in for large imports; one result per memory comes back in
+        /// the order the memories were sent.
+        async fn ingest_memories(
+            &self,
+            request: tonic::Request<tonic::Streaming<super::Memory>>,
+        ) -> std::result::Result<tonic::Response<Self::IngestMemoriesStream>, tonic::Status>;
+        /// Extracts the text of a file, splits it into chunks and stores each chunk as a
+        /// memory linked to the returned document.
+        async fn ingest_document(
+            &self,
+            request: tonic::Request<super::IngestDocumentParameters>,
+        ) -> std::result::Result<tonic::Response<super::IngestDocumentResponse>, tonic::Status>;
+        /// Fetches a web page and stores it like a document, chunked by its headings.
+        /// Ingesting the same URL again replaces the chunks stored for it before.
+        async fn ingest_url(
+            &self,
+            request: tonic::Request<super::IngestUrlParameters>,
+        ) -> std::result::Result<tonic::Response<super::IngestDocumentResponse>, tonic::Status>;
         async fn update_memory(
             &self,
             request: tonic::Request<super::UpdateMemoryParameters>,
@@ -269,15 +628,30 @@ pub mod memory_service_server {
             &self,
             request: tonic::Request<super::DeleteMemoryParameters>,
         ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
+        /// Deletes a document together with all of its chunks.
+        async fn delete_document(
+            &self,
+            request: tonic::Request<super::DeleteDocumentParameters>,
+        ) -> std::result::Result<tonic::Response<()>, tonic::Status>;
         /// Qdrant Queries
         async fn get_memories_by_query(
             &self,
             request: tonic::Request<super::GetMemoriesByQueryParameters>,
-        ) -> std::result::Result<tonic::Response<super::MemoryBulk>, tonic::Status>;
+        ) -> std::result::Result<tonic::Response<super::MemorySearchResponse>, tonic::Status>;
         async fn get_memories_by_user_id(
             &self,
             request: tonic::Request<super::GetMemoriesByUserIdParameters>,
         ) -> std::result::Result<tonic::Response<super::MemoryBulk>, tonic::Status>;
+        async fn recall_context(
+            &self,
+            request: tonic::Request<super::RecallContextParameters>,
+        ) -> std::result::Result<tonic::Response<super::MemoryContext>, tonic::Status>;
+        /// Administration
+        /// Counters of this server's embedding cache since it started.
+        async fn get_embedding_cache_stats(
+            &self,
+            request: tonic::Request<()>,
+        ) -> std::result::Result<tonic::Response<super::EmbeddingCacheStats>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct MemoryServiceServer<T> {
@@ -356,7 +730,7 @@ pub mod memory_service_server {
                     #[allow(non_camel_case_types)]
                     struct AddMemorySvc<T: MemoryService>(pub Arc<T>);
                     impl<T: MemoryService> tonic::server::UnaryService<super::Memory> for AddMemorySvc<T> {
-                        type Response = ();
+                        type Response = super::Memory;
                         type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(&mut self, request: tonic::Request<super::Memory>) -> Self::Future {
                             let inner = Arc::clone(&self.0);
@@ -392,7 +766,7 @@ pub mod memory_service_server {
                     #[allow(non_camel_case_types)]
                     struct AddMemoryBulkSvc<T: MemoryService>(pub Arc<T>);
                     impl<T: MemoryService> tonic::server::UnaryService<super::MemoryBulk> for AddMemoryBulkSvc<T> {
-                        type Response = ();
+                        type Response = super::AddMemoryBulkResponse;
                         type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
@@ -427,6 +801,128 @@ pub mod memory_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/memory.MemoryService/IngestMemories" => {
+                    #[allow(non_camel_case_types)]
+                    struct IngestMemoriesSvc<T: MemoryService>(pub Arc<T>);
+                    impl<T: MemoryService> tonic::server::StreamingService<super::Memory> for IngestMemoriesSvc<T> {
+                        type Response = super::AddMemoryResult;
+                        type ResponseStream = T::IngestMemoriesStream;
+                        type Future =
+                            BoxFuture<tonic::Response<Self::ResponseStream>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<tonic::Streaming<super::Memory>>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MemoryService>::ingest_memories(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = IngestMemoriesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/memory.MemoryService/IngestDocument" => {
+                    #[allow(non_camel_case_types)]
+                    struct IngestDocumentSvc<T: MemoryService>(pub Arc<T>);
+                    impl<T: MemoryService>
+                        tonic::server::UnaryService<super::IngestDocumentParameters>
+                        for IngestDocumentSvc<T>
+                    {
+                        type Response = super::IngestDocumentResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::IngestDocumentParameters>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MemoryService>::ingest_document(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = IngestDocumentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/memory.MemoryService/IngestUrl" => {
+                    #[allow(non_camel_case_types)]
+                    struct IngestUrlSvc<T: MemoryService>(pub Arc<T>);
+                    impl<T: MemoryService> tonic::server::UnaryService<super::IngestUrlParameters> for IngestUrlSvc<T> {
+                        type Response = super::IngestDocumentResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::IngestUrlParameters>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MemoryService>::ingest_url(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = IngestUrlSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/memory.MemoryService/UpdateMemory" => {
                     #[allow(non_camel_case_types)]
                     struct UpdateMemorySvc<T: MemoryService>(pub Arc<T>);
@@ -511,6 +1007,48 @@ pub mod memory_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/memory.MemoryService/DeleteDocument" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeleteDocumentSvc<T: MemoryService>(pub Arc<T>);
+                    impl<T: MemoryService>
+                        tonic::server::UnaryService<super::DeleteDocumentParameters>
+                        for DeleteDocumentSvc<T>
+                    {
+                        type Response = ();
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DeleteDocumentParameters>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MemoryService>::delete_document(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = DeleteDocumentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/memory.MemoryService/GetMemoriesByQuery" => {
                     #[allow(non_camel_case_types)]
                     struct GetMemoriesByQuerySvc<T: MemoryService>(pub Arc<T>);
@@ -518,7 +1056,7 @@ pub mod memory_service_server {
                         tonic::server::UnaryService<super::GetMemoriesByQueryParameters>
                         for GetMemoriesByQuerySvc<T>
                     {
-                        type Response = super::MemoryBulk;
+                        type Response = super::MemorySearchResponse;
                         type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
                         fn call(
                             &mut self,
@@ -595,6 +1133,85 @@ pub mod memory_service_server {
                     };
                     Box::pin(fut)
                 }
+                "/memory.MemoryService/RecallContext" => {
+                    #[allow(non_camel_case_types)]
+                    struct RecallContextSvc<T: MemoryService>(pub Arc<T>);
+                    impl<T: MemoryService>
+                        tonic::server::UnaryService<super::RecallContextParameters>
+                        for RecallContextSvc<T>
+                    {
+                        type Response = super::MemoryContext;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RecallContextParameters>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MemoryService>::recall_context(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = RecallContextSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/memory.MemoryService/GetEmbeddingCacheStats" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetEmbeddingCacheStatsSvc<T: MemoryService>(pub Arc<T>);
+                    impl<T: MemoryService> tonic::server::UnaryService<()> for GetEmbeddingCacheStatsSvc<T> {
+                        type Response = super::EmbeddingCacheStats;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(&mut self, request: tonic::Request<()>) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as MemoryService>::get_embedding_cache_stats(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let method = GetEmbeddingCacheStatsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     let mut response = http::Response::new(empty_body());
                     let headers = response.headers_mut();
@@ -3,17 +3,17 @@ use qdrant_client::{
     Payload, Qdrant,
     qdrant::{
         Condition, CreateCollectionBuilder, CreateFieldIndexCollectionBuilder, DeletePointsBuilder,
-        Distance, FieldType, Filter, HnswConfigDiffBuilder, KeywordIndexParamsBuilder, PointId,
-        PointStruct, PointVectors, PointsIdsList, QuantizationType, ScalarQuantizationBuilder,
-        ScrollPointsBuilder, ScrollResponse, SearchPointsBuilder, SearchResponse,
-        SetPayloadPointsBuilder, UpdatePointVectorsBuilder, UpsertPointsBuilder,
-        VectorParamsBuilder,
+        Distance, FieldType, Filter, GetPointsBuilder, HnswConfigDiffBuilder,
+        KeywordIndexParamsBuilder, PointId, PointStruct, PointVectors, PointsIdsList,
+        QuantizationType, RetrievedPoint, ScalarQuantizationBuilder, ScrollPointsBuilder,
+        ScrollResponse, SearchPointsBuilder, SearchResponse, SetPayloadPointsBuilder,
+        UpdatePointVectorsBuilder, UpsertPointsBuilder, VectorParamsBuilder, vector_output,
+        vectors_output::VectorsOptions,
     },
 };
 use serde::Serialize;
 use serde_json::json;
 use umem_utils::QdrantIdentifiable;
-use uuid::Uuid;
 
 pub struct QdrantVectorStore {
     client: Qdrant,
@@ -22,40 +22,82 @@ pub struct QdrantVectorStore {
 
 impl QdrantVectorStore {
     pub async fn new(url: &str, api_key: &str, collection_name: &str) -> Result<Self> {
+        Self::open(
+            url,
+            api_key,
+            CreateCollectionBuilder::new(collection_name)
+                .vectors_config(VectorParamsBuilder::new(1024, Distance::Cosine))
+                .hnsw_config(HnswConfigDiffBuilder::default().payload_m(16).m(0))
+                .quantization_config(
+                    ScalarQuantizationBuilder::default()
+                        .r#type(QuantizationType::Int8.into())
+                        .always_ram(true),
+                ),
+            &["document_id"],
+        )
+        .await
+    }
+
+    /// Opens a collection of payload-only records, such as documents, that are looked
+    /// up by id or payload but never searched by vector.
+    pub async fn new_record_store(url: &str, api_key: &str, collection_name: &str) -> Result<Self> {
+        Self::open(
+            url,
+            api_key,
+            CreateCollectionBuilder::new(collection_name)
+                .vectors_config(VectorParamsBuilder::new(1, Distance::Dot)),
+            &[],
+        )
+        .await
+    }
+
+    /// Creates the collection on first use, with a tenant index on `user_id` and keyword
+    /// indexes on `keyword_fields`.
+    async fn open(
+        url: &str,
+        api_key: &str,
+        collection: CreateCollectionBuilder,
+        keyword_fields: &[&str],
+    ) -> Result<Self> {
         let client = Qdrant::from_url(url).api_key(api_key).build()?;
+        let collection = collection.build();
+        let collection_name = collection.collection_name.clone();
 
-        if !client.collection_exists(collection_name).await? {
-            client
-                .create_collection(
-                    CreateCollectionBuilder::new(collection_name)
-                        .vectors_config(VectorParamsBuilder::new(1024, Distance::Cosine))
-                        .hnsw_config(HnswConfigDiffBuilder::default().payload_m(16).m(0))
-                        .quantization_config(
-                            ScalarQuantizationBuilder::default()
-                                .r#type(QuantizationType::Int8.into())
-                                .always_ram(true),
-                        ),
-                )
-                .await?;
+        if !client.collection_exists(&collection_name).await? {
+            client.create_collection(collection).await?;
 
             client
                 .create_field_index(
                     CreateFieldIndexCollectionBuilder::new(
-                        collection_name,
+                        &collection_name,
                         "user_id",
                         FieldType::Keyword,
                     )
                     .field_index_params(KeywordIndexParamsBuilder::default().is_tenant(true)),
                 )
                 .await?;
+            for field in keyword_fields {
+                client
+                    .create_field_index(CreateFieldIndexCollectionBuilder::new(
+                        &collection_name,
+                        *field,
+                        FieldType::Keyword,
+                    ))
+                    .await?;
+            }
         }
 
         Ok(QdrantVectorStore {
             client,
-            collection_name: collection_name.to_string(),
+            collection_name,
         })
     }
 
+    pub async fn health_check(&self) -> Result<()> {
+        self.client.health_check().await?;
+        Ok(())
+    }
+
     pub async fn insert_embedding<S: Serialize + QdrantIdentifiable>(
         &self,
         payload: S,
@@ -66,36 +108,33 @@ impl QdrantVectorStore {
         self.client
             .upsert_points(UpsertPointsBuilder::new(
                 self.collection_name.as_str(),
-                [PointStruct::new(
-                    PointId::from(point_id.into()),
-                    vectors,
-                    payload,
-                )],
+                [PointStruct::new(point_id.into(), vectors, payload)],
             ))
             .await?;
         Ok(())
     }
 
-    pub async fn insert_embeddings_bulk<S: Serialize>(
+    /// Stores `payload` in a collection opened with [`Self::new_record_store`].
+    pub async fn insert_record<S: Serialize + QdrantIdentifiable>(&self, payload: S) -> Result<()> {
+        self.insert_embedding(payload, vec![1.0]).await
+    }
+
+    pub async fn insert_embeddings_bulk<S: Serialize + QdrantIdentifiable>(
         &self,
         points: Vec<(S, Vec<f32>)>,
     ) -> Result<()> {
+        let points = points
+            .into_iter()
+            .map(|(payload, vectors)| {
+                let point_id = payload.get_id();
+                let payload = Payload::try_from(json!(payload))?;
+                Ok(PointStruct::new(point_id.into(), vectors, payload))
+            })
+            .collect::<Result<Vec<_>>>()?;
         self.client
             .upsert_points(UpsertPointsBuilder::new(
                 self.collection_name.as_str(),
-                points
-                    .into_iter()
-                    .map(|(payload, vectors)| {
-                        let mut payload =
-                            Payload::try_from(json!(payload)).expect("Couldn't parse to payload.");
-                        let uuid = Uuid::new_v4().to_string();
-                        payload.insert("memory_id", uuid.as_str());
-                        let now = chrono::prelude::Utc::now().timestamp();
-                        payload.insert("created_at", now);
-                        payload.insert("updated_at", now);
-                        PointStruct::new(PointId::from(uuid.as_str()), vectors, payload)
-                    })
-                    .collect::<Vec<_>>(),
+                points,
             ))
             .await?;
         Ok(())
@@ -146,6 +185,100 @@ impl QdrantVectorStore {
         Ok(search_result)
     }
 
+    /// One page of the points whose payload matches all `payload` fields. The next
+    /// page starts at the `next_page_offset` of this one, passed as `offset`.
+    pub async fn scroll_with_payload(
+        &self,
+        payload: Vec<(String, String)>,
+        limit: u32,
+        offset: Option<PointId>,
+    ) -> Result<ScrollResponse> {
+        let mut request = ScrollPointsBuilder::new(self.collection_name.as_str())
+            .filter(Filter::must(
+                payload
+                    .into_iter()
+                    .map(|(field, value)| Condition::matches(field, value)),
+            ))
+            .limit(limit)
+            .with_payload(true)
+            .with_vectors(false);
+        if let Some(offset) = offset {
+            request = request.offset(offset);
+        }
+
+        Ok(self.client.scroll(request).await?)
+    }
+
+    pub async fn get_point(&self, id: &str) -> Result<Option<RetrievedPoint>> {
+        let response = self
+            .client
+            .get_points(
+                GetPointsBuilder::new(self.collection_name.as_str(), vec![id.into()])
+                    .with_payload(true)
+                    .with_vectors(false),
+            )
+            .await?;
+
+        Ok(response.result.into_iter().next())
+    }
+
+    /// Fetches the points that exist among `ids`, in no particular order.
+    pub async fn get_points(&self, ids: Vec<&str>) -> Result<Vec<RetrievedPoint>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let response = self
+            .client
+            .get_points(
+                GetPointsBuilder::new(
+                    self.collection_name.as_str(),
+                    ids.into_iter().map(PointId::from).collect::<Vec<_>>(),
+                )
+                .with_payload(true)
+                .with_vectors(false),
+            )
+            .await?;
+
+        Ok(response.result)
+    }
+
+    /// Fetches the points that exist among `ids` with their dense vectors, in no
+    /// particular order.
+    pub async fn get_points_with_vectors(
+        &self,
+        ids: Vec<&str>,
+    ) -> Result<Vec<(RetrievedPoint, Vec<f32>)>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let response = self
+            .client
+            .get_points(
+                GetPointsBuilder::new(
+                    self.collection_name.as_str(),
+                    ids.into_iter().map(PointId::from).collect::<Vec<_>>(),
+                )
+                .with_payload(true)
+                .with_vectors(true),
+            )
+            .await?;
+
+        Ok(response
+            .result
+            .into_iter()
+            .filter_map(|mut point| {
+                let vector = match point.vectors.take()?.vectors_options? {
+                    VectorsOptions::Vector(output) => match output.vector {
+                        Some(vector_output::Vector::Dense(dense)) => dense.data,
+                        _ => output.data,
+                    },
+                    VectorsOptions::Vectors(_) => return None,
+                };
+                Some((point, vector))
+            })
+            .collect())
+    }
+
     pub async fn delete_point(&self, id: &str) -> Result<()> {
         self.client
             .delete_points(
@@ -174,6 +307,43 @@ impl QdrantVectorStore {
         Ok(())
     }
 
+    /// Deletes every point whose payload matches all `payload` fields.
+    pub async fn delete_points_by_payload(&self, payload: Vec<(String, String)>) -> Result<()> {
+        self.client
+            .delete_points(
+                DeletePointsBuilder::new(self.collection_name.as_str())
+                    .points(Filter::must(
+                        payload
+                            .into_iter()
+                            .map(|(field, value)| Condition::matches(field, value)),
+                    ))
+                    .wait(true),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Sets the fields of `payload` on every point among `ids`, keeping their others.
+    pub async fn set_payload_bulk<S: Serialize>(&self, ids: Vec<&str>, payload: S) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        let mut payload = Payload::try_from(json!(payload))?;
+        payload.insert("updated_at", chrono::prelude::Utc::now().timestamp());
+        self.client
+            .set_payload(
+                SetPayloadPointsBuilder::new(self.collection_name.as_str(), payload)
+                    .points_selector(PointsIdsList {
+                        ids: ids.into_iter().map(PointId::from).collect(),
+                    })
+                    .wait(true),
+            )
+            .await?;
+
+        Ok(())
+    }
+
     pub async fn update_point<S: Serialize>(
         &self,
         id: &str,
@@ -0,0 +1,556 @@
+use crate::robots::Robots;
+use crate::{links, read_text, read_text_prefix};
+use anyhow::{bail, Result};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Client, Response};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Sent with every crawler request so site owners can tell the crawler apart and
+/// address it in robots.txt.
+pub const DEFAULT_USER_AGENT: &str = concat!("umem-crawler/", env!("CARGO_PKG_VERSION"));
+
+/// Links to files like these are never fetched as pages.
+const SKIPPED_EXTENSIONS: &[&str] = &[
+    "7z", "avi", "css", "csv", "dmg", "doc", "docx", "exe", "gif", "gz", "ico", "jpeg", "jpg",
+    "js", "mov", "mp3", "mp4", "pdf", "png", "ppt", "pptx", "rar", "svg", "tar", "tgz", "webm",
+    "webp", "woff", "woff2", "xls", "xlsx", "zip",
+];
+
+/// Pages and sitemaps longer than this are skipped with an error.
+const MAX_PAGE_BYTES: usize = 16 * 1024 * 1024;
+/// Robots.txt content past this is ignored, as RFC 9309 allows past 500 KiB.
+const MAX_ROBOTS_BYTES: usize = 500 * 1024;
+
+/// Which links a crawl follows, relative to its seed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CrawlScope {
+    /// The host of the seed.
+    Host,
+    /// The host of the seed and its subdomains, ignoring a leading `www.`.
+    Domain,
+    /// URLs under the directory of the seed, such as everything below `/docs/`.
+    #[default]
+    Prefix,
+}
+
+#[derive(Clone, Debug)]
+pub struct CrawlOptions {
+    /// Links followed away from the seed, or from the pages a sitemap lists.
+    pub max_depth: usize,
+    /// Pages yielded before the crawl stops.
+    pub max_pages: usize,
+    pub scope: CrawlScope,
+    /// Least time between two requests to one host. A longer robots.txt `Crawl-delay`
+    /// takes precedence, up to `max_crawl_delay`.
+    pub delay: Duration,
+    /// Longest `Crawl-delay` obeyed, so a site cannot stall the crawl indefinitely.
+    pub max_crawl_delay: Duration,
+    pub user_agent: String,
+}
+
+impl Default for CrawlOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_pages: 100,
+            scope: CrawlScope::default(),
+            delay: Duration::from_secs(1),
+            max_crawl_delay: Duration::from_secs(60),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CrawledPage {
+    /// Where the page was fetched from, after redirects.
+    pub url: Url,
+    /// The canonical URL the page declares, or `url`.
+    pub canonical_url: Url,
+    /// Links followed from the seed to reach the page.
+    pub depth: usize,
+    pub html: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Page,
+    Sitemap,
+}
+
+/// A breadth-first crawl that obeys robots.txt, waits between requests to the same
+/// host and yields every in-scope HTML page once, by canonical URL.
+pub struct Crawler {
+    options: CrawlOptions,
+    client: Client,
+    seed: Url,
+    prefix: String,
+    frontier: VecDeque<(Url, usize, Target)>,
+    /// URLs queued or yielded, without fragments.
+    seen: HashSet<String>,
+    robots: HashMap<String, Robots>,
+    last_request: HashMap<String, Instant>,
+    yielded: usize,
+}
+
+impl Crawler {
+    /// Starts at `seed`, a page or, when its path ends in `.xml`, a sitemap or sitemap
+    /// index whose pages are crawled from depth 0.
+    pub fn new(seed: &str, options: CrawlOptions) -> Result<Self> {
+        let mut seed = Url::parse(seed)?;
+        if !matches!(seed.scheme(), "http" | "https") {
+            bail!("Only http and https URLs can be crawled");
+        }
+        seed.set_fragment(None);
+        let client = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .user_agent(&options.user_agent)
+            .build()?;
+
+        let mut prefix = seed.clone();
+        prefix.set_query(None);
+        let directory = seed.path()[..=seed.path().rfind('/').unwrap_or(0)].to_string();
+        prefix.set_path(&directory);
+
+        let target = if seed.path().ends_with(".xml") {
+            Target::Sitemap
+        } else {
+            Target::Page
+        };
+        Ok(Self {
+            options,
+            client,
+            prefix: prefix.to_string(),
+            frontier: VecDeque::from([(seed.clone(), 0, target)]),
+            seen: HashSet::from([seed.to_string()]),
+            seed,
+            robots: HashMap::new(),
+            last_request: HashMap::new(),
+            yielded: 0,
+        })
+    }
+
+    /// Fetches until the next page to ingest. A page that fails to load is returned
+    /// as an error, and the crawl goes on with the next call. Returns `None` once the
+    /// frontier is empty or `max_pages` pages were yielded.
+    pub async fn next_page(&mut self) -> Option<Result<CrawledPage>> {
+        while self.yielded < self.options.max_pages {
+            let (url, depth, target) = self.frontier.pop_front()?;
+            match self.visit(url, depth, target).await {
+                Ok(Some(page)) => {
+                    self.yielded += 1;
+                    return Some(Ok(page));
+                }
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+
+    async fn visit(
+        &mut self,
+        url: Url,
+        depth: usize,
+        target: Target,
+    ) -> Result<Option<CrawledPage>> {
+        if !self.robots(&url).await.is_allowed(&path_and_query(&url)) {
+            return Ok(None);
+        }
+        let response = self.fetch(&url).await?;
+        let status = response.status();
+        if !status.is_success() {
+            bail!("{url} returned with status-code {status}");
+        }
+        let mut final_url = response.url().clone();
+        final_url.set_fragment(None);
+        if final_url != url
+            && (!self.in_scope(&final_url) || !self.seen.insert(final_url.to_string()))
+        {
+            return Ok(None);
+        }
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_ascii_lowercase()
+        };
+        let content_type = header(CONTENT_TYPE.as_str());
+        let robots_tag = header("x-robots-tag");
+
+        if target == Target::Sitemap {
+            let xml = read_text(response, MAX_PAGE_BYTES).await?;
+            let nested = if xml.contains("<sitemapindex") {
+                Target::Sitemap
+            } else {
+                Target::Page
+            };
+            for location in sitemap_locations(&xml) {
+                if let Ok(location) = final_url.join(&location) {
+                    self.enqueue(location, 0, nested);
+                }
+            }
+            return Ok(None);
+        }
+        if !content_type.is_empty() && !content_type.contains("html") {
+            return Ok(None);
+        }
+
+        let html = read_text(response, MAX_PAGE_BYTES).await?;
+        let page = links::scan(&html, &final_url);
+        let nofollow = page.nofollow || robots_tag.contains("nofollow");
+        if depth < self.options.max_depth && !nofollow {
+            for link in page.links {
+                self.enqueue(link, depth + 1, Target::Page);
+            }
+        }
+        if page.noindex || robots_tag.contains("noindex") {
+            return Ok(None);
+        }
+        let canonical_url = page
+            .canonical
+            .map(|mut canonical| {
+                canonical.set_fragment(None);
+                canonical
+            })
+            .filter(|canonical| self.in_scope(canonical))
+            .unwrap_or_else(|| final_url.clone());
+        if canonical_url != final_url && !self.seen.insert(canonical_url.to_string()) {
+            // Another URL of the same page was or will be yielded.
+            return Ok(None);
+        }
+        Ok(Some(CrawledPage {
+            url: final_url,
+            canonical_url,
+            depth,
+            html,
+        }))
+    }
+
+    fn enqueue(&mut self, mut url: Url, depth: usize, target: Target) {
+        url.set_fragment(None);
+        let skipped = target == Target::Page
+            && url.path().rsplit_once('.').is_some_and(|(_, extension)| {
+                SKIPPED_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+            });
+        if !skipped && self.in_scope(&url) && self.seen.insert(url.to_string()) {
+            self.frontier.push_back((url, depth, target));
+        }
+    }
+
+    fn in_scope(&self, url: &Url) -> bool {
+        if !matches!(url.scheme(), "http" | "https") {
+            return false;
+        }
+        match self.options.scope {
+            CrawlScope::Host => {
+                url.host_str() == self.seed.host_str()
+                    && url.port_or_known_default() == self.seed.port_or_known_default()
+            }
+            CrawlScope::Domain => {
+                let domain = self.seed.host_str().unwrap_or_default();
+                let domain = domain.strip_prefix("www.").unwrap_or(domain);
+                url.host_str().is_some_and(|host| {
+                    host == domain
+                        || host
+                            .strip_suffix(domain)
+                            .is_some_and(|subdomain| subdomain.ends_with('.'))
+                })
+            }
+            CrawlScope::Prefix => url.as_str().starts_with(&self.prefix),
+        }
+    }
+
+    /// The robots.txt rules of the origin of `url`, fetched on first use. A missing
+    /// file allows everything, and one that cannot be fetched disallows everything.
+    async fn robots(&mut self, url: &Url) -> &Robots {
+        let origin = url.origin().ascii_serialization();
+        if !self.robots.contains_key(&origin) {
+            let robots = match url.join("/robots.txt") {
+                Ok(robots_url) => match self.fetch(&robots_url).await {
+                    Ok(response) if response.status().is_success() => {
+                        match read_text_prefix(response, MAX_ROBOTS_BYTES).await {
+                            Ok((text, _)) => Robots::parse(&text, &self.options.user_agent),
+                            Err(_) => Robots::disallow_all(),
+                        }
+                    }
+                    Ok(response) if response.status().is_client_error() => Robots::allow_all(),
+                    _ => Robots::disallow_all(),
+                },
+                Err(_) => Robots::disallow_all(),
+            };
+            self.robots.insert(origin.clone(), robots);
+        }
+        &self.robots[&origin]
+    }
+
+    /// Sends a GET once the host has had its delay since the previous request.
+    async fn fetch(&mut self, url: &Url) -> reqwest::Result<Response> {
+        let origin = url.origin().ascii_serialization();
+        let delay = self
+            .robots
+            .get(&origin)
+            .and_then(Robots::crawl_delay)
+            .map_or(self.options.delay, |delay| {
+                delay
+                    .min(self.options.max_crawl_delay)
+                    .max(self.options.delay)
+            });
+        if let Some(last) = self.last_request.get(&origin) {
+            tokio::time::sleep_until((*last + delay).into()).await;
+        }
+        self.last_request.insert(origin, Instant::now());
+        self.client.get(url.clone()).send().await
+    }
+}
+
+fn path_and_query(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    }
+}
+
+/// Contents of the `<loc>` elements of a sitemap or sitemap index.
+fn sitemap_locations(xml: &str) -> Vec<String> {
+    xml.split("<loc>")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("</loc>"))
+        .map(|(location, _)| {
+            location
+                .trim()
+                .trim_start_matches("<![CDATA[")
+                .trim_end_matches("]]>")
+                .replace("&amp;", "&")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture::{FixtureResponse, FixtureServer};
+
+    fn page(links: &[&str], head: &str) -> FixtureResponse {
+        let links = links
+            .iter()
+            .map(|href| format!("<a href=\"{href}\">{href}</a>"))
+            .collect::<String>();
+        FixtureResponse::html(&format!(
+            "<html><head>{head}</head><body>{links}</body></html>"
+        ))
+    }
+
+    fn options() -> CrawlOptions {
+        CrawlOptions {
+            delay: Duration::ZERO,
+            ..Default::default()
+        }
+    }
+
+    async fn crawl(seed: &str, options: CrawlOptions) -> Vec<CrawledPage> {
+        let mut crawler = Crawler::new(seed, options).unwrap();
+        let mut pages = Vec::new();
+        while let Some(page) = crawler.next_page().await {
+            pages.push(page.unwrap());
+        }
+        pages
+    }
+
+    #[tokio::test]
+    async fn test_crawl_obeys_robots_scope_depth_and_canonical_urls() {
+        let server = FixtureServer::start().await;
+        server
+            .route(
+                "/robots.txt",
+                FixtureResponse::new(
+                    200,
+                    "text/plain",
+                    "User-agent: *\nDisallow: /docs/private/\n",
+                ),
+            )
+            .route(
+                "/docs/",
+                page(
+                    &[
+                        "intro",
+                        "#top",
+                        "private/secret",
+                        "intro?utm=feed",
+                        "hidden",
+                        "manual.pdf",
+                        "/blog/",
+                        "https://other.example/docs/",
+                    ],
+                    "",
+                ),
+            )
+            .route(
+                "/docs/intro",
+                page(
+                    &["deep/one"],
+                    r#"<link rel="canonical" href="/docs/intro">"#,
+                ),
+            )
+            .route(
+                "/docs/intro?utm=feed",
+                page(&[], r#"<link rel="canonical" href="/docs/intro">"#),
+            )
+            .route(
+                "/docs/hidden",
+                page(&["after-hidden"], "").header("X-Robots-Tag", "noindex"),
+            )
+            .route("/docs/after-hidden", page(&[], ""))
+            .route("/docs/deep/one", page(&["two"], ""))
+            .route("/docs/deep/two", page(&[], ""));
+
+        let pages = crawl(
+            &server.url("/docs/"),
+            CrawlOptions {
+                max_depth: 2,
+                ..options()
+            },
+        )
+        .await;
+        assert_eq!(
+            pages
+                .iter()
+                .map(|page| (page.url.path(), page.depth))
+                .collect::<Vec<_>>(),
+            vec![
+                ("/docs/", 0),
+                ("/docs/intro", 1),
+                ("/docs/deep/one", 2),
+                ("/docs/after-hidden", 2),
+            ]
+        );
+        let paths = server.paths();
+        for path in [
+            "/docs/private/secret",
+            "/docs/manual.pdf",
+            "/blog/",
+            "/docs/deep/two",
+        ] {
+            assert!(!paths.contains(&path.to_string()), "{path} was fetched");
+        }
+        assert_eq!(
+            paths.iter().filter(|path| *path == "/robots.txt").count(),
+            1
+        );
+        assert!(server
+            .requests()
+            .iter()
+            .all(|request| request.headers["user-agent"] == DEFAULT_USER_AGENT));
+    }
+
+    #[tokio::test]
+    async fn test_sitemaps_seed_the_crawl_at_the_crawl_delay() {
+        let server = FixtureServer::start().await;
+        let urlset = ["/a", "/b", "/c"]
+            .iter()
+            .map(|path| format!("<url><loc>{}</loc></url>", server.url(path)))
+            .collect::<String>();
+        server
+            .route(
+                "/robots.txt",
+                FixtureResponse::new(200, "text/plain", "User-agent: *\nCrawl-delay: 0.2\n"),
+            )
+            .route(
+                "/sitemap.xml",
+                FixtureResponse::new(
+                    200,
+                    "application/xml",
+                    &format!(
+                        "<?xml version=\"1.0\"?><sitemapindex><sitemap><loc>{}</loc></sitemap></sitemapindex>",
+                        server.url("/pages.xml")
+                    ),
+                ),
+            )
+            .route(
+                "/pages.xml",
+                FixtureResponse::new(200, "application/xml", &format!("<urlset>{urlset}</urlset>")),
+            )
+            .route("/a", page(&["/linked"], ""))
+            .route("/b", page(&[], ""))
+            .route("/c", page(&[], ""));
+
+        let pages = crawl(
+            &server.url("/sitemap.xml"),
+            CrawlOptions {
+                max_depth: 0,
+                max_pages: 2,
+                scope: CrawlScope::Host,
+                ..options()
+            },
+        )
+        .await;
+        assert_eq!(
+            pages.iter().map(|page| page.url.path()).collect::<Vec<_>>(),
+            vec!["/a", "/b"]
+        );
+        let requests = server.requests();
+        assert_eq!(
+            requests
+                .iter()
+                .map(|request| request.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/robots.txt", "/sitemap.xml", "/pages.xml", "/a", "/b"]
+        );
+        for pair in requests.windows(2) {
+            let gap = pair[1].received_at - pair[0].received_at;
+            assert!(
+                gap >= Duration::from_millis(190),
+                "{gap:?} between requests"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_crawl_delay_is_capped() {
+        let server = FixtureServer::start().await;
+        server
+            .route(
+                "/robots.txt",
+                FixtureResponse::new(200, "text/plain", "User-agent: *\nCrawl-delay: 86400\n"),
+            )
+            .route("/", page(&["/next"], ""))
+            .route("/next", page(&[], ""));
+
+        let started = Instant::now();
+        let pages = crawl(
+            &server.url("/"),
+            CrawlOptions {
+                max_crawl_delay: Duration::from_millis(50),
+                ..options()
+            },
+        )
+        .await;
+        assert_eq!(pages.len(), 2);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_scopes() {
+        let crawler =
+            |seed: &str, scope| Crawler::new(seed, CrawlOptions { scope, ..options() }).unwrap();
+        let url = |url: &str| Url::parse(url).unwrap();
+
+        let prefix = crawler("https://example.com/docs/intro?x=1", CrawlScope::Prefix);
+        assert!(prefix.in_scope(&url("https://example.com/docs/guide")));
+        assert!(!prefix.in_scope(&url("https://example.com/blog/")));
+
+        let host = crawler("https://example.com/docs/", CrawlScope::Host);
+        assert!(host.in_scope(&url("https://example.com/blog/")));
+        assert!(!host.in_scope(&url("https://docs.example.com/")));
+
+        let domain = crawler("https://www.example.com/", CrawlScope::Domain);
+        assert!(domain.in_scope(&url("https://docs.example.com/")));
+        assert!(domain.in_scope(&url("http://example.com/")));
+        assert!(!domain.in_scope(&url("https://badexample.com/")));
+        assert!(!domain.in_scope(&url("mailto:team@example.com")));
+    }
+}
@@ -0,0 +1,177 @@
+//! A local HTTP server answering tests with canned responses, so tests of fetching
+//! code run offline. Other crates get it with the `test-fixtures` feature.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[derive(Clone, Debug)]
+pub struct FixtureResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl FixtureResponse {
+    pub fn html(body: &str) -> Self {
+        Self::new(200, "text/html; charset=utf-8", body)
+    }
+
+    pub fn new(status: u16, content_type: &str, body: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.to_string(),
+        }
+    }
+
+    /// A redirect to `location` with the given 3xx `status`.
+    pub fn redirect(status: u16, location: &str) -> Self {
+        Self::new(status, "text/plain", "").header("Location", location)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Pages saved from real sites, trimmed to what the tests look for.
+pub mod samples {
+    pub const BOOKS_HOMEPAGE: &str = include_str!("../fixtures/books_homepage.html");
+    pub const BOOK_PAGE: &str = include_str!("../fixtures/book_page.html");
+    pub const QUOTES_HOMEPAGE: &str = include_str!("../fixtures/quotes_homepage.html");
+    pub const OPENLIBRARY_HOMEPAGE: &str = include_str!("../fixtures/openlibrary_homepage.html");
+    pub const WIKIPEDIA_MAIN_PAGE: &str = include_str!("../fixtures/wikipedia_main_page.html");
+    pub const OPENAI_HOMEPAGE: &str = include_str!("../fixtures/openai_homepage.html");
+    /// The interstitial Cloudflare serves while it checks a browser.
+    pub const CLOUDFLARE_CHALLENGE: &str = include_str!("../fixtures/cloudflare_challenge.html");
+}
+
+/// A request the server received.
+#[derive(Clone, Debug)]
+pub struct FixtureRequest {
+    /// Path with query, as sent.
+    pub path: String,
+    /// Header names lowercased.
+    pub headers: HashMap<String, String>,
+    pub received_at: Instant,
+}
+
+pub struct FixtureServer {
+    address: SocketAddr,
+    routes: Arc<Mutex<HashMap<String, VecDeque<FixtureResponse>>>>,
+    requests: Arc<Mutex<Vec<FixtureRequest>>>,
+}
+
+impl FixtureServer {
+    /// Answers 404 until routes are added. The server runs until the test runtime
+    /// shuts down.
+    pub async fn start() -> Self {
+        Self::start_on(IpAddr::V4(Ipv4Addr::LOCALHOST)).await
+    }
+
+    /// Like [`FixtureServer::start`], listening on `ip`, such as another loopback
+    /// address standing in for a public host.
+    pub async fn start_on(ip: IpAddr) -> Self {
+        let listener = TcpListener::bind((ip, 0)).await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let routes: Arc<Mutex<HashMap<String, VecDeque<FixtureResponse>>>> = Arc::default();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let (served, log) = (Arc::clone(&routes), Arc::clone(&requests));
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (routes, log) = (Arc::clone(&served), Arc::clone(&log));
+                tokio::spawn(async move {
+                    let Some(request) = read_request(&mut stream).await else {
+                        return;
+                    };
+                    let response = match routes.lock().unwrap().get_mut(&request.path) {
+                        Some(responses) if responses.len() > 1 => responses.pop_front(),
+                        Some(responses) => responses.front().cloned(),
+                        None => None,
+                    }
+                    .unwrap_or_else(|| FixtureResponse::new(404, "text/plain", "not found"));
+                    log.lock().unwrap().push(request);
+
+                    let mut head = format!(
+                        "HTTP/1.1 {} Fixture\r\nContent-Length: {}\r\nConnection: close\r\n",
+                        response.status,
+                        response.body.len()
+                    );
+                    for (name, value) in &response.headers {
+                        head.push_str(&format!("{name}: {value}\r\n"));
+                    }
+                    head.push_str("\r\n");
+                    let _ = stream.write_all(head.as_bytes()).await;
+                    let _ = stream.write_all(response.body.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        Self {
+            address,
+            routes,
+            requests,
+        }
+    }
+
+    /// Serves `response` at `path`, with its query, replacing what was served there.
+    pub fn route(&self, path: &str, response: FixtureResponse) -> &Self {
+        self.route_sequence(path, vec![response])
+    }
+
+    /// Serves `responses` at `path` one request after another, repeating the last.
+    pub fn route_sequence(&self, path: &str, responses: Vec<FixtureResponse>) -> &Self {
+        self.routes
+            .lock()
+            .unwrap()
+            .insert(path.to_string(), responses.into());
+        self
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.address)
+    }
+
+    pub fn requests(&self) -> Vec<FixtureRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Paths requested so far, in order.
+    pub fn paths(&self) -> Vec<String> {
+        self.requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect()
+    }
+}
+
+async fn read_request(stream: &mut tokio::net::TcpStream) -> Option<FixtureRequest> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 1024];
+    while !buffer.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+    let head = String::from_utf8_lossy(&buffer);
+    let mut lines = head.lines();
+    let path = lines.next()?.split_whitespace().nth(1)?.to_string();
+    let headers = lines
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+    Some(FixtureRequest {
+        path,
+        headers,
+        received_at: Instant::now(),
+    })
+}
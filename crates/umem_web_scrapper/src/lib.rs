@@ -1,40 +1,206 @@
-use anyhow::{bail, Result};
+mod crawler;
+#[cfg(any(test, feature = "test-fixtures"))]
+pub mod fixture;
+mod links;
+mod robots;
+
+pub use crawler::{CrawlOptions, CrawlScope, CrawledPage, Crawler, DEFAULT_USER_AGENT};
+pub use robots::Robots;
+
+use anyhow::{anyhow, bail, Context, Result};
 use lazy_static::lazy_static;
 use reqwest::cookie::Jar;
-use reqwest::Client;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::header::{CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, Response, StatusCode};
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
+use url::{Host, Url};
 
 pub struct Scrapper;
 
+const MAX_REDIRECTS: usize = 10;
+/// Pages longer than this are refused rather than read into memory.
+const MAX_PAGE_BYTES: usize = 16 * 1024 * 1024;
+
 lazy_static! {
     static ref jar: Arc<Jar> = Arc::new(Jar::default());
-    static ref client: Client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-            .cookie_provider(Arc::clone(&jar))
-            .cookie_store(true)
-            .build()
-            .expect("Failed to create HTTP client");
+    static ref client: Client = builder().build().expect("Failed to create HTTP client");
+    static ref public_client: Client = restrict_to(builder(), is_public)
+        .build()
+        .expect("Failed to create HTTP client");
+}
+
+fn builder() -> ClientBuilder {
+    Client::builder()
+        .timeout(Duration::from_secs(30))
+        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
+        .cookie_provider(Arc::clone(&jar))
+        .cookie_store(true)
+}
+
+/// Makes `builder` refuse every connection and redirect to an address `allowed`
+/// rejects. Host names are checked by the resolver whose addresses the connection then
+/// uses, so a second lookup cannot swap in another address, and address literals by
+/// the redirect policy.
+fn restrict_to(builder: ClientBuilder, allowed: fn(IpAddr) -> bool) -> ClientBuilder {
+    builder
+        // A proxy would resolve the host itself.
+        .no_proxy()
+        .dns_resolver(Arc::new(CheckedResolver { allowed }))
+        .redirect(Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(anyhow!("Too many redirects"));
+            }
+            match check_url(attempt.url(), allowed) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        }))
+}
+
+/// Rejects `url` unless it is http(s) and, when its host is an address, `allowed` lets
+/// it through. Host names are left to [`CheckedResolver`].
+fn check_url(url: &Url, allowed: fn(IpAddr) -> bool) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        bail!("Only http and https URLs can be fetched");
+    }
+    let ip = match url.host().context("URL has no host")? {
+        Host::Ipv4(ip) => IpAddr::V4(ip),
+        Host::Ipv6(ip) => IpAddr::V6(ip),
+        Host::Domain(_) => return Ok(()),
+    };
+    if !allowed(ip) {
+        bail!("{url} points at the non-public address {ip}");
+    }
+    Ok(())
+}
+
+/// Resolves host names with the system resolver and fails the lookup when any address
+/// is not `allowed`.
+struct CheckedResolver {
+    allowed: fn(IpAddr) -> bool,
+}
+
+impl Resolve for CheckedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allowed = self.allowed;
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addresses = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .collect::<Vec<SocketAddr>>();
+            if let Some(address) = addresses.iter().find(|address| !allowed(address.ip())) {
+                return Err(
+                    anyhow!("{host} resolves to the non-public address {}", address.ip()).into(),
+                );
+            }
+            Ok(Box::new(addresses.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ScrapeOptions {
+    /// How long a bot check is given to let the client through before the page is
+    /// fetched again.
+    pub challenge_retry_delay: Duration,
+}
+
+impl Default for ScrapeOptions {
+    fn default() -> Self {
+        Self {
+            challenge_retry_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// What a server sent to let a page be fetched conditionally later.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fetched {
+    /// The server answered 304: the page is as it was when `Validators` were taken.
+    NotModified,
+    Modified {
+        html: String,
+        validators: Validators,
+    },
 }
 
 impl Scrapper {
     pub async fn scrape(url: &str) -> Result<String> {
-        let html = Self::scrape_callback(url).await?;
-        if html.contains("checking your browser")
-            || html.contains("cloudflare")
-            || html.contains("Please wait")
-            || html.contains("ray id")
-        {
-            tokio::time::sleep(Duration::from_secs(5)).await;
-            return Ok(Self::scrape_callback(url).await?);
+        Self::scrape_with_options(url, &ScrapeOptions::default()).await
+    }
+
+    pub async fn scrape_with_options(url: &str, options: &ScrapeOptions) -> Result<String> {
+        match Self::scrape_with(&client, url, &Validators::default(), options).await? {
+            Fetched::Modified { html, .. } => Ok(html),
+            Fetched::NotModified => bail!("{url} answered 304 Not Modified unasked"),
+        }
+    }
+
+    /// Fetches `url` with `If-None-Match` and `If-Modified-Since` taken from `previous`,
+    /// so servers can answer that the page did not change instead of sending it again.
+    pub async fn scrape_if_changed(url: &str, previous: &Validators) -> Result<Fetched> {
+        Self::scrape_with(&client, url, previous, &ScrapeOptions::default()).await
+    }
+
+    /// Like [`Scrapper::scrape_if_changed`], but only connects to public addresses,
+    /// including at every redirect, so URLs supplied by users cannot reach internal
+    /// services.
+    pub async fn scrape_public_if_changed(url: &str, previous: &Validators) -> Result<Fetched> {
+        Self::scrape_with(&public_client, url, previous, &ScrapeOptions::default()).await
+    }
+
+    async fn scrape_with(
+        http_client: &Client,
+        url: &str,
+        previous: &Validators,
+        options: &ScrapeOptions,
+    ) -> Result<Fetched> {
+        let fetched = Self::fetch(http_client, url, previous).await?;
+        if let Fetched::Modified { html, .. } = &fetched {
+            if is_challenge(html) {
+                tokio::time::sleep(options.challenge_retry_delay).await;
+                return Self::fetch(http_client, url, previous).await;
+            }
         }
+        Ok(fetched)
+    }
 
-        Ok(html)
+    /// Parses `url` and rejects it unless it is http(s) and every address its host
+    /// resolves to is public, for a clear error before fetching it with
+    /// [`Scrapper::scrape_public_if_changed`], which checks again as it connects.
+    pub async fn ensure_public_url(url: &str) -> Result<Url> {
+        let url = Url::parse(url)?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("Only http and https URLs can be fetched");
+        }
+        let port = url.port_or_known_default().unwrap_or(80);
+        let addresses = match url.host().context("URL has no host")? {
+            Host::Ipv4(ip) => vec![IpAddr::V4(ip)],
+            Host::Ipv6(ip) => vec![IpAddr::V6(ip)],
+            Host::Domain(domain) => tokio::net::lookup_host((domain, port))
+                .await
+                .with_context(|| format!("Cannot resolve {domain}"))?
+                .map(|address| address.ip())
+                .collect(),
+        };
+        if let Some(ip) = addresses.into_iter().find(|ip| !is_public(*ip)) {
+            bail!("{url} resolves to the non-public address {ip}");
+        }
+        Ok(url)
     }
 
-    async fn scrape_callback(url: &str) -> Result<String> {
-        let response = client
+    async fn fetch(http_client: &Client, url: &str, previous: &Validators) -> Result<Fetched> {
+        let mut request = http_client
             .get(url)
             .header(
                 "Accept",
@@ -46,101 +212,485 @@ impl Scrapper {
             .header("Sec-Fetch-Dest", "document")
             .header("Sec-Fetch-Mode", "navigate")
             .header("Sec-Fetch-Site", "none")
-            .header("Cache-Control", "max-age=0")
-            .send()
-            .await?;
+            .header("Cache-Control", "max-age=0");
+        if let Some(etag) = &previous.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &previous.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        let response = request.send().await?;
         let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+        if status.is_redirection() {
+            bail!("{url} redirected somewhere that cannot be fetched");
+        }
         if status.is_client_error() || status.is_server_error() {
             bail!("Couldn't be parsed. {url} returned with status-code {status}");
         }
-        let html = response.text().await?;
-        Ok(html)
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+        let validators = Validators {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+        };
+        let html = read_text(response, MAX_PAGE_BYTES).await?;
+        Ok(Fetched::Modified { html, validators })
+    }
+}
+
+/// The body of `response` decoded with the charset it declares, failing when it is
+/// longer than `limit` bytes.
+pub(crate) async fn read_text(response: Response, limit: usize) -> Result<String> {
+    let url = response.url().clone();
+    let (text, truncated) = read_text_prefix(response, limit).await?;
+    if truncated {
+        bail!("{url} is larger than {limit} bytes");
+    }
+    Ok(text)
+}
+
+/// Like [`read_text`], dropping what follows the first `limit` bytes instead of failing.
+/// Also returns whether anything was dropped.
+pub(crate) async fn read_text_prefix(
+    mut response: Response,
+    limit: usize,
+) -> reqwest::Result<(String, bool)> {
+    let encoding = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|content_type| content_type.split_once("charset="))
+        .and_then(|(_, charset)| {
+            encoding_rs::Encoding::for_label(charset.trim_matches(['"', ' ']).as_bytes())
+        })
+        .unwrap_or(encoding_rs::UTF_8);
+    let mut body = Vec::new();
+    let mut truncated = false;
+    while let Some(chunk) = response.chunk().await? {
+        let room = limit - body.len();
+        if chunk.len() > room {
+            body.extend_from_slice(&chunk[..room]);
+            truncated = true;
+            break;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let (text, _, _) = encoding.decode(&body);
+    Ok((text.into_owned(), truncated))
+}
+
+/// Whether `html` is a bot check page, which a later request usually gets past.
+fn is_challenge(html: &str) -> bool {
+    html.contains("checking your browser")
+        || html.contains("cloudflare")
+        || html.contains("Please wait")
+        || html.contains("ray id")
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            let this_network = a == 0; // 0.0.0.0/8, which Linux routes to the local host
+            let shared = a == 100 && (b & 0xc0) == 64; // 100.64.0.0/10
+            let benchmarking = a == 198 && (b & 0xfe) == 18; // 198.18.0.0/15
+            let reserved = a >= 240; // 240.0.0.0/4 and the broadcast address
+            !(this_network
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || shared
+                || benchmarking
+                || reserved)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(ip));
+            }
+            let segments = ip.segments();
+            // ::/96 holds the unspecified, loopback and IPv4-compatible addresses.
+            let ipv4_compatible = segments[..6] == [0; 6];
+            // 64:ff9b::/96 and 64:ff9b:1::/48 translate to IPv4 addresses, private ones too.
+            let nat64 = segments[..2] == [0x64, 0xff9b];
+            let unique_local = (segments[0] & 0xfe00) == 0xfc00;
+            let link_local = (segments[0] & 0xffc0) == 0xfe80;
+            !(ipv4_compatible || nat64 || ip.is_multicast() || unique_local || link_local)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fixture::{samples, FixtureResponse, FixtureServer};
     use anyhow::Result;
 
     async fn assert_contains(url: &str, expected: &[&str]) -> Result<String> {
-        let html = Scrapper::scrape_callback(url).await?;
+        let html = Scrapper::scrape(url).await?;
         for pat in expected {
             assert!(html.contains(pat), "Expected `{}` in {}", pat, url);
         }
         Ok(html)
     }
 
+    #[tokio::test]
+    async fn test_internal_urls_are_rejected() {
+        for url in [
+            "http://127.0.0.1/",
+            "http://10.1.2.3/admin",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://[::1]:8080/",
+            "http://[::ffff:192.168.0.1]/",
+            "http://0.1.2.3/",
+            "http://224.0.0.251/",
+            "http://240.1.2.3/",
+            "http://255.255.255.255/",
+            "http://198.18.0.1/",
+            "http://198.19.255.254/",
+            "http://[ff02::1]/",
+            "http://[64:ff9b::a01:203]/",
+            "http://[64:ff9b:1::a01:203]/",
+            "http://[::10.1.2.3]/",
+            "http://[::]/",
+            "file:///etc/passwd",
+        ] {
+            assert!(Scrapper::ensure_public_url(url).await.is_err(), "{url}");
+        }
+        for url in [
+            "https://93.184.215.14/",
+            "https://198.20.0.1/",
+            "https://[2606:2800:21f:cb07:6820:80da:af6b:8b2c]/",
+        ] {
+            assert!(Scrapper::ensure_public_url(url).await.is_ok(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirects_to_internal_addresses_are_refused() -> Result<()> {
+        // 127.0.0.2 stands in for a public host and 127.0.0.1 for an internal service.
+        fn stand_in_is_public(ip: IpAddr) -> bool {
+            ip == IpAddr::from([127, 0, 0, 2])
+        }
+        let restricted = restrict_to(builder(), stand_in_is_public).build()?;
+        let internal = FixtureServer::start().await;
+        internal.route("/secret", FixtureResponse::html("<p>Internal</p>"));
+        let public = FixtureServer::start_on(IpAddr::from([127, 0, 0, 2])).await;
+        public.route("/page", FixtureResponse::html("<p>Public</p>"));
+        public.route(
+            "/to-address",
+            FixtureResponse::redirect(302, &internal.url("/secret")),
+        );
+        let by_name = internal.url("/secret").replace("127.0.0.1", "localhost");
+        public.route("/to-name", FixtureResponse::redirect(302, &by_name));
+        public.route(
+            "/to-file",
+            FixtureResponse::redirect(302, "file:///etc/passwd"),
+        );
+
+        let page = Scrapper::scrape_with(
+            &restricted,
+            &public.url("/page"),
+            &Validators::default(),
+            &ScrapeOptions::default(),
+        )
+        .await?;
+        assert!(matches!(page, Fetched::Modified { html, .. } if html.contains("Public")));
+        for path in ["/to-address", "/to-name", "/to-file"] {
+            let fetched = Scrapper::scrape_with(
+                &restricted,
+                &public.url(path),
+                &Validators::default(),
+                &ScrapeOptions::default(),
+            )
+            .await;
+            assert!(fetched.is_err(), "{path}");
+        }
+        assert!(internal.requests().is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_long_bodies_are_refused_or_truncated() -> Result<()> {
+        let server = FixtureServer::start().await;
+        server.route(
+            "/latin1",
+            FixtureResponse::new(200, "text/plain; charset=ISO-8859-1", "caf\u{e9} au lait"),
+        );
+        let response = || async { client.get(server.url("/latin1")).send().await };
+
+        assert!(read_text(response().await?, 5).await.is_err());
+        let (text, truncated) = read_text_prefix(response().await?, 4).await?;
+        // The fixture sends UTF-8, two bytes for the é, which Latin-1 reads as two letters.
+        assert_eq!((text.as_str(), truncated), ("caf\u{c3}", true));
+        assert_eq!(
+            read_text(response().await?, 64).await?,
+            "caf\u{c3}\u{a9} au lait"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_validators_make_requests_conditional() -> Result<()> {
+        let server = FixtureServer::start().await;
+        server.route(
+            "/page",
+            FixtureResponse::html("<p>First version</p>")
+                .header("ETag", "\"v1\"")
+                .header("Last-Modified", "Tue, 15 Oct 2024 08:00:00 GMT"),
+        );
+        let Fetched::Modified { html, validators } =
+            Scrapper::scrape_if_changed(&server.url("/page"), &Validators::default()).await?
+        else {
+            panic!("an unconditional request was answered 304");
+        };
+        assert!(html.contains("First version"));
+        assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+
+        server.route("/page", FixtureResponse::new(304, "text/html", ""));
+        let fetched = Scrapper::scrape_if_changed(&server.url("/page"), &validators).await?;
+        assert_eq!(fetched, Fetched::NotModified);
+
+        let requests = server.requests();
+        assert!(!requests[0].headers.contains_key("if-none-match"));
+        assert_eq!(requests[1].headers["if-none-match"], "\"v1\"");
+        assert_eq!(
+            requests[1].headers["if-modified-since"],
+            "Tue, 15 Oct 2024 08:00:00 GMT"
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_books_to_scrape_homepage() -> Result<()> {
-        let url = "https://books.toscrape.com/";
-        assert_contains(url, &["Books to Scrape", "All products"]).await?;
+        let server = FixtureServer::start().await;
+        server.route("/", FixtureResponse::html(samples::BOOKS_HOMEPAGE));
+        assert_contains(&server.url("/"), &["Books to Scrape", "All products"]).await?;
         Ok(())
     }
 
     #[tokio::test]
     async fn test_specific_book_page() -> Result<()> {
-        let url = "https://books.toscrape.com/catalogue/a-light-in-the-attic_1000/index.html";
-        assert_contains(url, &["A Light in the Attic", "In stock"]).await?;
+        let server = FixtureServer::start().await;
+        let path = "/catalogue/a-light-in-the-attic_1000/index.html";
+        server.route(path, FixtureResponse::html(samples::BOOK_PAGE));
+        assert_contains(&server.url(path), &["A Light in the Attic", "In stock"]).await?;
         Ok(())
     }
 
     #[tokio::test]
     async fn test_quotes_to_scrape_homepage() -> Result<()> {
-        let url = "https://quotes.toscrape.com/";
-        assert_contains(url, &["Quotes to Scrape", "Albert Einstein"]).await?;
+        let server = FixtureServer::start().await;
+        server.route("/", FixtureResponse::html(samples::QUOTES_HOMEPAGE));
+        assert_contains(&server.url("/"), &["Quotes to Scrape", "Albert Einstein"]).await?;
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_httpbin_headers_and_user_agent() -> Result<()> {
-        let url = "https://httpbin.org/headers";
-        let html = Scrapper::scrape_callback(url).await?;
-        assert!(html.contains("User-Agent"));
-        assert!(html.contains("Mozilla/5.0")); // from our client UA
+    async fn test_headers_and_user_agent() -> Result<()> {
+        let server = FixtureServer::start().await;
+        server.route(
+            "/headers",
+            FixtureResponse::new(200, "application/json", "{}"),
+        );
+        Scrapper::scrape(&server.url("/headers")).await?;
+        let request = &server.requests()[0];
+        assert!(request.headers["user-agent"].contains("Mozilla/5.0")); // from our client UA
+        assert!(request.headers["accept"].starts_with("text/html"));
+        assert_eq!(request.headers["accept-language"], "en-US,en;q=0.5");
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_httpbin_cookie_roundtrip() -> Result<()> {
-        // Set a cookie
-        let _ =
-            Scrapper::scrape_callback("https://httpbin.org/cookies/set/testkey/testvalue").await?;
-        // Retrieve cookies
-        let html = Scrapper::scrape_callback("https://httpbin.org/cookies").await?;
-        assert!(html.contains("testkey"));
-        assert!(html.contains("testvalue"));
+    async fn test_cookie_roundtrip() -> Result<()> {
+        let server = FixtureServer::start().await;
+        // Set a cookie, then be redirected to where it is read back
+        server.route(
+            "/cookies/set/testkey/testvalue",
+            FixtureResponse::redirect(302, "/cookies")
+                .header("Set-Cookie", "testkey=testvalue; Path=/"),
+        );
+        server.route(
+            "/cookies",
+            FixtureResponse::new(200, "application/json", "{}"),
+        );
+        Scrapper::scrape(&server.url("/cookies/set/testkey/testvalue")).await?;
+        Scrapper::scrape(&server.url("/cookies")).await?;
+
+        let requests = server.requests();
+        assert_eq!(
+            server.paths(),
+            ["/cookies/set/testkey/testvalue", "/cookies", "/cookies"]
+        );
+        for request in &requests[1..] {
+            assert!(request.headers["cookie"].contains("testkey=testvalue"));
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redirects_are_followed() -> Result<()> {
+        let server = FixtureServer::start().await;
+        server.route("/old", FixtureResponse::redirect(301, "/moved"));
+        server.route("/moved", FixtureResponse::redirect(307, "/current"));
+        server.route("/current", FixtureResponse::html(samples::BOOK_PAGE));
+        assert_contains(&server.url("/old"), &["A Light in the Attic"]).await?;
+        assert_eq!(server.paths(), ["/old", "/moved", "/current"]);
+
+        server.route("/gone", FixtureResponse::redirect(302, "/missing"));
+        assert!(Scrapper::scrape(&server.url("/gone")).await.is_err());
         Ok(())
     }
 
     #[tokio::test]
     async fn test_openlibrary_homepage() -> Result<()> {
-        let url = "https://openlibrary.org/";
-        assert_contains(url, &["Open Library", "Borrow"]).await?;
+        let server = FixtureServer::start().await;
+        server.route("/", FixtureResponse::html(samples::OPENLIBRARY_HOMEPAGE));
+        assert_contains(&server.url("/"), &["Open Library", "Borrow"]).await?;
         Ok(())
     }
 
     #[tokio::test]
     async fn test_wikipedia_main_page() -> Result<()> {
-        let url = "https://en.wikipedia.org/wiki/Main_Page";
-        let html = Scrapper::scrape_callback(url).await?;
+        let server = FixtureServer::start().await;
+        server.route(
+            "/wiki/Main_Page",
+            FixtureResponse::html(samples::WIKIPEDIA_MAIN_PAGE),
+        );
+        let html = Scrapper::scrape(&server.url("/wiki/Main_Page")).await?;
         assert!(html.contains("Wikipedia"));
         assert!(html.contains("From today"));
         Ok(())
     }
 
     #[tokio::test]
-    async fn test_404_results_in_error() {
-        let url = "https://httpbin.org/status/404";
-        let res = Scrapper::scrape_callback(url).await;
-        assert!(res.is_err());
+    async fn test_error_statuses_result_in_error() {
+        let server = FixtureServer::start().await;
+        for status in [400, 403, 404, 429, 500, 503] {
+            let path = format!("/status/{status}");
+            server.route(&path, FixtureResponse::new(status, "text/plain", "failed"));
+            let res = Scrapper::scrape(&server.url(&path)).await;
+            assert!(res.is_err(), "{status}");
+        }
+        // Unrouted paths answer 404
+        assert!(Scrapper::scrape(&server.url("/nowhere")).await.is_err());
     }
 
     #[tokio::test]
     async fn test_open_ai() -> Result<()> {
-        let url = "https://openai.com";
-        let html = Scrapper::scrape_callback(url).await?;
+        let server = FixtureServer::start().await;
+        server.route("/", FixtureResponse::html(samples::OPENAI_HOMEPAGE));
+        let html = Scrapper::scrape(&server.url("/")).await?;
+        assert!(html.contains("What can I help with?"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cloudflare_challenge_is_retried() -> Result<()> {
+        let server = FixtureServer::start().await;
+        server.route_sequence(
+            "/",
+            vec![
+                FixtureResponse::html(samples::CLOUDFLARE_CHALLENGE),
+                FixtureResponse::html(samples::OPENAI_HOMEPAGE),
+            ],
+        );
+        let options = ScrapeOptions {
+            challenge_retry_delay: Duration::from_millis(10),
+        };
+        let html = Scrapper::scrape_with_options(&server.url("/"), &options).await?;
         assert!(html.contains("What can I help with?"));
+        assert_eq!(server.paths(), ["/", "/"]);
+        assert_eq!(
+            ScrapeOptions::default().challenge_retry_delay,
+            Duration::from_secs(5)
+        );
         Ok(())
     }
+
+    #[cfg(feature = "live-tests")]
+    mod live {
+        use super::*;
+
+        #[tokio::test]
+        async fn test_books_to_scrape_homepage() -> Result<()> {
+            let url = "https://books.toscrape.com/";
+            assert_contains(url, &["Books to Scrape", "All products"]).await?;
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_specific_book_page() -> Result<()> {
+            let url = "https://books.toscrape.com/catalogue/a-light-in-the-attic_1000/index.html";
+            assert_contains(url, &["A Light in the Attic", "In stock"]).await?;
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_quotes_to_scrape_homepage() -> Result<()> {
+            let url = "https://quotes.toscrape.com/";
+            assert_contains(url, &["Quotes to Scrape", "Albert Einstein"]).await?;
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_httpbin_headers_and_user_agent() -> Result<()> {
+            let url = "https://httpbin.org/headers";
+            let html = Scrapper::scrape(url).await?;
+            assert!(html.contains("User-Agent"));
+            assert!(html.contains("Mozilla/5.0")); // from our client UA
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_httpbin_cookie_roundtrip() -> Result<()> {
+            // Set a cookie
+            let _ = Scrapper::scrape("https://httpbin.org/cookies/set/testkey/testvalue").await?;
+            // Retrieve cookies
+            let html = Scrapper::scrape("https://httpbin.org/cookies").await?;
+            assert!(html.contains("testkey"));
+            assert!(html.contains("testvalue"));
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_openlibrary_homepage() -> Result<()> {
+            let url = "https://openlibrary.org/";
+            assert_contains(url, &["Open Library", "Borrow"]).await?;
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_wikipedia_main_page() -> Result<()> {
+            let url = "https://en.wikipedia.org/wiki/Main_Page";
+            let html = Scrapper::scrape(url).await?;
+            assert!(html.contains("Wikipedia"));
+            assert!(html.contains("From today"));
+            Ok(())
+        }
+
+        #[tokio::test]
+        async fn test_404_results_in_error() {
+            let url = "https://httpbin.org/status/404";
+            let res = Scrapper::scrape(url).await;
+            assert!(res.is_err());
+        }
+
+        #[tokio::test]
+        async fn test_open_ai() -> Result<()> {
+            let url = "https://openai.com";
+            let html = Scrapper::scrape(url).await?;
+            assert!(html.contains("What can I help with?"));
+            Ok(())
+        }
+    }
 }
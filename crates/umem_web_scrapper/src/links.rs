@@ -0,0 +1,212 @@
+use url::Url;
+
+/// What a crawler needs from a page: where it links to and what it says about itself.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PageLinks {
+    /// Absolute targets of `<a href>`, without `rel="nofollow"` links.
+    pub links: Vec<Url>,
+    pub canonical: Option<Url>,
+    /// `<meta name="robots" content="noindex">`: the page is not to be stored.
+    pub noindex: bool,
+    /// `<meta name="robots" content="nofollow">`: none of its links are to be followed.
+    pub nofollow: bool,
+}
+
+/// Scans the tags of `html`, resolving links against `url` or the page's `<base>`.
+pub(crate) fn scan(html: &str, url: &Url) -> PageLinks {
+    let tags = tags(html);
+    let base = tags
+        .iter()
+        .find(|tag| tag.name == "base")
+        .and_then(|tag| tag.attribute("href"))
+        .and_then(|href| url.join(href).ok())
+        .unwrap_or_else(|| url.clone());
+
+    let mut page = PageLinks::default();
+    for tag in &tags {
+        let rel = tag
+            .attribute("rel")
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let rel = rel.split_whitespace().collect::<Vec<_>>();
+        match tag.name.as_str() {
+            "a" if !rel.contains(&"nofollow") => {
+                if let Some(link) = tag.attribute("href").and_then(|href| base.join(href).ok()) {
+                    page.links.push(link);
+                }
+            }
+            "link" if rel.contains(&"canonical") && page.canonical.is_none() => {
+                page.canonical = tag.attribute("href").and_then(|href| base.join(href).ok());
+            }
+            "meta" => {
+                let name = tag.attribute("name").unwrap_or_default();
+                if name.eq_ignore_ascii_case("robots") {
+                    let content = tag.attribute("content").unwrap_or_default();
+                    for directive in content.split(',').map(str::trim) {
+                        page.noindex |= directive.eq_ignore_ascii_case("noindex")
+                            || directive.eq_ignore_ascii_case("none");
+                        page.nofollow |= directive.eq_ignore_ascii_case("nofollow")
+                            || directive.eq_ignore_ascii_case("none");
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    page
+}
+
+struct Tag {
+    name: String,
+    attributes: Vec<(String, String)>,
+}
+
+impl Tag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim())
+    }
+}
+
+/// Start tags of `html` with lowercased names, skipping comments and the contents of
+/// scripts and styles.
+fn tags(html: &str) -> Vec<Tag> {
+    let lowercase = html.to_ascii_lowercase();
+    let mut tags = Vec::new();
+    let mut position = 0;
+    while let Some(offset) = html[position..].find('<') {
+        let start = position + offset + 1;
+        if html[start..].starts_with("!--") {
+            position = html[start..]
+                .find("-->")
+                .map_or(html.len(), |end| start + end + 3);
+            continue;
+        }
+        let name_length = html[start..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(html.len() - start);
+        if name_length == 0 {
+            position = start;
+            continue;
+        }
+        let name = lowercase[start..start + name_length].to_string();
+        let (attributes, end) = attributes(html, start + name_length);
+        position = end;
+        if name == "script" || name == "style" {
+            position = lowercase[end..]
+                .find(&format!("</{name}"))
+                .map_or(html.len(), |close| end + close);
+        }
+        tags.push(Tag { name, attributes });
+    }
+    tags
+}
+
+/// Reads attributes from `position` to the end of the tag, returning them with the
+/// position after the tag.
+fn attributes(html: &str, mut position: usize) -> (Vec<(String, String)>, usize) {
+    let bytes = html.as_bytes();
+    let is_name_end = |b: u8| b.is_ascii_whitespace() || matches!(b, b'=' | b'>' | b'/');
+    let mut attributes = Vec::new();
+    loop {
+        while position < bytes.len()
+            && (bytes[position].is_ascii_whitespace() || bytes[position] == b'/')
+        {
+            position += 1;
+        }
+        if position >= bytes.len() {
+            return (attributes, bytes.len());
+        }
+        if bytes[position] == b'>' {
+            return (attributes, position + 1);
+        }
+
+        let name_start = position;
+        while position < bytes.len() && !is_name_end(bytes[position]) {
+            position += 1;
+        }
+        let name = html[name_start..position].to_ascii_lowercase();
+        while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+            position += 1;
+        }
+        let mut value = String::new();
+        if position < bytes.len() && bytes[position] == b'=' {
+            position += 1;
+            while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+                position += 1;
+            }
+            let value_start;
+            let value_end;
+            match bytes.get(position) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    value_start = position + 1;
+                    value_end = html[value_start..]
+                        .find(quote as char)
+                        .map_or(html.len(), |end| value_start + end);
+                    position = (value_end + 1).min(html.len());
+                }
+                _ => {
+                    value_start = position;
+                    while position < bytes.len()
+                        && !bytes[position].is_ascii_whitespace()
+                        && bytes[position] != b'>'
+                    {
+                        position += 1;
+                    }
+                    value_end = position;
+                }
+            }
+            value = unescape(&html[value_start..value_end]);
+        }
+        attributes.push((name, value));
+    }
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_are_resolved_and_filtered() {
+        let html = r#"<html><head>
+            <base href="/docs/">
+            <link rel="canonical" href="https://example.com/docs/intro">
+            <meta name="robots" content="noindex">
+            <script>var html = '<a href="/from-script">';</script>
+            </head><body>
+            <!-- <a href="/commented-out"> -->
+            <a href="guide.html?a=1&amp;b=2">Guide</a>
+            <a class=nav href=/about>About</a>
+            <a href='https://other.example/'>Elsewhere</a>
+            <a rel="nofollow" href="/login">Log in</a>
+            <a name="anchor-only">Here</a>
+            </body></html>"#;
+        let url = Url::parse("https://example.com/docs/intro?ref=home").unwrap();
+        let page = scan(html, &url);
+        assert_eq!(
+            page.links.iter().map(Url::as_str).collect::<Vec<_>>(),
+            vec![
+                "https://example.com/docs/guide.html?a=1&b=2",
+                "https://example.com/about",
+                "https://other.example/",
+            ]
+        );
+        assert_eq!(
+            page.canonical.as_ref().map(Url::as_str),
+            Some("https://example.com/docs/intro")
+        );
+        assert!(page.noindex);
+        assert!(!page.nofollow);
+    }
+}
@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+/// The rules of a robots.txt file that apply to one user agent, after RFC 9309.
+#[derive(Clone, Debug, Default)]
+pub struct Robots {
+    rules: Vec<Rule>,
+    crawl_delay: Option<Duration>,
+    sitemaps: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    allow: bool,
+    pattern: String,
+}
+
+impl Robots {
+    /// Parses `text` for `user_agent`. Groups naming the product token of `user_agent`,
+    /// the part before the first `/`, take precedence over `*` groups.
+    pub fn parse(text: &str, user_agent: &str) -> Self {
+        let token = user_agent
+            .split(['/', ' '])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let mut specific = Self::default();
+        let mut wildcard = Self::default();
+        let mut sitemaps = Vec::new();
+        // Agents of the group being read, and whether its rules have started.
+        let mut agents: Vec<String> = Vec::new();
+        let mut in_rules = false;
+
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if in_rules {
+                        agents.clear();
+                        in_rules = false;
+                    }
+                    agents.push(value.to_ascii_lowercase());
+                }
+                "sitemap" => sitemaps.push(value.to_string()),
+                key => {
+                    in_rules = true;
+                    for agent in &agents {
+                        let group = if *agent == token {
+                            &mut specific
+                        } else if agent == "*" {
+                            &mut wildcard
+                        } else {
+                            continue;
+                        };
+                        match key {
+                            "allow" | "disallow" if !value.is_empty() => group.rules.push(Rule {
+                                allow: key == "allow",
+                                pattern: value.to_string(),
+                            }),
+                            "crawl-delay" => {
+                                group.crawl_delay = value
+                                    .parse::<f64>()
+                                    .ok()
+                                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok());
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+        }
+
+        let has_specific = !specific.rules.is_empty() || specific.crawl_delay.is_some();
+        let mut robots = if has_specific { specific } else { wildcard };
+        robots.sitemaps = sitemaps;
+        robots
+    }
+
+    /// Rules for a site whose robots.txt does not exist.
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Rules for a site whose robots.txt could not be fetched.
+    pub fn disallow_all() -> Self {
+        Self {
+            rules: vec![Rule {
+                allow: false,
+                pattern: "/".to_string(),
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Whether `path`, with its query, may be fetched. The longest matching rule
+    /// decides, and `Allow` wins over an equally long `Disallow`.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| matches(&rule.pattern, path))
+            .max_by_key(|rule| (rule.pattern.len(), rule.allow))
+            .is_none_or(|rule| rule.allow)
+    }
+
+    pub fn crawl_delay(&self) -> Option<Duration> {
+        self.crawl_delay
+    }
+
+    /// Sitemap URLs listed anywhere in the file.
+    pub fn sitemaps(&self) -> &[String] {
+        &self.sitemaps
+    }
+}
+
+/// Matches a path pattern where `*` stands for any characters and a trailing `$`
+/// anchors the end.
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i + 1 == parts.len() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "\
+User-agent: *
+Disallow: /private/
+Allow: /private/open
+Crawl-delay: 2
+
+# Our own rules replace the ones for everyone.
+User-agent: OtherBot
+User-agent: umem-crawler
+Disallow: /search
+Disallow: /*.pdf$
+Allow: /search/help
+Crawl-delay: 0.5
+
+Sitemap: https://example.com/sitemap.xml
+";
+
+    #[test]
+    fn test_longest_match_decides() {
+        let robots = Robots::parse(ROBOTS, "umem-crawler/0.1");
+        assert!(!robots.is_allowed("/search?q=x"));
+        assert!(robots.is_allowed("/search/help"));
+        assert!(!robots.is_allowed("/files/report.pdf"));
+        assert!(robots.is_allowed("/files/report.pdf?page=2"));
+        assert!(robots.is_allowed("/private/anything"));
+        assert_eq!(robots.crawl_delay(), Some(Duration::from_millis(500)));
+        assert_eq!(robots.sitemaps(), ["https://example.com/sitemap.xml"]);
+    }
+
+    #[test]
+    fn test_other_agents_fall_back_to_the_wildcard_group() {
+        let robots = Robots::parse(ROBOTS, "SomeBot/2.0");
+        assert!(!robots.is_allowed("/private/secret"));
+        assert!(robots.is_allowed("/private/open/page"));
+        assert!(robots.is_allowed("/search"));
+        assert_eq!(robots.crawl_delay(), Some(Duration::from_secs(2)));
+
+        assert!(Robots::allow_all().is_allowed("/anything"));
+        assert!(!Robots::disallow_all().is_allowed("/"));
+    }
+
+    #[test]
+    fn test_unusable_crawl_delays_are_ignored() {
+        for delay in ["-1", "NaN", "inf", "1e300", "soon"] {
+            let robots = Robots::parse(&format!("User-agent: *\nCrawl-delay: {delay}\n"), "bot");
+            assert_eq!(robots.crawl_delay(), None, "{delay}");
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{bail, Context, Result};
+use std::time::Duration;
+use umem_controller::MemoryController;
+use umem_proto_generated::generated;
+use umem_web_scrapper::{CrawlOptions, CrawlScope, Crawler};
+
+const API_KEY_USAGE: &str = "usage:
+    umem api-key create <user_id> <name> <scope>...
+    umem api-key list [user_id]
+    umem api-key revoke <key_id>
+
+scopes: memories:read memories:write memories:delete admin";
+
+const CRAWL_USAGE: &str = "usage:
+    umem crawl <user_id> <url> [--max-pages N] [--max-depth N] [--scope prefix|host|domain]
+               [--delay-ms N] [--max-crawl-delay-ms N] [--tag TAG]...
+
+<url> is the page to start from, or a sitemap when it ends in .xml.";
+
+const REFRESH_USAGE: &str = "usage:
+    umem refresh [user_id]
+
+Re-fetches the web pages stored for user_id, or for every user, and updates those
+that changed.";
+
+pub fn run_api_key_command(args: &[String]) -> Result<()> {
+    let store = umem_auth::api_key_store()?;
+
+    match args
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        ["create", user_id, name, scopes @ ..] => {
+            let scopes = scopes.iter().map(|scope| scope.to_string()).collect();
+            let (api_key, plaintext) = store.create(user_id, name, scopes)?;
+            println!(
+                "Created API key {} for user {}",
+                api_key.id, api_key.user_id
+            );
+            println!("{}", plaintext);
+            println!("Store this key now, it cannot be shown again.");
+        }
+        ["list", rest @ ..] if rest.len() <= 1 => {
+            for api_key in store.list(rest.first().copied())? {
+                println!(
+                    "{}\t{}\t{}\t[{}]\t{}",
+                    api_key.id,
+                    api_key.user_id,
+                    api_key.name,
+                    api_key.scopes.join(","),
+                    if api_key.is_revoked() {
+                        "revoked"
+                    } else {
+                        "active"
+                    },
+                );
+            }
+        }
+        ["revoke", id] => {
+            let api_key = store.revoke(id)?;
+            println!(
+                "Revoked API key {} for user {}",
+                api_key.id, api_key.user_id
+            );
+        }
+        _ => bail!(API_KEY_USAGE),
+    }
+
+    Ok(())
+}
+
+/// Crawls a site and stores every page it yields as a document of `user_id`.
+pub async fn run_crawl_command(args: &[String]) -> Result<()> {
+    let [user_id, seed, flags @ ..] = args else {
+        bail!(CRAWL_USAGE);
+    };
+    let mut options = CrawlOptions::default();
+    let mut tags = Vec::new();
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        let value = flags.next().context(CRAWL_USAGE)?;
+        match flag.as_str() {
+            "--max-pages" => options.max_pages = value.parse()?,
+            "--max-depth" => options.max_depth = value.parse()?,
+            "--delay-ms" => options.delay = Duration::from_millis(value.parse()?),
+            "--max-crawl-delay-ms" => {
+                options.max_crawl_delay = Duration::from_millis(value.parse()?)
+            }
+            "--scope" => {
+                options.scope = match value.as_str() {
+                    "prefix" => CrawlScope::Prefix,
+                    "host" => CrawlScope::Host,
+                    "domain" => CrawlScope::Domain,
+                    _ => bail!(CRAWL_USAGE),
+                }
+            }
+            "--tag" => tags.push(value.clone()),
+            _ => bail!(CRAWL_USAGE),
+        }
+    }
+
+    let mut crawler = Crawler::new(seed, options)?;
+    let (mut stored, mut failed) = (0, 0);
+    while let Some(page) = crawler.next_page().await {
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                failed += 1;
+                eprintln!("Skipped: {e:#}");
+                continue;
+            }
+        };
+        let parameters = generated::IngestUrlParameters {
+            user_id: user_id.clone(),
+            url: page.canonical_url.to_string(),
+            tags: tags.clone(),
+            ..Default::default()
+        };
+        match MemoryController::ingest_html(parameters, &page.html).await {
+            Ok(response) => {
+                stored += 1;
+                let chunks = response.document.map_or(0, |document| document.chunk_count);
+                println!("{}\t{} chunks", page.canonical_url, chunks);
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!(
+                    "Skipped {}: {:#}",
+                    page.canonical_url,
+                    anyhow::Error::from(e)
+                );
+            }
+        }
+    }
+    println!("Stored {stored} pages, skipped {failed}");
+
+    Ok(())
+}
+
+/// Re-ingests the stored web pages of one user or of all users.
+pub async fn run_refresh_command(args: &[String]) -> Result<()> {
+    let user_id = match args {
+        [] => "",
+        [user_id] => user_id.as_str(),
+        _ => bail!(REFRESH_USAGE),
+    };
+
+    let (mut updated, mut unchanged, mut failed) = (0, 0, 0);
+    for (url, result) in MemoryController::refresh_urls(user_id).await? {
+        match result {
+            Ok(response) if response.unchanged => unchanged += 1,
+            Ok(response) => {
+                updated += 1;
+                let chunks = response.document.map_or(0, |document| document.chunk_count);
+                println!(
+                    "{url}\t{chunks} chunks, {} embedded",
+                    response.embedded_chunks
+                );
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("Skipped {url}: {:#}", anyhow::Error::from(e));
+            }
+        }
+    }
+    println!("Updated {updated} pages, {unchanged} unchanged, skipped {failed}");
+
+    Ok(())
+}
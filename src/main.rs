@@ -1,19 +1,71 @@
 use anyhow::Result;
 use dotenv::dotenv;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use umem_auth::{OidcConfig, TokenValidator};
+use umem_controller::MemoryController;
 use umem_grpc_server::MemoryServiceGrpc;
 
+mod cli;
+mod shutdown;
 mod tracing;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenv().ok();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.first().map(String::as_str) == Some("api-key") {
+        return cli::run_api_key_command(&args[1..]);
+    }
+    if args.first().map(String::as_str) == Some("crawl") {
+        return cli::run_crawl_command(&args[1..]).await;
+    }
+    if args.first().map(String::as_str) == Some("refresh") {
+        return cli::run_refresh_command(&args[1..]).await;
+    }
+
     let _guard = tracing::init_tracing()?;
 
-    let mcp_handle = tokio::spawn(async move { umem_mcp::run_server().await });
-    let grpc_handle =
-        tokio::spawn(async move { MemoryServiceGrpc::run_server("0.0.0.0:5050").await });
+    if let Err(e) = umem_auth::spawn_api_key_reload_task() {
+        ::tracing::warn!("API keys are disabled: {:#}", e);
+    }
+    MemoryController::spawn_embedding_coalescer();
+
+    let (validator, provider_metadata) = TokenValidator::discover(OidcConfig::from_env()?).await?;
+    let validator = Arc::new(validator);
+
+    let shutdown = CancellationToken::new();
+
+    let mcp_validator = Arc::clone(&validator);
+    let mcp_shutdown = shutdown.clone();
+    let mcp_handle = tokio::spawn(async move {
+        umem_mcp::run_server(mcp_validator, provider_metadata, mcp_shutdown).await
+    });
+    let grpc_shutdown = shutdown.clone();
+    let grpc_handle = tokio::spawn(async move {
+        MemoryServiceGrpc::run_server("0.0.0.0:5050", validator, grpc_shutdown).await
+    });
+
+    let servers = async { tokio::try_join!(mcp_handle, grpc_handle) };
+    tokio::pin!(servers);
 
-    let _ = tokio::try_join!(mcp_handle, grpc_handle)?;
+    tokio::select! {
+        result = &mut servers => {
+            let _ = result?;
+        }
+        _ = shutdown::signal() => {
+            let timeout = shutdown::timeout_from_env();
+            ::tracing::info!("Shutdown signal received, draining for up to {:?}", timeout);
+            shutdown.cancel();
+            match tokio::time::timeout(timeout, &mut servers).await {
+                Ok(result) => {
+                    let _ = result?;
+                }
+                Err(_) => ::tracing::warn!("Graceful shutdown timed out, exiting"),
+            }
+        }
+    }
 
     Ok(())
 }